@@ -0,0 +1,490 @@
+use std::{cmp::Ordering, fmt::Write};
+
+use bevy::prelude::*;
+
+use crate::{
+    ControlPanelUi, CreateBaseUi, MainMenuItem, MainMenuUI, MenuName,
+    boundary_boxes::BoundaryBox,
+    factory_world::*,
+    linear_program::LpError,
+    plan_compare::signed,
+    rational::Rational,
+    solver::{
+        ByproductPolicies, ObjectiveWeights, ProductionProblem, ProductionSolution,
+        SolverObjective, canvas_problem, solve_production, solve_with_recipes,
+    },
+};
+
+pub fn build(app: &mut App) {
+    app.init_resource::<PendingRanking>();
+
+    app.add_systems(Startup, create_alternates_ui.after(CreateBaseUi));
+    app.add_systems(Update, (start_ranking, step_ranking).chain());
+}
+
+/// How a plan changes when a locked recipe is unlocked.
+pub struct AlternateGain {
+    pub recipe_id: RecipeId,
+    /// The weighted objective with the recipe unlocked.
+    pub objective: Rational,
+    /// How much better the plan gets, `None` when there's no plan without the recipe.
+    pub saved: Option<PlanSavings>,
+}
+
+impl AlternateGain {
+    /// Whether unlocking the recipe is what makes a plan possible at all.
+    pub fn enables_plan(&self) -> bool {
+        self.saved.is_none()
+    }
+}
+
+pub struct PlanSavings {
+    /// How much lower the weighted objective gets.
+    pub objective: Rational,
+    /// Raw resources per minute, apart from power.
+    pub raw: Rational,
+    /// MW.
+    pub power: Rational,
+    pub buildings: Rational,
+}
+
+fn raw_input_total(solution: &ProductionSolution, world: &FactoryWorld) -> Rational {
+    solution
+        .raw_inputs
+        .iter()
+        .filter(|&&(resource_id, _)| world.get_resource_name(resource_id) != Some(POWER_RESOURCE))
+        .map(|&(_, rate)| rate)
+        .sum()
+}
+
+/// Ranks the locked recipes one solve at a time, so a long ranking doesn't hold up a frame.
+pub struct AlternateRanking {
+    problem: ProductionProblem,
+    policies: ByproductPolicies,
+    weights: ObjectiveWeights,
+    /// The plan with the recipes already unlocked, `None` if there isn't one.
+    base: Option<ProductionSolution>,
+    pending: Vec<RecipeId>,
+    total: usize,
+    gains: Vec<AlternateGain>,
+}
+
+impl AlternateRanking {
+    /// Solves the base plan, an infeasible one still ranks the recipes that would make it feasible.
+    pub fn new(
+        problem: ProductionProblem,
+        policies: ByproductPolicies,
+        weights: ObjectiveWeights,
+        world: &FactoryWorld,
+    ) -> Result<Self, LpError> {
+        let base = match solve_production(&problem, &policies, &weights, world) {
+            Ok(base) => Some(base),
+            Err(LpError::Infeasible) => None,
+            Err(err) => return Err(err),
+        };
+
+        let pending: Vec<RecipeId> = world
+            .iter_recipes()
+            .map(|(recipe_id, _)| recipe_id)
+            .filter(|&recipe_id| world.is_recipe_locked(recipe_id))
+            .collect();
+
+        Ok(AlternateRanking {
+            problem,
+            policies,
+            weights,
+            base,
+            total: pending.len(),
+            pending,
+            gains: Vec::new(),
+        })
+    }
+
+    pub fn base(&self) -> Option<&ProductionSolution> {
+        self.base.as_ref()
+    }
+
+    /// How many locked recipes have been solved for, out of how many.
+    pub fn progress(&self) -> (usize, usize) {
+        (self.total - self.pending.len(), self.total)
+    }
+
+    /// Solves with the next locked recipe unlocked, returns false once none are left.
+    ///
+    /// Recipes removed or unlocked since the ranking started are skipped.
+    pub fn step(&mut self, world: &FactoryWorld) -> bool {
+        let Some(locked_id) = self.pending.pop() else {
+            return false;
+        };
+
+        if !world.is_recipe_locked(locked_id) {
+            return true;
+        }
+
+        let Ok(solution) = solve_with_recipes(
+            &self.problem,
+            &self.policies,
+            &self.weights,
+            world,
+            |recipe_id| recipe_id == locked_id || !world.is_recipe_locked(recipe_id),
+        ) else {
+            return true;
+        };
+
+        let saved = match &self.base {
+            Some(base) => {
+                let objective = base.objective - solution.objective;
+
+                if !objective.is_positive() {
+                    return true;
+                }
+
+                Some(PlanSavings {
+                    objective,
+                    raw: raw_input_total(base, world) - raw_input_total(&solution, world),
+                    power: base.power_used - solution.power_used,
+                    buildings: base.building_count - solution.building_count,
+                })
+            }
+            None => None,
+        };
+
+        self.gains.push(AlternateGain {
+            recipe_id: locked_id,
+            objective: solution.objective,
+            saved,
+        });
+
+        true
+    }
+
+    /// The recipes that make a plan possible come first, cheapest plan first,
+    /// then the ones that improve it, biggest saving first.
+    pub fn finish(
+        mut self,
+        world: &FactoryWorld,
+    ) -> (Option<ProductionSolution>, Vec<AlternateGain>) {
+        let recipe_name = |recipe_id: RecipeId| {
+            world
+                .get_recipe(recipe_id)
+                .map_or("", |recipe| recipe.name.as_str())
+        };
+
+        self.gains.sort_by(|a, b| {
+            let order = match (&a.saved, &b.saved) {
+                (None, None) => a.objective.cmp(&b.objective),
+                (None, Some(_)) => Ordering::Less,
+                (Some(_), None) => Ordering::Greater,
+                (Some(a_saved), Some(b_saved)) => b_saved.objective.cmp(&a_saved.objective),
+            };
+
+            order.then_with(|| recipe_name(a.recipe_id).cmp(recipe_name(b.recipe_id)))
+        });
+
+        (self.base, self.gains)
+    }
+}
+
+/// Solves again with each locked recipe unlocked on its own, and ranks the ones that
+/// make the plan possible or improve the objective, best first.
+pub fn rank_alternates(
+    problem: ProductionProblem,
+    policies: &ByproductPolicies,
+    objective: &SolverObjective,
+    world: &FactoryWorld,
+) -> Result<(Option<ProductionSolution>, Vec<AlternateGain>), LpError> {
+    let mut ranking =
+        AlternateRanking::new(problem, policies.clone(), objective.weights.clone(), world)?;
+
+    while ranking.step(world) {}
+
+    Ok(ranking.finish(world))
+}
+
+/// The ranking in progress, if any.
+#[derive(Resource, Default)]
+struct PendingRanking(Option<AlternateRanking>);
+
+#[derive(Resource)]
+struct AlternatesUi {
+    report_text_entity: Entity,
+}
+
+#[derive(Component)]
+#[require(Button)]
+struct RankAlternatesButton;
+
+fn create_alternates_ui(
+    mut commands: Commands,
+    main_menu: Res<MainMenuUI>,
+    control_panel: Res<ControlPanelUi>,
+) {
+    let sub_menu_entity = commands
+        .spawn((Node {
+            display: Display::None,
+            flex_direction: FlexDirection::Column,
+            ..default()
+        },))
+        .set_parent(control_panel.control_panel_entity)
+        .id();
+
+    commands
+        .spawn((
+            MainMenuItem {
+                sub_menu_entity,
+                menu: MenuName::Alternates,
+            },
+            Text::new("Alternates"),
+            Node {
+                width: Val::Percent(100.0),
+                ..default()
+            },
+        ))
+        .set_parent(main_menu.main_menu_entity);
+
+    commands
+        .spawn(Text::new(
+            "Ranks locked recipes by how much unlocking them improves the solver's plan",
+        ))
+        .set_parent(sub_menu_entity);
+
+    commands
+        .spawn((RankAlternatesButton, Text::new("Rank Locked Recipes")))
+        .set_parent(sub_menu_entity);
+
+    let report_text_entity = commands
+        .spawn(Text::default())
+        .set_parent(sub_menu_entity)
+        .id();
+
+    commands.insert_resource(AlternatesUi { report_text_entity });
+}
+
+#[allow(clippy::too_many_arguments)]
+fn start_ranking(
+    button_q: Query<&Interaction, (Changed<Interaction>, With<RankAlternatesButton>)>,
+    boundary_box_q: Query<&BoundaryBox>,
+    alternates_ui: Res<AlternatesUi>,
+    mut text_q: Query<&mut Text>,
+    policies: Res<ByproductPolicies>,
+    objective: Res<SolverObjective>,
+    world: Res<FactoryWorld>,
+    mut pending: ResMut<PendingRanking>,
+) {
+    if !button_q
+        .iter()
+        .any(|interaction| *interaction == Interaction::Pressed)
+    {
+        return;
+    }
+
+    let mut report_text = text_q
+        .get_mut(alternates_ui.report_text_entity)
+        .expect("Should be a text");
+
+    let problem = canvas_problem(boundary_box_q.iter());
+
+    if problem.targets.is_empty() {
+        report_text.0 = "Add sink boxes for what the plan should make".into();
+        pending.0 = None;
+        return;
+    }
+
+    match AlternateRanking::new(
+        problem,
+        policies.clone(),
+        objective.weights.clone(),
+        world.as_ref(),
+    ) {
+        Ok(ranking) => pending.0 = Some(ranking),
+        Err(err) => {
+            report_text.0 = err.to_string();
+            pending.0 = None;
+        }
+    }
+}
+
+/// Solves for one locked recipe a frame, and shows the ranking once they're all done.
+fn step_ranking(
+    alternates_ui: Res<AlternatesUi>,
+    mut text_q: Query<&mut Text>,
+    world: Res<FactoryWorld>,
+    mut pending: ResMut<PendingRanking>,
+) {
+    let Some(ranking) = pending.0.as_mut() else {
+        return;
+    };
+
+    let mut report_text = text_q
+        .get_mut(alternates_ui.report_text_entity)
+        .expect("Should be a text");
+
+    if ranking.step(world.as_ref()) {
+        let (solved, total) = ranking.progress();
+        report_text.0 = format!("Ranking locked recipes, {solved} of {total} solved");
+        return;
+    }
+
+    let (base, gains) = pending
+        .0
+        .take()
+        .expect("Should be ranking")
+        .finish(world.as_ref());
+
+    let mut report = match &base {
+        Some(base) => format!("Objective with the unlocked recipes: {}\n", base.objective),
+        None => "No plan meets all the constraints with the unlocked recipes\n".into(),
+    };
+
+    if gains.is_empty() {
+        report.push_str(if base.is_some() {
+            "Unlocking any one recipe wouldn't improve the plan"
+        } else {
+            "Unlocking any one recipe wouldn't make a plan possible"
+        });
+    }
+
+    for (rank, gain) in gains.iter().enumerate() {
+        let recipe_name = world
+            .get_recipe(gain.recipe_id)
+            .map_or("?", |recipe| recipe.name.as_str());
+
+        match &gain.saved {
+            Some(saved) => writeln!(
+                report,
+                "{}. {}\n  objective {}, raw {}/min, power {} MW, buildings {}",
+                rank + 1,
+                recipe_name,
+                signed(-saved.objective),
+                signed(-saved.raw),
+                signed(-saved.power),
+                signed(-saved.buildings)
+            ),
+            None => writeln!(
+                report,
+                "{}. {}\n  makes a plan possible, objective {}",
+                rank + 1,
+                recipe_name,
+                gain.objective
+            ),
+        }
+        .unwrap();
+    }
+
+    report_text.0 = report;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        recipe_json::{LoadOptions, load_recipes},
+        solver::{ObjectiveMetric, ObjectivePreset},
+    };
+    use std::collections::HashMap;
+
+    const REFINERY: &str = r#"{"recipes": [
+        {"name": "Refine", "rates": [["Oil", -30], ["Fuel", 20], ["Residue", 10]]},
+        {"name": "Recycle", "rates": [["Residue", -1000], ["Fuel", 1]]}
+    ]}"#;
+
+    #[test]
+    fn recipes_that_make_a_plan_possible_are_ranked() {
+        let mut world = FactoryWorld::default();
+        load_recipes(&mut world, REFINERY, LoadOptions::default()).unwrap();
+
+        let fuel = world.get_resource_id("Fuel");
+        let residue = world.get_resource_id("Residue");
+        let refine = world.get_recipe_id("Refine").unwrap();
+        world.set_recipe_locked(refine, true);
+
+        // Without Refine there's no residue to recycle into fuel.
+        let problem = ProductionProblem {
+            targets: HashMap::from([(fuel, Rational::from_integer(20))]),
+            raw_limits: HashMap::from([(residue, Rational::ZERO)]),
+        };
+        let objective = SolverObjective {
+            preset: ObjectivePreset::FewestBuildings,
+            weights: ObjectiveWeights::only(ObjectiveMetric::Buildings),
+        };
+
+        let (base, gains) =
+            rank_alternates(problem, &ByproductPolicies::default(), &objective, &world).unwrap();
+
+        assert!(base.is_none());
+        assert_eq!(gains.len(), 1);
+        assert_eq!(gains[0].recipe_id, refine);
+        assert!(gains[0].enables_plan());
+        assert_eq!(gains[0].objective, Rational::ONE);
+    }
+
+    #[test]
+    fn improving_recipes_are_ranked_by_saving() {
+        let mut world = FactoryWorld::default();
+        load_recipes(
+            &mut world,
+            r#"{"recipes": [
+                {"name": "Plate", "rates": [["Ore", -60], ["Mega Joule", -240], ["Plate", 20]]},
+                {"name": "Cast Plate", "rates": [["Ore", -40], ["Mega Joule", -600], ["Plate", 40]]},
+                {"name": "Pressed Plate", "rates": [["Ore", -30], ["Plate", 30]]},
+                {"name": "Slow Plate", "rates": [["Ore", -10], ["Plate", 10]]}
+            ]}"#,
+            LoadOptions::default(),
+        )
+        .unwrap();
+
+        let plate = world.get_resource_id("Plate");
+        let locked: Vec<RecipeId> = ["Cast Plate", "Pressed Plate", "Slow Plate"]
+            .into_iter()
+            .map(|name| world.get_recipe_id(name).unwrap())
+            .collect();
+
+        for &recipe_id in locked.iter() {
+            world.set_recipe_locked(recipe_id, true);
+        }
+
+        let problem = ProductionProblem {
+            targets: HashMap::from([(plate, Rational::from_integer(60))]),
+            raw_limits: HashMap::new(),
+        };
+        let objective = SolverObjective {
+            preset: ObjectivePreset::FewestBuildings,
+            weights: ObjectiveWeights::only(ObjectiveMetric::Buildings),
+        };
+
+        let (base, gains) =
+            rank_alternates(problem, &ByproductPolicies::default(), &objective, &world).unwrap();
+
+        assert_eq!(base.unwrap().objective, Rational::from_integer(3));
+
+        // Slow Plate needs more buildings than the base plan, so it isn't listed
+        let ranked: Vec<RecipeId> = gains.iter().map(|gain| gain.recipe_id).collect();
+        assert_eq!(ranked, vec![locked[0], locked[1]]);
+
+        let savings = |index: usize| {
+            let saved = gains[index].saved.as_ref().unwrap();
+            (saved.objective, saved.raw, saved.power, saved.buildings)
+        };
+
+        // Cast Plate: 1.5 machines on 60 ore and 15 MW, against 3 on 180 ore and 12 MW
+        assert_eq!(
+            savings(0),
+            (
+                Rational::new(3, 2),
+                Rational::from_integer(120),
+                Rational::from_integer(-3),
+                Rational::new(3, 2)
+            )
+        );
+        // Pressed Plate: 2 machines on 60 ore and no power
+        assert_eq!(
+            savings(1),
+            (
+                Rational::ONE,
+                Rational::from_integer(120),
+                Rational::from_integer(12),
+                Rational::ONE
+            )
+        );
+    }
+}
@@ -0,0 +1,920 @@
+use bevy::{
+    prelude::*,
+    tasks::{IoTaskPool, Task, block_on, poll_once},
+    utils::HashMap,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ControlPanelUi, CreateBaseUi, MainMenuItem, MainMenuUI, MenuName,
+    boundary_boxes::{BoundaryBox, BoundaryKind, spawn_boundary_box},
+    box_groups::BoxGroup,
+    box_links::{BoxLink, spawn_link},
+    chain_solve::LinkShare,
+    drag_plane::{DragBox, DragBoxPlane, PlaneScroll, SelectedBox},
+    factory_world::*,
+    keymap::{AppAction, action_sent},
+    logistics::{LogisticsBox, LogisticsKind, SplitRatio, spawn_logistics_box},
+    rational::Rational,
+    recipe_ui::{RecipeBox, spawn_recipe_box},
+    text_input::TextInput,
+};
+
+pub fn build(app: &mut App) {
+    app.insert_resource(BlueprintLibrary::default());
+    app.insert_resource(SystemClipboard(None));
+
+    app.add_systems(Startup, create_blueprint_ui.after(CreateBaseUi));
+    app.add_systems(
+        Update,
+        (
+            save_selection_as_blueprint,
+            start_open_library_dialogue,
+            finish_open_library_dialogue,
+            start_save_library_dialogue,
+            finish_save_library_dialogue,
+            insert_blueprint_ui,
+            remove_blueprint_ui,
+            update_blueprint_list,
+            copy_selected_boxes,
+            paste_boxes,
+        ),
+    );
+}
+
+/// A set of boxes and the links between them that can be inserted onto a plane.
+///
+/// Recipes are stored by name so a blueprint can be used with any recipe file that has them.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Blueprint {
+    pub name: String,
+    pub boxes: Vec<BlueprintBox>,
+    pub links: Vec<BlueprintLink>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct BlueprintBox {
+    /// Empty for source and sink boxes.
+    #[serde(default)]
+    pub recipe: String,
+    pub multiplier: Rational,
+    /// Position relative to the top left of the blueprint.
+    pub position: (f32, f32),
+    /// Set for source and sink boxes instead of a recipe.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub boundary: Option<BlueprintBoundary>,
+    /// Set for splitters and mergers instead of a recipe.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub logistics: Option<BlueprintLogistics>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct BlueprintBoundary {
+    pub kind: BoundaryKind,
+    pub resource: String,
+    pub rate: Rational,
+    #[serde(default = "uses_purity_default")]
+    pub uses_purity: bool,
+}
+
+fn uses_purity_default() -> bool {
+    true
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct BlueprintLogistics {
+    pub kind: LogisticsKind,
+    pub resource: String,
+}
+
+/// A link between two boxes of a blueprint, by their index in [`Blueprint::boxes`].
+#[derive(Serialize, Deserialize, Clone)]
+pub struct BlueprintLink {
+    pub from: usize,
+    pub to: usize,
+    pub resource: String,
+    /// The [`SplitRatio`] of links leaving smart splitters.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ratio: Option<Rational>,
+    /// The [`LinkShare`] of links given one by chain solving or an applied plan.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub share: Option<Rational>,
+}
+
+#[derive(Serialize, Deserialize, Default, Resource)]
+pub struct BlueprintLibrary {
+    pub blueprints: Vec<Blueprint>,
+}
+
+/// Builds a blueprint from some boxes and the links between them.
+///
+/// Group boxes contribute their members, links to boxes outside the set are left out.
+#[allow(clippy::type_complexity)]
+pub fn capture_blueprint(
+    name: String,
+    box_entities: impl IntoIterator<Item = Entity>,
+    drag_box_q: &Query<(
+        &DragBox,
+        Option<&RecipeBox>,
+        Option<&BoundaryBox>,
+        Option<&LogisticsBox>,
+        Option<&BoxGroup>,
+    )>,
+    link_q: &Query<(&BoxLink, Option<&SplitRatio>, Option<&LinkShare>)>,
+    world: &FactoryWorld,
+) -> Blueprint {
+    let flat_boxes: Vec<(Entity, Vec2, BlueprintBox)> = flatten_boxes(box_entities, drag_box_q)
+        .into_iter()
+        .filter_map(|(box_entity, position)| {
+            let (_, recipe_box, boundary_box, logistics_box, _) =
+                drag_box_q.get(box_entity).ok()?;
+
+            let empty_box = BlueprintBox {
+                recipe: String::new(),
+                multiplier: Rational::ONE,
+                position: (0., 0.),
+                boundary: None,
+                logistics: None,
+            };
+
+            let blueprint_box = if let Some(recipe_box) = recipe_box {
+                BlueprintBox {
+                    recipe: world.get_recipe(recipe_box.recipe_id).ok()?.name.clone(),
+                    multiplier: recipe_box.multiplier,
+                    ..empty_box
+                }
+            } else if let Some(boundary_box) = boundary_box {
+                BlueprintBox {
+                    boundary: Some(BlueprintBoundary {
+                        kind: boundary_box.kind,
+                        resource: world
+                            .get_resource_name(boundary_box.resource_id)?
+                            .to_string(),
+                        rate: boundary_box.rate,
+                        uses_purity: boundary_box.uses_purity,
+                    }),
+                    ..empty_box
+                }
+            } else {
+                let logistics_box = logistics_box?;
+
+                BlueprintBox {
+                    logistics: Some(BlueprintLogistics {
+                        kind: logistics_box.kind,
+                        resource: world
+                            .get_resource_name(logistics_box.resource_id)?
+                            .to_string(),
+                    }),
+                    ..empty_box
+                }
+            };
+
+            Some((box_entity, position, blueprint_box))
+        })
+        .collect();
+
+    let origin = flat_boxes
+        .iter()
+        .fold(Vec2::INFINITY, |origin, &(_, position, _)| {
+            origin.min(position)
+        });
+
+    let box_indices: HashMap<Entity, usize> = flat_boxes
+        .iter()
+        .enumerate()
+        .map(|(index, &(box_entity, ..))| (box_entity, index))
+        .collect();
+
+    let links = link_q
+        .iter()
+        .filter_map(|(link, split_ratio, link_share)| {
+            Some(BlueprintLink {
+                from: *box_indices.get(&link.from_box)?,
+                to: *box_indices.get(&link.to_box)?,
+                resource: world.get_resource_name(link.resource_id)?.to_string(),
+                ratio: split_ratio.map(|split_ratio| split_ratio.0),
+                share: link_share.map(|link_share| link_share.0),
+            })
+        })
+        .collect();
+
+    let boxes = flat_boxes
+        .into_iter()
+        .map(|(_, position, blueprint_box)| {
+            let position = position - origin;
+
+            BlueprintBox {
+                position: (position.x, position.y),
+                ..blueprint_box
+            }
+        })
+        .collect();
+
+    Blueprint { name, boxes, links }
+}
+
+/// The boxes among some boxes and inside any groups among them, with their positions.
+#[allow(clippy::type_complexity)]
+pub fn flatten_boxes(
+    box_entities: impl IntoIterator<Item = Entity>,
+    drag_box_q: &Query<(
+        &DragBox,
+        Option<&RecipeBox>,
+        Option<&BoundaryBox>,
+        Option<&LogisticsBox>,
+        Option<&BoxGroup>,
+    )>,
+) -> Vec<(Entity, Vec2)> {
+    let mut flat_boxes = Vec::new();
+    let mut pending_entities: Vec<Entity> = box_entities.into_iter().collect();
+
+    while let Some(box_entity) = pending_entities.pop() {
+        let Ok((drag_box, .., group)) = drag_box_q.get(box_entity) else {
+            continue;
+        };
+
+        match group {
+            Some(group) => pending_entities.extend(group.members.iter().copied()),
+            None => flat_boxes.push((box_entity, drag_box.position)),
+        }
+    }
+
+    flat_boxes
+}
+
+/// Recipes and resources a blueprint names that aren't in the world.
+#[derive(Default)]
+pub struct MissingNames {
+    pub recipes: Vec<String>,
+    pub resources: Vec<String>,
+}
+
+impl MissingNames {
+    pub fn is_empty(&self) -> bool {
+        self.recipes.is_empty() && self.resources.is_empty()
+    }
+
+    fn add_recipe(&mut self, recipe_name: &str) {
+        if !self.recipes.iter().any(|name| name == recipe_name) {
+            self.recipes.push(recipe_name.to_string());
+        }
+    }
+
+    fn add_resource(&mut self, resource_name: &str) {
+        if !self.resources.iter().any(|name| name == resource_name) {
+            self.resources.push(resource_name.to_string());
+        }
+    }
+
+    /// Like "missing recipes: A, B, missing resources: C", for status texts.
+    pub fn describe(&self) -> String {
+        let mut parts = Vec::new();
+
+        if !self.recipes.is_empty() {
+            parts.push(format!("missing recipes: {}", self.recipes.join(", ")));
+        }
+
+        if !self.resources.is_empty() {
+            parts.push(format!("missing resources: {}", self.resources.join(", ")));
+        }
+
+        parts.join(", ")
+    }
+}
+
+/// Spawns the boxes and links of a blueprint with its top left at `origin`.
+///
+/// Returns the spawned boxes, and the names of any recipes and resources that aren't in
+/// the world. Boxes and links for missing names are skipped, along with links to skipped boxes.
+pub fn insert_blueprint(
+    commands: &mut Commands,
+    plane_entity: Entity,
+    origin: Vec2,
+    blueprint: &Blueprint,
+    world: &mut FactoryWorld,
+) -> (Vec<Entity>, MissingNames) {
+    let mut missing = MissingNames::default();
+
+    let box_entities: Vec<Option<Entity>> = blueprint
+        .boxes
+        .iter()
+        .map(|blueprint_box| {
+            let position = origin + Vec2::from(blueprint_box.position);
+
+            if let Some(boundary) = &blueprint_box.boundary {
+                let Some(resource_id) = world.find_resource_id(&boundary.resource) else {
+                    missing.add_resource(&boundary.resource);
+                    return None;
+                };
+
+                let boundary_box = BoundaryBox {
+                    kind: boundary.kind,
+                    resource_id,
+                    rate: boundary.rate,
+                    uses_purity: boundary.uses_purity,
+                };
+
+                return Some(spawn_boundary_box(
+                    commands,
+                    plane_entity,
+                    position,
+                    boundary_box,
+                    world,
+                ));
+            }
+
+            if let Some(logistics) = &blueprint_box.logistics {
+                let Some(resource_id) = world.find_resource_id(&logistics.resource) else {
+                    missing.add_resource(&logistics.resource);
+                    return None;
+                };
+
+                return Some(spawn_logistics_box(
+                    commands,
+                    plane_entity,
+                    position,
+                    logistics.kind,
+                    resource_id,
+                    world,
+                ));
+            }
+
+            let Some(recipe_id) = world.get_recipe_id(&blueprint_box.recipe) else {
+                missing.add_recipe(&blueprint_box.recipe);
+                return None;
+            };
+
+            spawn_recipe_box(
+                commands,
+                plane_entity,
+                position,
+                recipe_id,
+                blueprint_box.multiplier,
+                world,
+            )
+            .ok()
+        })
+        .collect();
+
+    for blueprint_link in blueprint.links.iter() {
+        let (Some(Some(from_box)), Some(Some(to_box))) = (
+            box_entities.get(blueprint_link.from),
+            box_entities.get(blueprint_link.to),
+        ) else {
+            continue;
+        };
+
+        let Some(resource_id) = world.find_resource_id(&blueprint_link.resource) else {
+            missing.add_resource(&blueprint_link.resource);
+            continue;
+        };
+
+        let link_entity = spawn_link(
+            commands,
+            plane_entity,
+            BoxLink {
+                from_box: *from_box,
+                to_box: *to_box,
+                resource_id,
+            },
+        );
+
+        if let Some(ratio) = blueprint_link.ratio {
+            commands.entity(link_entity).insert(SplitRatio(ratio));
+        }
+
+        if let Some(share) = blueprint_link.share {
+            commands.entity(link_entity).insert(LinkShare(share));
+        }
+    }
+
+    (box_entities.into_iter().flatten().collect(), missing)
+}
+
+#[derive(Resource)]
+struct BlueprintUi {
+    blueprint_list_entity: Entity,
+    status_text_entity: Entity,
+}
+
+#[derive(Component)]
+#[require(Button)]
+struct SaveSelectionButton {
+    name_input_entity: Entity,
+}
+
+#[derive(Component)]
+#[require(Button)]
+struct OpenLibraryButton;
+
+#[derive(Component)]
+#[require(Button)]
+struct SaveLibraryButton;
+
+#[derive(Component)]
+#[require(Button)]
+struct InsertBlueprintButton {
+    index: usize,
+}
+
+#[derive(Component)]
+#[require(Button)]
+struct RemoveBlueprintButton {
+    index: usize,
+}
+
+fn create_blueprint_ui(
+    mut commands: Commands,
+    main_menu: Res<MainMenuUI>,
+    control_panel: Res<ControlPanelUi>,
+) {
+    let sub_menu_entity = commands
+        .spawn((Node {
+            display: Display::None,
+            flex_direction: FlexDirection::Column,
+            ..default()
+        },))
+        .set_parent(control_panel.control_panel_entity)
+        .id();
+
+    commands
+        .spawn((
+            MainMenuItem {
+                sub_menu_entity,
+                menu: MenuName::Blueprints,
+            },
+            Text::new("Blueprints"),
+            Node {
+                width: Val::Percent(100.0),
+                ..default()
+            },
+        ))
+        .set_parent(main_menu.main_menu_entity);
+
+    commands
+        .spawn((OpenLibraryButton, Text::new("Open Library")))
+        .set_parent(sub_menu_entity);
+
+    commands
+        .spawn((SaveLibraryButton, Text::new("Save Library")))
+        .set_parent(sub_menu_entity);
+
+    let name_input_entity = commands
+        .spawn(TextInput::new("Blueprint name"))
+        .set_parent(sub_menu_entity)
+        .id();
+
+    commands
+        .spawn((
+            SaveSelectionButton { name_input_entity },
+            Text::new("Save Selection"),
+        ))
+        .set_parent(sub_menu_entity);
+
+    let status_text_entity = commands
+        .spawn((
+            Text::default(),
+            Node {
+                max_width: Val::Px(300.),
+                ..default()
+            },
+        ))
+        .set_parent(sub_menu_entity)
+        .id();
+
+    let blueprint_list_entity = commands
+        .spawn((Node {
+            flex_direction: FlexDirection::Column,
+            ..default()
+        },))
+        .set_parent(sub_menu_entity)
+        .id();
+
+    commands.insert_resource(BlueprintUi {
+        blueprint_list_entity,
+        status_text_entity,
+    });
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn save_selection_as_blueprint(
+    button_q: Query<(&Interaction, &SaveSelectionButton), Changed<Interaction>>,
+    mut name_input_q: Query<&mut TextInput>,
+    selected_q: Query<Entity, With<SelectedBox>>,
+    drag_box_q: Query<(
+        &DragBox,
+        Option<&RecipeBox>,
+        Option<&BoundaryBox>,
+        Option<&LogisticsBox>,
+        Option<&BoxGroup>,
+    )>,
+    link_q: Query<(&BoxLink, Option<&SplitRatio>, Option<&LinkShare>)>,
+    world: Res<FactoryWorld>,
+    mut library: ResMut<BlueprintLibrary>,
+    blueprint_ui: Res<BlueprintUi>,
+    mut text_q: Query<&mut Text>,
+) {
+    for (interaction, button) in button_q.iter() {
+        let Interaction::Pressed = interaction else {
+            continue;
+        };
+
+        let mut status_text = text_q
+            .get_mut(blueprint_ui.status_text_entity)
+            .expect("Should be a text");
+
+        if selected_q.is_empty() {
+            status_text.0 = "No boxes selected".into();
+            continue;
+        }
+
+        let mut name_input = name_input_q
+            .get_mut(button.name_input_entity)
+            .expect("Should be a text input");
+
+        let name = match std::mem::take(&mut name_input.value) {
+            name if name.trim().is_empty() => {
+                format!("Blueprint {}", library.blueprints.len() + 1)
+            }
+            name => name,
+        };
+
+        let blueprint = capture_blueprint(
+            name,
+            selected_q.iter(),
+            &drag_box_q,
+            &link_q,
+            world.as_ref(),
+        );
+
+        status_text.0 = format!(
+            "Saved \"{}\" with {} boxes",
+            blueprint.name,
+            blueprint.boxes.len()
+        );
+
+        library.blueprints.push(blueprint);
+    }
+}
+
+#[derive(Component)]
+struct OpenLibraryTask {
+    task: Task<Option<String>>,
+}
+
+fn start_open_library_dialogue(
+    mut commands: Commands,
+    button_q: Query<&Interaction, (Changed<Interaction>, With<OpenLibraryButton>)>,
+) {
+    let Ok(Interaction::Pressed) = button_q.get_single() else {
+        return;
+    };
+
+    commands.spawn(OpenLibraryTask {
+        task: IoTaskPool::get().spawn(open_json_async("Blueprint library")),
+    });
+}
+
+pub async fn open_json_async(filter_name: &'static str) -> Option<String> {
+    let file_handle = rfd::AsyncFileDialog::new()
+        .add_filter(filter_name, &["json"])
+        .pick_file()
+        .await?;
+
+    String::from_utf8(file_handle.read().await).ok()
+}
+
+fn finish_open_library_dialogue(
+    mut commands: Commands,
+    mut task_q: Query<(Entity, &mut OpenLibraryTask)>,
+    mut library: ResMut<BlueprintLibrary>,
+    blueprint_ui: Res<BlueprintUi>,
+    mut text_q: Query<&mut Text>,
+) {
+    for (task_entity, mut task) in task_q.iter_mut() {
+        if task.task.is_finished() {
+            let Some(task_result) = block_on(poll_once(&mut task.task)) else {
+                error!("Expected task to be finished");
+                continue;
+            };
+
+            commands.entity(task_entity).despawn_recursive();
+
+            let Some(json) = task_result else {
+                continue;
+            };
+
+            let mut status_text = text_q
+                .get_mut(blueprint_ui.status_text_entity)
+                .expect("Should be a text");
+
+            match serde_json::from_str::<BlueprintLibrary>(&json) {
+                Ok(opened_library) => {
+                    status_text.0 =
+                        format!("Opened {} blueprints", opened_library.blueprints.len());
+                    library.blueprints.extend(opened_library.blueprints);
+                }
+                Err(err) => {
+                    error!("Invalid blueprint library: {}", err);
+                    status_text.0 = "Invalid blueprint library".into();
+                }
+            }
+        }
+    }
+}
+
+#[derive(Component)]
+struct SaveLibraryTask {
+    task: Task<std::io::Result<bool>>,
+}
+
+fn start_save_library_dialogue(
+    mut commands: Commands,
+    button_q: Query<&Interaction, (Changed<Interaction>, With<SaveLibraryButton>)>,
+    library: Res<BlueprintLibrary>,
+) {
+    let Ok(Interaction::Pressed) = button_q.get_single() else {
+        return;
+    };
+
+    let json = serde_json::to_string_pretty(library.as_ref()).expect("Library should serialize");
+
+    commands.spawn(SaveLibraryTask {
+        task: IoTaskPool::get().spawn(save_json_async(
+            json,
+            "Blueprint library",
+            "blueprints.json",
+        )),
+    });
+}
+
+/// Returns `false` if no file was picked.
+pub async fn save_json_async(
+    json: String,
+    filter_name: &'static str,
+    file_name: &'static str,
+) -> std::io::Result<bool> {
+    let Some(file_handle) = rfd::AsyncFileDialog::new()
+        .add_filter(filter_name, &["json"])
+        .set_file_name(file_name)
+        .save_file()
+        .await
+    else {
+        return Ok(false);
+    };
+
+    file_handle.write(json.as_bytes()).await?;
+
+    Ok(true)
+}
+
+fn finish_save_library_dialogue(
+    mut commands: Commands,
+    mut task_q: Query<(Entity, &mut SaveLibraryTask)>,
+    blueprint_ui: Res<BlueprintUi>,
+    mut text_q: Query<&mut Text>,
+) {
+    for (task_entity, mut task) in task_q.iter_mut() {
+        if task.task.is_finished() {
+            let Some(task_result) = block_on(poll_once(&mut task.task)) else {
+                error!("Expected task to be finished");
+                continue;
+            };
+
+            commands.entity(task_entity).despawn_recursive();
+
+            let mut status_text = text_q
+                .get_mut(blueprint_ui.status_text_entity)
+                .expect("Should be a text");
+
+            match task_result {
+                Ok(true) => status_text.0 = "Saved library".into(),
+                Ok(false) => (),
+                Err(err) => {
+                    error!("Failed to save blueprint library: {}", err);
+                    status_text.0 = "Failed to save library".into();
+                }
+            }
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn insert_blueprint_ui(
+    mut commands: Commands,
+    button_q: Query<(&Interaction, &InsertBlueprintButton), Changed<Interaction>>,
+    library: Res<BlueprintLibrary>,
+    mut world: ResMut<FactoryWorld>,
+    plane_q: Query<Entity, With<DragBoxPlane>>,
+    plane_scroll: Res<PlaneScroll>,
+    blueprint_ui: Res<BlueprintUi>,
+    mut text_q: Query<&mut Text>,
+) {
+    for (interaction, button) in button_q.iter() {
+        let Interaction::Pressed = interaction else {
+            continue;
+        };
+
+        let Some(blueprint) = library.blueprints.get(button.index) else {
+            error!("No blueprint at index {}", button.index);
+            continue;
+        };
+
+        let root_plane_entity = plane_q.get_single().expect("Should be one root plane");
+
+        let (box_entities, missing) = insert_blueprint(
+            &mut commands,
+            root_plane_entity,
+            -plane_scroll.scroll_position,
+            blueprint,
+            world.as_mut(),
+        );
+
+        let mut status_text = text_q
+            .get_mut(blueprint_ui.status_text_entity)
+            .expect("Should be a text");
+
+        status_text.0 = if missing.is_empty() {
+            format!(
+                "Inserted \"{}\" with {} boxes",
+                blueprint.name,
+                box_entities.len()
+            )
+        } else {
+            warn!(
+                "Blueprint \"{}\" has {}",
+                blueprint.name,
+                missing.describe()
+            );
+
+            format!(
+                "Inserted \"{}\" with {} boxes, {}",
+                blueprint.name,
+                box_entities.len(),
+                missing.describe()
+            )
+        };
+    }
+}
+
+fn remove_blueprint_ui(
+    button_q: Query<(&Interaction, &RemoveBlueprintButton), Changed<Interaction>>,
+    mut library: ResMut<BlueprintLibrary>,
+) {
+    for (interaction, button) in button_q.iter() {
+        let Interaction::Pressed = interaction else {
+            continue;
+        };
+
+        if button.index < library.blueprints.len() {
+            library.blueprints.remove(button.index);
+        }
+    }
+}
+
+/// Rebuilds the list of blueprints whenever the library changes.
+fn update_blueprint_list(
+    mut commands: Commands,
+    library: Res<BlueprintLibrary>,
+    blueprint_ui: Res<BlueprintUi>,
+) {
+    if !library.is_changed() {
+        return;
+    }
+
+    commands
+        .entity(blueprint_ui.blueprint_list_entity)
+        .despawn_descendants();
+
+    for (index, blueprint) in library.blueprints.iter().enumerate() {
+        commands
+            .spawn(Node {
+                flex_direction: FlexDirection::Row,
+                ..default()
+            })
+            .with_children(|builder| {
+                builder.spawn((RemoveBlueprintButton { index }, Text::new("X")));
+
+                builder.spawn((InsertBlueprintButton { index }, Text::new(&blueprint.name)));
+            })
+            .set_parent(blueprint_ui.blueprint_list_entity);
+    }
+}
+
+/// How far copies are placed from the boxes they were copied from.
+pub const DUPLICATE_OFFSET: Vec2 = Vec2::new(30., 30.);
+
+/// The system clipboard, opened the first time it's used.
+///
+/// It's kept open because on some platforms copied text is only available while it is.
+#[derive(Resource)]
+struct SystemClipboard(Option<arboard::Clipboard>);
+
+impl SystemClipboard {
+    fn get(&mut self) -> Option<&mut arboard::Clipboard> {
+        if self.0.is_none() {
+            match arboard::Clipboard::new() {
+                Ok(clipboard) => self.0 = Some(clipboard),
+                Err(err) => error!("Couldn't open the clipboard: {}", err),
+            }
+        }
+
+        self.0.as_mut()
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn copy_selected_boxes(
+    mut actions: EventReader<AppAction>,
+    selected_q: Query<Entity, With<SelectedBox>>,
+    drag_box_q: Query<(
+        &DragBox,
+        Option<&RecipeBox>,
+        Option<&BoundaryBox>,
+        Option<&LogisticsBox>,
+        Option<&BoxGroup>,
+    )>,
+    link_q: Query<(&BoxLink, Option<&SplitRatio>, Option<&LinkShare>)>,
+    world: Res<FactoryWorld>,
+    mut clipboard: ResMut<SystemClipboard>,
+) {
+    if !action_sent(&mut actions, AppAction::CopySelected) || selected_q.is_empty() {
+        return;
+    }
+
+    let blueprint = capture_blueprint(
+        "Copied Boxes".into(),
+        selected_q.iter(),
+        &drag_box_q,
+        &link_q,
+        world.as_ref(),
+    );
+
+    let json = serde_json::to_string_pretty(&blueprint).expect("Blueprint should serialize");
+
+    let Some(clipboard) = clipboard.get() else {
+        return;
+    };
+
+    match clipboard.set_text(json) {
+        Ok(()) => info!("Copied {} boxes", blueprint.boxes.len()),
+        Err(err) => error!("Couldn't copy boxes: {}", err),
+    }
+}
+
+/// Pastes a blueprint from the clipboard at the top left of the view, and selects it.
+fn paste_boxes(
+    mut commands: Commands,
+    mut actions: EventReader<AppAction>,
+    selected_q: Query<Entity, With<SelectedBox>>,
+    plane_q: Query<Entity, With<DragBoxPlane>>,
+    plane_scroll: Res<PlaneScroll>,
+    mut world: ResMut<FactoryWorld>,
+    mut clipboard: ResMut<SystemClipboard>,
+) {
+    if !action_sent(&mut actions, AppAction::Paste) {
+        return;
+    }
+
+    let Some(clipboard) = clipboard.get() else {
+        return;
+    };
+
+    let text = match clipboard.get_text() {
+        Ok(text) => text,
+        Err(err) => {
+            info!("Nothing to paste: {}", err);
+            return;
+        }
+    };
+
+    let blueprint = match serde_json::from_str::<Blueprint>(&text) {
+        Ok(blueprint) => blueprint,
+        Err(err) => {
+            info!("Clipboard doesn't hold boxes: {}", err);
+            return;
+        }
+    };
+
+    let root_plane_entity = plane_q.get_single().expect("Should be one root plane");
+
+    let (box_entities, missing) = insert_blueprint(
+        &mut commands,
+        root_plane_entity,
+        -plane_scroll.scroll_position + DUPLICATE_OFFSET,
+        &blueprint,
+        world.as_mut(),
+    );
+
+    if !missing.is_empty() {
+        warn!("Pasted boxes have {}", missing.describe());
+    }
+
+    for selected_entity in selected_q.iter() {
+        commands.entity(selected_entity).remove::<SelectedBox>();
+    }
+
+    for box_entity in box_entities {
+        commands.entity(box_entity).insert(SelectedBox);
+    }
+}
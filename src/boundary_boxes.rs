@@ -0,0 +1,613 @@
+use bevy::{color::palettes::css::*, prelude::*};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ControlPanelUi, CreateBaseUi, MainMenuItem, MainMenuUI, MenuName,
+    drag_plane::{DragBox, DragBoxPlane, PlaneScroll},
+    factory_world::*,
+    rational::Rational,
+    recipe_ui::spawn_box_contents,
+    text_input::{FocusedTextInput, TextInput, TextInputSubmitted},
+};
+
+/// The rate new sink boxes start with, per minute.
+const DEFAULT_SINK_RATE: i128 = 60;
+
+pub fn build(app: &mut App) {
+    app.insert_resource(ExtractorCalculator {
+        extractor_index: 0,
+        purity: Purity::Normal,
+    });
+
+    app.add_systems(Startup, create_boundary_ui.after(CreateBaseUi));
+    app.add_systems(
+        Update,
+        (
+            cycle_calculator_options,
+            update_calculator_result,
+            add_boundary_boxes,
+            cycle_boundary_kinds,
+            submit_boundary_rates,
+            refresh_boundary_boxes,
+        )
+            .chain(),
+    );
+}
+
+/// Where the resources going into a sink box end up.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SinkKind {
+    /// A final product of the factory, that the plan has to make at the box's rate.
+    Output,
+    /// Takes whatever is left over, for points.
+    AwesomeSink,
+    /// Takes whatever is left over, to keep.
+    Storage,
+}
+
+impl SinkKind {
+    /// Whether the sink takes whatever is left over, rather than wanting its rate.
+    pub fn takes_surplus(self) -> bool {
+        !matches!(self, SinkKind::Output)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            SinkKind::Output => "Output",
+            SinkKind::AwesomeSink => "AWESOME Sink",
+            SinkKind::Storage => "Storage",
+        }
+    }
+
+    fn next(self) -> SinkKind {
+        match self {
+            SinkKind::Output => SinkKind::AwesomeSink,
+            SinkKind::AwesomeSink => SinkKind::Storage,
+            SinkKind::Storage => SinkKind::Output,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BoundaryKind {
+    /// An extractor bringing a raw resource into the factory.
+    Source(Purity),
+    /// Somewhere a resource leaves the factory on purpose.
+    Sink(SinkKind),
+}
+
+impl BoundaryKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            BoundaryKind::Source(purity) => purity.name(),
+            BoundaryKind::Sink(sink_kind) => sink_kind.name(),
+        }
+    }
+
+    fn next(self) -> BoundaryKind {
+        match self {
+            BoundaryKind::Source(purity) => BoundaryKind::Source(purity.next()),
+            BoundaryKind::Sink(sink_kind) => BoundaryKind::Sink(sink_kind.next()),
+        }
+    }
+}
+
+/// A drag box at the edge of the factory, that only makes or only takes a single resource.
+#[derive(Component, Clone, Copy)]
+pub struct BoundaryBox {
+    pub kind: BoundaryKind,
+    pub resource_id: ResourceId,
+    /// Per minute, on a normal node for sources.
+    pub rate: Rational,
+    /// Whether the purity of the node matters to a source.
+    /// Sources from water extractors make the same anywhere, so they stay on normal nodes.
+    pub uses_purity: bool,
+}
+
+impl BoundaryBox {
+    pub fn is_source(&self) -> bool {
+        matches!(self.kind, BoundaryKind::Source(_))
+    }
+
+    /// Whether the box is an output the plan has to make.
+    pub fn is_output(&self) -> bool {
+        matches!(self.kind, BoundaryKind::Sink(SinkKind::Output))
+    }
+
+    /// The kind of sink, if it's one that takes whatever is left over.
+    pub fn surplus_sink(&self) -> Option<SinkKind> {
+        match self.kind {
+            BoundaryKind::Sink(sink_kind) if sink_kind.takes_surplus() => Some(sink_kind),
+            _ => None,
+        }
+    }
+
+    /// The next kind to switch to, which keeps the purity of sources that don't use it.
+    fn next_kind(&self) -> BoundaryKind {
+        match self.kind {
+            BoundaryKind::Source(_) if !self.uses_purity => self.kind,
+            kind => kind.next(),
+        }
+    }
+
+    fn kind_name(&self) -> &'static str {
+        match self.kind {
+            BoundaryKind::Source(_) if !self.uses_purity => "Any purity",
+            kind => kind.name(),
+        }
+    }
+
+    /// What the box makes or takes per minute, with the purity applied.
+    pub fn actual_rate(&self) -> Rational {
+        match self.kind {
+            BoundaryKind::Source(purity) => self.rate * purity.factor(),
+            BoundaryKind::Sink(_) => self.rate,
+        }
+    }
+
+    /// Positive for sources and negative for sinks.
+    pub fn net_rate(&self) -> Rational {
+        if self.is_source() {
+            self.actual_rate()
+        } else {
+            -self.actual_rate()
+        }
+    }
+}
+
+#[derive(Component)]
+#[require(Button)]
+struct AddBoundaryButton {
+    source: bool,
+}
+
+#[derive(Component)]
+#[require(Button)]
+struct BoundaryKindButton {
+    box_entity: Entity,
+}
+
+#[derive(Component)]
+struct BoundaryRateInput {
+    box_entity: Entity,
+}
+
+#[derive(Resource)]
+struct BoundaryUi {
+    resource_input_entity: Entity,
+    extractor_button_entity: Entity,
+    purity_button_entity: Entity,
+    node_count_input_entity: Entity,
+    clock_input_entity: Entity,
+    result_text_entity: Entity,
+    status_text_entity: Entity,
+}
+
+/// The extractor and node purity new sources are set up with.
+#[derive(Resource)]
+struct ExtractorCalculator {
+    extractor_index: usize,
+    purity: Purity,
+}
+
+#[derive(Component)]
+#[require(Button)]
+struct ExtractorButton;
+
+#[derive(Component)]
+#[require(Button)]
+struct PurityButton;
+
+/// Works out what the calculator's extractors make per minute on normal nodes,
+/// along with the purity to apply on top and whether the extractors use it.
+///
+/// The clock is entered as a percentage, like `"250"` for 250%.
+fn calculator_rate(
+    calculator: &ExtractorCalculator,
+    node_count: &str,
+    clock_percent: &str,
+    world: &FactoryWorld,
+) -> Result<(Rational, Purity, bool), String> {
+    let extractor = world
+        .get_extractor(calculator.extractor_index)
+        .ok_or("No extractors")?;
+
+    let node_count = node_count
+        .trim()
+        .parse::<u32>()
+        .map_err(|_| format!("Invalid node count \"{}\"", node_count))?;
+
+    let clock = match clock_percent.trim().parse::<Rational>() {
+        Ok(percent) => percent / Rational::from_integer(100),
+        Err(_) => return Err(format!("Invalid clock speed \"{}\"", clock_percent)),
+    };
+
+    if !clock.is_positive() || clock > Extractor::max_clock() {
+        return Err(format!(
+            "Clock speed has to be above 0% and at most {}%",
+            Extractor::max_clock() * Rational::from_integer(100)
+        ));
+    }
+
+    let purity = if extractor.uses_purity {
+        calculator.purity
+    } else {
+        Purity::Normal
+    };
+
+    Ok((
+        extractor.rate(Purity::Normal, node_count, clock),
+        purity,
+        extractor.uses_purity,
+    ))
+}
+
+fn create_boundary_ui(
+    mut commands: Commands,
+    main_menu: Res<MainMenuUI>,
+    control_panel: Res<ControlPanelUi>,
+) {
+    let sub_menu_entity = commands
+        .spawn((Node {
+            display: Display::None,
+            flex_direction: FlexDirection::Column,
+            ..default()
+        },))
+        .set_parent(control_panel.control_panel_entity)
+        .id();
+
+    commands
+        .spawn((
+            MainMenuItem {
+                sub_menu_entity,
+                menu: MenuName::SourcesAndSinks,
+            },
+            Text::new("Sources & Sinks"),
+            Node {
+                width: Val::Percent(100.0),
+                ..default()
+            },
+        ))
+        .set_parent(main_menu.main_menu_entity);
+
+    let resource_input_entity = commands
+        .spawn(TextInput::new("Resource name"))
+        .set_parent(sub_menu_entity)
+        .id();
+
+    let extractor_button_entity = commands
+        .spawn((ExtractorButton, Text::default()))
+        .set_parent(sub_menu_entity)
+        .id();
+
+    let purity_button_entity = commands
+        .spawn((PurityButton, Text::default()))
+        .set_parent(sub_menu_entity)
+        .id();
+
+    let node_count_input_entity = commands
+        .spawn(TextInput {
+            value: "1".into(),
+            placeholder: "Nodes".into(),
+        })
+        .set_parent(sub_menu_entity)
+        .id();
+
+    let clock_input_entity = commands
+        .spawn(TextInput {
+            value: "100".into(),
+            placeholder: "Clock %".into(),
+        })
+        .set_parent(sub_menu_entity)
+        .id();
+
+    let result_text_entity = commands
+        .spawn(Text::default())
+        .set_parent(sub_menu_entity)
+        .id();
+
+    commands
+        .spawn((AddBoundaryButton { source: true }, Text::new("Add Source")))
+        .set_parent(sub_menu_entity);
+
+    commands
+        .spawn((AddBoundaryButton { source: false }, Text::new("Add Sink")))
+        .set_parent(sub_menu_entity);
+
+    let status_text_entity = commands
+        .spawn(Text::default())
+        .set_parent(sub_menu_entity)
+        .id();
+
+    commands.insert_resource(BoundaryUi {
+        resource_input_entity,
+        extractor_button_entity,
+        purity_button_entity,
+        node_count_input_entity,
+        clock_input_entity,
+        result_text_entity,
+        status_text_entity,
+    });
+}
+
+/// Spawns a source or sink box on the plane.
+pub fn spawn_boundary_box(
+    commands: &mut Commands,
+    plane_entity: Entity,
+    position: Vec2,
+    boundary_box: BoundaryBox,
+    world: &FactoryWorld,
+) -> Entity {
+    let box_entity = commands
+        .spawn((
+            DragBox { position },
+            boundary_box,
+            Node {
+                position_type: PositionType::Absolute,
+                ..default()
+            },
+        ))
+        .set_parent(plane_entity)
+        .id();
+
+    spawn_boundary_box_contents(commands, box_entity, &boundary_box, world);
+
+    box_entity
+}
+
+fn spawn_boundary_box_contents(
+    commands: &mut Commands,
+    box_entity: Entity,
+    boundary_box: &BoundaryBox,
+    world: &FactoryWorld,
+) {
+    let rates = [(boundary_box.resource_id, boundary_box.actual_rate())];
+
+    let (title, title_color, inputs, outputs): (_, Color, &[_], &[_]) = if boundary_box.is_source()
+    {
+        ("Source", DARK_CYAN.into(), &[], &rates)
+    } else {
+        ("Sink", DARK_MAGENTA.into(), &rates, &[])
+    };
+
+    spawn_box_contents(
+        commands,
+        box_entity,
+        title,
+        title_color,
+        inputs,
+        outputs,
+        true,
+        world,
+        |builder| {
+            builder.spawn((
+                BoundaryKindButton { box_entity },
+                Text::new(boundary_box.kind_name()),
+            ));
+
+            builder.spawn((
+                BoundaryRateInput { box_entity },
+                TextInput {
+                    value: boundary_box.rate.to_string(),
+                    placeholder: "rate".into(),
+                },
+                BackgroundColor(BLACK.into()),
+            ));
+        },
+    );
+}
+
+fn cycle_calculator_options(
+    extractor_button_q: Query<&Interaction, (Changed<Interaction>, With<ExtractorButton>)>,
+    purity_button_q: Query<&Interaction, (Changed<Interaction>, With<PurityButton>)>,
+    mut calculator: ResMut<ExtractorCalculator>,
+    world: Res<FactoryWorld>,
+) {
+    if let Ok(Interaction::Pressed) = extractor_button_q.get_single() {
+        calculator.extractor_index =
+            (calculator.extractor_index + 1) % world.iter_extractors().count().max(1);
+    }
+
+    if let Ok(Interaction::Pressed) = purity_button_q.get_single() {
+        calculator.purity = calculator.purity.next();
+    }
+}
+
+/// Shows the extractor setup and the rate it makes whenever any of it changes.
+fn update_calculator_result(
+    calculator: Res<ExtractorCalculator>,
+    boundary_ui: Res<BoundaryUi>,
+    input_q: Query<Ref<TextInput>>,
+    mut text_q: Query<&mut Text, Without<TextInput>>,
+    world: Res<FactoryWorld>,
+) {
+    let node_count_input = input_q
+        .get(boundary_ui.node_count_input_entity)
+        .expect("Should be a text input");
+    let clock_input = input_q
+        .get(boundary_ui.clock_input_entity)
+        .expect("Should be a text input");
+
+    if !calculator.is_changed() && !node_count_input.is_changed() && !clock_input.is_changed() {
+        return;
+    }
+
+    let extractor = world.get_extractor(calculator.extractor_index);
+
+    let texts = [
+        (
+            boundary_ui.extractor_button_entity,
+            extractor.map_or("No extractors".into(), |extractor| {
+                format!("Extractor: {}", extractor.name)
+            }),
+        ),
+        (
+            boundary_ui.purity_button_entity,
+            match extractor {
+                Some(extractor) if !extractor.uses_purity => "Purity: Any".into(),
+                _ => format!("Purity: {}", calculator.purity.name()),
+            },
+        ),
+        (
+            boundary_ui.result_text_entity,
+            match calculator_rate(
+                &calculator,
+                &node_count_input.value,
+                &clock_input.value,
+                world.as_ref(),
+            ) {
+                Ok((rate, purity, _)) => format!("Sources make {} / min", rate * purity.factor()),
+                Err(err) => err,
+            },
+        ),
+    ];
+
+    for (text_entity, value) in texts {
+        let mut text = text_q.get_mut(text_entity).expect("Should be a text");
+        text.0 = value;
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn add_boundary_boxes(
+    mut commands: Commands,
+    button_q: Query<(&Interaction, &AddBoundaryButton), Changed<Interaction>>,
+    boundary_ui: Res<BoundaryUi>,
+    calculator: Res<ExtractorCalculator>,
+    input_q: Query<&TextInput>,
+    mut text_q: Query<&mut Text>,
+    plane_q: Query<Entity, With<DragBoxPlane>>,
+    plane_scroll: Res<PlaneScroll>,
+    world: Res<FactoryWorld>,
+) {
+    for (interaction, button) in button_q.iter() {
+        let Interaction::Pressed = interaction else {
+            continue;
+        };
+
+        let resource_input = input_q
+            .get(boundary_ui.resource_input_entity)
+            .expect("Should be a text input");
+
+        let mut status_text = text_q
+            .get_mut(boundary_ui.status_text_entity)
+            .expect("Should be a text");
+
+        let resource_name = resource_input.value.trim();
+
+        let Some(resource_id) = world.find_resource_id(resource_name) else {
+            status_text.0 = format!("No resource called \"{}\"", resource_name);
+            continue;
+        };
+
+        // sources are set up by the extractor calculator
+        let (kind, rate, uses_purity) = if button.source {
+            let node_count_input = input_q
+                .get(boundary_ui.node_count_input_entity)
+                .expect("Should be a text input");
+            let clock_input = input_q
+                .get(boundary_ui.clock_input_entity)
+                .expect("Should be a text input");
+
+            match calculator_rate(
+                &calculator,
+                &node_count_input.value,
+                &clock_input.value,
+                world.as_ref(),
+            ) {
+                Ok((rate, purity, uses_purity)) => {
+                    (BoundaryKind::Source(purity), rate, uses_purity)
+                }
+                Err(err) => {
+                    status_text.0 = err;
+                    continue;
+                }
+            }
+        } else {
+            (
+                BoundaryKind::Sink(SinkKind::Output),
+                Rational::from_integer(DEFAULT_SINK_RATE),
+                true,
+            )
+        };
+
+        status_text.0.clear();
+
+        let root_plane_entity = plane_q.get_single().expect("Should be one root plane");
+
+        spawn_boundary_box(
+            &mut commands,
+            root_plane_entity,
+            -plane_scroll.scroll_position,
+            BoundaryBox {
+                kind,
+                resource_id,
+                rate,
+                uses_purity,
+            },
+            world.as_ref(),
+        );
+    }
+}
+
+fn cycle_boundary_kinds(
+    button_q: Query<(&Interaction, &BoundaryKindButton), Changed<Interaction>>,
+    mut boundary_box_q: Query<&mut BoundaryBox>,
+) {
+    for (interaction, button) in button_q.iter() {
+        let Interaction::Pressed = interaction else {
+            continue;
+        };
+
+        if let Ok(mut boundary_box) = boundary_box_q.get_mut(button.box_entity) {
+            let next_kind = boundary_box.next_kind();
+
+            if boundary_box.kind != next_kind {
+                boundary_box.kind = next_kind;
+            }
+        }
+    }
+}
+
+fn submit_boundary_rates(
+    mut submitted_events: EventReader<TextInputSubmitted>,
+    mut input_q: Query<(&BoundaryRateInput, &mut TextInput)>,
+    mut boundary_box_q: Query<&mut BoundaryBox>,
+    mut focused: ResMut<FocusedTextInput>,
+) {
+    for event in submitted_events.read() {
+        let Ok((rate_input, mut text_input)) = input_q.get_mut(event.entity) else {
+            continue;
+        };
+
+        let Ok(mut boundary_box) = boundary_box_q.get_mut(rate_input.box_entity) else {
+            error!("Couldn't query boundary box {}", rate_input.box_entity);
+            continue;
+        };
+
+        match event.value.parse::<Rational>() {
+            Ok(rate) if !rate.is_negative() => boundary_box.rate = rate,
+            _ => info!("Invalid rate \"{}\"", event.value),
+        }
+
+        text_input.value = boundary_box.rate.to_string();
+        focused.0 = None;
+    }
+}
+
+/// Rebuilds boxes whose kind or rate changed, so they show their new rate.
+fn refresh_boundary_boxes(
+    mut commands: Commands,
+    boundary_box_q: Query<(Entity, Ref<BoundaryBox>)>,
+    world: Res<FactoryWorld>,
+) {
+    for (box_entity, boundary_box) in boundary_box_q.iter() {
+        if !boundary_box.is_changed() || boundary_box.is_added() {
+            continue;
+        }
+
+        commands.entity(box_entity).despawn_descendants();
+
+        spawn_boundary_box_contents(&mut commands, box_entity, &boundary_box, world.as_ref());
+    }
+}
@@ -0,0 +1,472 @@
+use bevy::{color::palettes::css::*, prelude::*, utils::HashMap};
+
+use crate::{
+    ControlPanelUi, CreateBaseUi, MainMenuItem, MainMenuUI, MenuName,
+    boundary_boxes::BoundaryBox,
+    drag_plane::{DragBox, DragBoxPlane, SelectedBox},
+    factory_world::*,
+    keymap::{AppAction, action_sent},
+    rational::Rational,
+    recipe_ui::{DespawnRecipeButton, RecipeBox, spawn_box_contents},
+    text_input::TextInput,
+};
+
+pub fn build(app: &mut App) {
+    app.add_systems(Startup, create_group_ui.after(CreateBaseUi));
+    app.add_systems(
+        Update,
+        (
+            group_selected_boxes,
+            expand_groups,
+            despawn_group_members,
+            delete_selected_boxes,
+            move_group_members,
+            refresh_groups,
+        ),
+    );
+}
+
+/// A drag box standing in for several other boxes, which are hidden while grouped.
+#[derive(Component)]
+pub struct BoxGroup {
+    pub name: String,
+    pub members: Vec<Entity>,
+    /// The net rates of all the members, negative rates are inputs to the group.
+    pub net_rates: Vec<(ResourceId, Rational)>,
+    /// Position of the group box when the members were last moved along with it.
+    last_position: Vec2,
+}
+
+/// Marks a drag box that is hidden inside a [`BoxGroup`].
+#[derive(Component)]
+pub struct GroupedBox {
+    pub group_entity: Entity,
+}
+
+#[derive(Component)]
+#[require(Button)]
+struct GroupSelectedButton {
+    name_input_entity: Entity,
+}
+
+#[derive(Component)]
+#[require(Button)]
+struct ExpandGroupButton {
+    group_entity: Entity,
+}
+
+fn create_group_ui(
+    mut commands: Commands,
+    main_menu: Res<MainMenuUI>,
+    control_panel: Res<ControlPanelUi>,
+) {
+    let sub_menu_entity = commands
+        .spawn((Node {
+            display: Display::None,
+            flex_direction: FlexDirection::Column,
+            ..default()
+        },))
+        .set_parent(control_panel.control_panel_entity)
+        .id();
+
+    commands
+        .spawn((
+            MainMenuItem {
+                sub_menu_entity,
+                menu: MenuName::Groups,
+            },
+            Text::new("Groups"),
+            Node {
+                width: Val::Percent(100.0),
+                ..default()
+            },
+        ))
+        .set_parent(main_menu.main_menu_entity);
+
+    let name_input_entity = commands
+        .spawn(TextInput::new("Group name"))
+        .set_parent(sub_menu_entity)
+        .id();
+
+    commands
+        .spawn((
+            GroupSelectedButton { name_input_entity },
+            Text::new("Group Selected"),
+        ))
+        .set_parent(sub_menu_entity);
+}
+
+/// Sums the rates of a recipe or boundary box, or of the members of a group box.
+fn box_net_rates(
+    box_entity: Entity,
+    recipe_box_q: &Query<&RecipeBox>,
+    boundary_box_q: &Query<&BoundaryBox>,
+    group_q: &Query<&BoxGroup>,
+    world: &FactoryWorld,
+    net_rates: &mut HashMap<ResourceId, Rational>,
+) {
+    if let Ok(recipe_box) = recipe_box_q.get(box_entity) {
+        let Ok(recipe) = world.get_recipe(recipe_box.recipe_id) else {
+            return;
+        };
+
+        for (resource_id, rate) in recipe.iter_net_rates() {
+            *net_rates.entry(resource_id).or_default() += rate * recipe_box.multiplier;
+        }
+    }
+
+    if let Ok(boundary_box) = boundary_box_q.get(box_entity) {
+        *net_rates.entry(boundary_box.resource_id).or_default() += boundary_box.net_rate();
+    }
+
+    if let Ok(group) = group_q.get(box_entity) {
+        for &member_entity in group.members.iter() {
+            box_net_rates(
+                member_entity,
+                recipe_box_q,
+                boundary_box_q,
+                group_q,
+                world,
+                net_rates,
+            );
+        }
+    }
+}
+
+/// The non-zero rates sorted by resource name.
+fn sorted_net_rates(
+    net_rates: HashMap<ResourceId, Rational>,
+    world: &FactoryWorld,
+) -> Vec<(ResourceId, Rational)> {
+    let mut net_rates: Vec<(ResourceId, Rational)> = net_rates
+        .into_iter()
+        .filter(|(_, rate)| !rate.is_zero())
+        .collect();
+    net_rates.sort_by_key(|&(resource_id, _)| world.get_resource_name(resource_id));
+    net_rates
+}
+
+/// Spawns the header and the aggregate inputs and outputs of a group box.
+fn spawn_group_contents(
+    commands: &mut Commands,
+    group_entity: Entity,
+    name: &str,
+    net_rates: &[(ResourceId, Rational)],
+    world: &FactoryWorld,
+) {
+    let (mut inputs, outputs): (Vec<_>, Vec<_>) = net_rates
+        .iter()
+        .copied()
+        .partition(|(_, rate)| rate.is_negative());
+
+    for (_, rate) in inputs.iter_mut() {
+        *rate = -*rate;
+    }
+
+    spawn_box_contents(
+        commands,
+        group_entity,
+        name,
+        DARK_GREEN.into(),
+        &inputs,
+        &outputs,
+        false,
+        world,
+        |builder| {
+            builder.spawn((ExpandGroupButton { group_entity }, Text::new("+")));
+        },
+    );
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn group_selected_boxes(
+    mut commands: Commands,
+    button_q: Query<(&Interaction, &GroupSelectedButton), Changed<Interaction>>,
+    mut name_input_q: Query<&mut TextInput>,
+    selected_q: Query<(Entity, &DragBox), (With<SelectedBox>, Without<GroupedBox>)>,
+    recipe_box_q: Query<&RecipeBox>,
+    boundary_box_q: Query<&BoundaryBox>,
+    group_q: Query<&BoxGroup>,
+    mut node_q: Query<&mut Node>,
+    plane_q: Query<Entity, With<DragBoxPlane>>,
+    world: Res<FactoryWorld>,
+    mut group_count: Local<u32>,
+) {
+    for (interaction, button) in button_q.iter() {
+        let Interaction::Pressed = interaction else {
+            continue;
+        };
+
+        if selected_q.is_empty() {
+            info!("No boxes selected to group");
+            continue;
+        }
+
+        let mut name_input = name_input_q
+            .get_mut(button.name_input_entity)
+            .expect("Should be a text input");
+
+        *group_count += 1;
+        let name = match std::mem::take(&mut name_input.value) {
+            name if name.trim().is_empty() => format!("Group {}", *group_count),
+            name => name,
+        };
+
+        let mut position = Vec2::INFINITY;
+        let mut members = Vec::new();
+        let mut net_rates = HashMap::default();
+
+        for (member_entity, drag_box) in selected_q.iter() {
+            position = position.min(drag_box.position);
+            members.push(member_entity);
+
+            box_net_rates(
+                member_entity,
+                &recipe_box_q,
+                &boundary_box_q,
+                &group_q,
+                world.as_ref(),
+                &mut net_rates,
+            );
+        }
+
+        let net_rates = sorted_net_rates(net_rates, world.as_ref());
+
+        info!("Grouping {} boxes as \"{}\"", members.len(), name);
+
+        let root_plane_entity = plane_q.get_single().expect("Should be one root plane");
+
+        let group_entity = commands
+            .spawn((
+                DragBox { position },
+                Node {
+                    position_type: PositionType::Absolute,
+                    ..default()
+                },
+            ))
+            .set_parent(root_plane_entity)
+            .id();
+
+        for &member_entity in members.iter() {
+            commands
+                .entity(member_entity)
+                .remove::<SelectedBox>()
+                .insert(GroupedBox { group_entity });
+
+            if let Ok(mut node) = node_q.get_mut(member_entity) {
+                node.display = Display::None;
+            }
+        }
+
+        spawn_group_contents(
+            &mut commands,
+            group_entity,
+            &name,
+            &net_rates,
+            world.as_ref(),
+        );
+
+        commands.entity(group_entity).insert(BoxGroup {
+            name,
+            members,
+            net_rates,
+            last_position: position,
+        });
+    }
+}
+
+/// Shows the members of a group again and removes the group box.
+fn expand_groups(
+    mut commands: Commands,
+    button_q: Query<(&Interaction, &ExpandGroupButton), Changed<Interaction>>,
+    group_q: Query<&BoxGroup>,
+    mut node_q: Query<&mut Node>,
+) {
+    for (interaction, button) in button_q.iter() {
+        let Interaction::Pressed = interaction else {
+            continue;
+        };
+
+        let Ok(group) = group_q.get(button.group_entity) else {
+            error!("Couldn't query group {}", button.group_entity);
+            continue;
+        };
+
+        for &member_entity in group.members.iter() {
+            commands.entity(member_entity).remove::<GroupedBox>();
+
+            if let Ok(mut node) = node_q.get_mut(member_entity) {
+                node.display = Display::DEFAULT;
+            }
+        }
+
+        commands.entity(button.group_entity).despawn_recursive();
+    }
+}
+
+fn despawn_members(commands: &mut Commands, group_q: &Query<&BoxGroup>, group: &BoxGroup) {
+    for &member_entity in group.members.iter() {
+        if let Ok(member_group) = group_q.get(member_entity) {
+            despawn_members(commands, group_q, member_group);
+        }
+
+        commands.entity(member_entity).despawn_recursive();
+    }
+}
+
+/// Despawning a group box also despawns everything inside it.
+fn despawn_group_members(
+    mut commands: Commands,
+    button_q: Query<(&DespawnRecipeButton, &Interaction), Changed<Interaction>>,
+    group_q: Query<&BoxGroup>,
+) {
+    for (button, interaction) in button_q.iter() {
+        let Interaction::Pressed = interaction else {
+            continue;
+        };
+
+        if let Ok(group) = group_q.get(button.box_entity) {
+            despawn_members(&mut commands, &group_q, group);
+        }
+    }
+}
+
+/// Despawns the selected boxes, including everything inside selected groups.
+fn delete_selected_boxes(
+    mut commands: Commands,
+    mut actions: EventReader<AppAction>,
+    selected_q: Query<Entity, With<SelectedBox>>,
+    group_q: Query<&BoxGroup>,
+) {
+    if !action_sent(&mut actions, AppAction::DeleteSelected) {
+        return;
+    }
+
+    for selected_entity in selected_q.iter() {
+        if let Ok(group) = group_q.get(selected_entity) {
+            despawn_members(&mut commands, &group_q, group);
+        }
+
+        commands.entity(selected_entity).despawn_recursive();
+    }
+}
+
+/// Moves the hidden members of a group by however much the group box was moved.
+///
+/// Nested groups are moved a frame later, when their own change is seen.
+fn move_group_members(mut drag_box_q: Query<(&mut DragBox, Option<&mut BoxGroup>)>) {
+    let mut member_moves = Vec::new();
+
+    for (drag_box, group) in drag_box_q.iter_mut() {
+        let Some(mut group) = group else {
+            continue;
+        };
+
+        if !drag_box.is_changed() {
+            continue;
+        }
+
+        let delta = drag_box.position - group.last_position;
+
+        if delta == Vec2::ZERO {
+            continue;
+        }
+
+        group.last_position = drag_box.position;
+
+        for &member_entity in group.members.iter() {
+            member_moves.push((member_entity, delta));
+        }
+    }
+
+    for (member_entity, delta) in member_moves {
+        if let Ok((mut member_box, _)) = drag_box_q.get_mut(member_entity) {
+            member_box.position += delta;
+        }
+    }
+}
+
+/// Drops members whose recipe was removed, and sums the rates of every group again
+/// when a recipe or a member box changes, like when chain solving sets a member's multiplier.
+#[allow(clippy::type_complexity)]
+fn refresh_groups(
+    mut commands: Commands,
+    mut recipe_events: EventReader<RecipeEvent>,
+    mut group_q: Query<(Entity, &mut BoxGroup)>,
+    recipe_box_q: Query<&RecipeBox>,
+    boundary_box_q: Query<&BoundaryBox>,
+    changed_member_q: Query<
+        (),
+        (
+            With<GroupedBox>,
+            Or<(Changed<RecipeBox>, Changed<BoundaryBox>)>,
+        ),
+    >,
+    world: Res<FactoryWorld>,
+) {
+    let mut changed = !changed_member_q.is_empty();
+    let mut removed_recipes = Vec::new();
+
+    for &event in recipe_events.read() {
+        changed = true;
+
+        if let RecipeEvent::Removed(recipe_id) = event {
+            removed_recipes.push(recipe_id);
+        }
+    }
+
+    if !changed {
+        return;
+    }
+
+    // the boxes of removed recipes are despawned along with the recipe
+    for (_, mut group) in group_q.iter_mut() {
+        group
+            .members
+            .retain(|&member_entity| match recipe_box_q.get(member_entity) {
+                Ok(recipe_box) => !removed_recipes.contains(&recipe_box.recipe_id),
+                Err(_) => true,
+            });
+    }
+
+    let group_entities: Vec<Entity> = group_q
+        .iter()
+        .map(|(group_entity, _)| group_entity)
+        .collect();
+
+    for group_entity in group_entities {
+        let mut net_rates = HashMap::default();
+        let mut group_lens = group_q.transmute_lens::<&BoxGroup>();
+
+        box_net_rates(
+            group_entity,
+            &recipe_box_q,
+            &boundary_box_q,
+            &group_lens.query(),
+            world.as_ref(),
+            &mut net_rates,
+        );
+
+        let net_rates = sorted_net_rates(net_rates, world.as_ref());
+
+        let (_, mut group) = group_q
+            .get_mut(group_entity)
+            .expect("Group was just listed");
+
+        if group.net_rates == net_rates {
+            continue;
+        }
+
+        group.net_rates = net_rates;
+
+        commands.entity(group_entity).despawn_descendants();
+
+        spawn_group_contents(
+            &mut commands,
+            group_entity,
+            &group.name,
+            &group.net_rates,
+            world.as_ref(),
+        );
+    }
+}
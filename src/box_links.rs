@@ -0,0 +1,318 @@
+use bevy::{color::palettes::css::*, prelude::*, utils::HashMap};
+
+use crate::{
+    box_groups::GroupedBox,
+    drag_plane::{DragBox, DragBoxPlane},
+    factory_world::*,
+    link_flow::LinkFlow,
+    recipe_ui::{BoxPort, RecipeBox},
+};
+
+const LINK_WIDTH: f32 = 2.;
+
+pub fn build(app: &mut App) {
+    app.insert_resource(PendingLink(None));
+
+    app.add_systems(
+        Update,
+        (
+            connect_ports,
+            disconnect_ports,
+            highlight_pending_port,
+            despawn_dangling_links,
+            update_link_lines,
+        ),
+    );
+}
+
+/// A line on the plane carrying a resource from an output of one box to an input of another.
+#[derive(Component, Clone, Copy)]
+#[require(Node, ZIndex(|| ZIndex(-1)), BackgroundColor(|| BackgroundColor(LIGHT_GRAY.into())))]
+pub struct BoxLink {
+    pub from_box: Entity,
+    pub to_box: Entity,
+    pub resource_id: ResourceId,
+}
+
+/// The port that was clicked first when linking two boxes.
+#[derive(Resource)]
+pub struct PendingLink(pub Option<BoxPort>);
+
+/// Spawns a link on the plane, doesn't check that the boxes have matching ports.
+pub fn spawn_link(commands: &mut Commands, plane_entity: Entity, link: BoxLink) -> Entity {
+    commands
+        .spawn((
+            link,
+            Node {
+                position_type: PositionType::Absolute,
+                ..default()
+            },
+        ))
+        .set_parent(plane_entity)
+        .id()
+}
+
+/// Clicking an output port and then an input port for the same resource links the two boxes.
+fn connect_ports(
+    mut commands: Commands,
+    port_q: Query<(&Interaction, &BoxPort), Changed<Interaction>>,
+    link_q: Query<&BoxLink>,
+    plane_q: Query<Entity, With<DragBoxPlane>>,
+    mut pending_link: ResMut<PendingLink>,
+) {
+    for (interaction, &port) in port_q.iter() {
+        let Interaction::Pressed = interaction else {
+            continue;
+        };
+
+        let Some(pending_port) = pending_link.0 else {
+            pending_link.0 = Some(port);
+            continue;
+        };
+
+        if pending_port == port {
+            pending_link.0 = None;
+            continue;
+        }
+
+        if pending_port.output == port.output
+            || pending_port.resource_id != port.resource_id
+            || pending_port.box_entity == port.box_entity
+        {
+            pending_link.0 = Some(port);
+            continue;
+        }
+
+        pending_link.0 = None;
+
+        let (from_port, to_port) = if port.output {
+            (port, pending_port)
+        } else {
+            (pending_port, port)
+        };
+
+        let link = BoxLink {
+            from_box: from_port.box_entity,
+            to_box: to_port.box_entity,
+            resource_id: port.resource_id,
+        };
+
+        if link_q.iter().any(|existing| {
+            existing.from_box == link.from_box
+                && existing.to_box == link.to_box
+                && existing.resource_id == link.resource_id
+        }) {
+            continue;
+        }
+
+        let root_plane_entity = plane_q.get_single().expect("Should be one root plane");
+
+        spawn_link(&mut commands, root_plane_entity, link);
+    }
+}
+
+/// Right clicking a port removes all the links attached to it.
+fn disconnect_ports(
+    mut commands: Commands,
+    mouse_input: Res<ButtonInput<MouseButton>>,
+    port_q: Query<(&Interaction, &BoxPort)>,
+    link_q: Query<(Entity, &BoxLink)>,
+) {
+    if !mouse_input.just_pressed(MouseButton::Right) {
+        return;
+    }
+
+    for (interaction, port) in port_q.iter() {
+        let Interaction::Hovered = interaction else {
+            continue;
+        };
+
+        for (link_entity, link) in link_q.iter() {
+            let port_box = if port.output {
+                link.from_box
+            } else {
+                link.to_box
+            };
+
+            if port_box == port.box_entity && link.resource_id == port.resource_id {
+                commands.entity(link_entity).despawn_recursive();
+            }
+        }
+    }
+}
+
+fn highlight_pending_port(
+    mut port_q: Query<(&BoxPort, &mut BackgroundColor)>,
+    pending_link: Res<PendingLink>,
+) {
+    if !pending_link.is_changed() {
+        return;
+    }
+
+    for (port, mut background_color) in port_q.iter_mut() {
+        background_color.0 = if pending_link.0 == Some(*port) {
+            DARK_GOLDENROD.into()
+        } else {
+            Color::NONE
+        };
+    }
+}
+
+fn despawn_dangling_links(
+    mut commands: Commands,
+    link_q: Query<(Entity, &BoxLink)>,
+    drag_box_q: Query<(), With<DragBox>>,
+    mut pending_link: ResMut<PendingLink>,
+) {
+    for (link_entity, link) in link_q.iter() {
+        if !drag_box_q.contains(link.from_box) || !drag_box_q.contains(link.to_box) {
+            commands.entity(link_entity).despawn_recursive();
+        }
+    }
+
+    if let Some(pending_port) = pending_link.0
+        && !drag_box_q.contains(pending_port.box_entity)
+    {
+        pending_link.0 = None;
+    }
+}
+
+/// Writes the recipe boxes and the links between them as a Graphviz DOT graph,
+/// with each link labelled with its resource and how much of it flows along the link.
+pub fn link_graph_dot(
+    recipe_box_q: &Query<(Entity, &RecipeBox)>,
+    link_q: &Query<(&BoxLink, Option<&LinkFlow>)>,
+    world: &FactoryWorld,
+) -> String {
+    let mut dot = String::from("digraph boxes {\n    rankdir=LR;\n");
+
+    for (box_entity, recipe_box) in recipe_box_q.iter() {
+        let Ok(recipe) = world.get_recipe(recipe_box.recipe_id) else {
+            continue;
+        };
+
+        dot += &format!(
+            "    box_{} [label={}, shape=box];\n",
+            box_entity.index(),
+            dot_quote(&format!("{} x{}", recipe.name, recipe_box.multiplier))
+        );
+    }
+
+    for (link, flow) in link_q.iter() {
+        if !recipe_box_q.contains(link.from_box) || !recipe_box_q.contains(link.to_box) {
+            continue;
+        }
+
+        let resource_name = world
+            .get_resource_name(link.resource_id)
+            .expect("Resource name should exist");
+
+        // flows are worked out every frame, so only a link made this frame has none yet
+        let label = match flow {
+            Some(flow) => format!("{} {} / min", flow.supply, resource_name),
+            None => resource_name.to_string(),
+        };
+
+        dot += &format!(
+            "    box_{} -> box_{} [label={}];\n",
+            link.from_box.index(),
+            link.to_box.index(),
+            dot_quote(&label)
+        );
+    }
+
+    dot.push_str("}\n");
+    dot
+}
+
+/// The rect of a ui node in logical pixels.
+pub fn node_rect(computed_node: &ComputedNode, transform: &GlobalTransform) -> Rect {
+    let scale = computed_node.inverse_scale_factor();
+
+    Rect::from_center_size(
+        transform.translation().truncate() * scale,
+        computed_node.size() * scale,
+    )
+}
+
+/// The box that is shown for a box, which is the outermost group if it is grouped.
+fn visible_box(mut box_entity: Entity, grouped_q: &Query<&GroupedBox>) -> Entity {
+    while let Ok(grouped) = grouped_q.get(box_entity) {
+        box_entity = grouped.group_entity;
+    }
+
+    box_entity
+}
+
+/// Stretches each link line between the right edge of its output and the left edge of its input.
+fn update_link_lines(
+    mut link_q: Query<(&BoxLink, &mut Node, &mut Transform)>,
+    plane_q: Query<(&ComputedNode, &GlobalTransform), With<DragBoxPlane>>,
+    box_q: Query<(&ComputedNode, &GlobalTransform), With<DragBox>>,
+    port_q: Query<(&BoxPort, &ComputedNode, &GlobalTransform)>,
+    grouped_q: Query<&GroupedBox>,
+) {
+    let Ok((plane_node, plane_transform)) = plane_q.get_single() else {
+        return;
+    };
+
+    let plane_min = node_rect(plane_node, plane_transform).min;
+
+    let port_heights: HashMap<(Entity, ResourceId, bool), f32> = port_q
+        .iter()
+        .filter(|(_, computed_node, _)| !computed_node.is_empty())
+        .map(|(port, computed_node, transform)| {
+            (
+                (port.box_entity, port.resource_id, port.output),
+                node_rect(computed_node, transform).center().y,
+            )
+        })
+        .collect();
+
+    // finds where a link meets a box, falling back to the middle of the box if the port is hidden
+    let anchor = |box_entity: Entity, resource_id: ResourceId, output: bool| {
+        let shown_entity = visible_box(box_entity, &grouped_q);
+        let (computed_node, transform) = box_q.get(shown_entity).ok()?;
+        let box_rect = node_rect(computed_node, transform);
+
+        let y = port_heights
+            .get(&(shown_entity, resource_id, output))
+            .copied()
+            .unwrap_or(box_rect.center().y);
+
+        let x = if output {
+            box_rect.max.x
+        } else {
+            box_rect.min.x
+        };
+
+        Some((shown_entity, Vec2::new(x, y) - plane_min))
+    };
+
+    for (link, mut node, mut transform) in link_q.iter_mut() {
+        let (Some((from_shown_entity, start)), Some((to_shown_entity, end))) = (
+            anchor(link.from_box, link.resource_id, true),
+            anchor(link.to_box, link.resource_id, false),
+        ) else {
+            continue;
+        };
+
+        // links inside a collapsed group aren't shown
+        if from_shown_entity == to_shown_entity {
+            node.display = Display::None;
+            continue;
+        }
+
+        let delta = end - start;
+        let length = delta.length();
+        let middle = (start + end) * 0.5;
+
+        node.display = Display::DEFAULT;
+        node.left = Val::Px(middle.x - length * 0.5);
+        node.top = Val::Px(middle.y - LINK_WIDTH * 0.5);
+        node.width = Val::Px(length);
+        node.height = Val::Px(LINK_WIDTH);
+
+        transform.rotation = Quat::from_rotation_z(delta.y.atan2(delta.x));
+    }
+}
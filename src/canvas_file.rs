@@ -0,0 +1,266 @@
+use bevy::{
+    prelude::*,
+    tasks::{IoTaskPool, Task, block_on, poll_once},
+};
+
+use crate::{
+    ControlPanelUi, CreateBaseUi, MainMenuItem, MainMenuUI, MenuName,
+    blueprints::{
+        Blueprint, capture_blueprint, insert_blueprint, open_json_async, save_json_async,
+    },
+    boundary_boxes::BoundaryBox,
+    box_groups::{BoxGroup, GroupedBox},
+    box_links::BoxLink,
+    chain_solve::LinkShare,
+    drag_plane::{DragBox, DragBoxPlane, PlaneScroll},
+    factory_world::FactoryWorld,
+    keymap::{AppAction, action_sent},
+    logistics::{LogisticsBox, SplitRatio},
+    recipe_ui::RecipeBox,
+};
+
+pub fn build(app: &mut App) {
+    app.add_systems(Startup, create_canvas_ui.after(CreateBaseUi));
+    app.add_systems(
+        Update,
+        (
+            start_open_canvas_dialogue,
+            finish_open_canvas_dialogue,
+            start_save_canvas_dialogue,
+            finish_save_canvas_dialogue,
+        ),
+    );
+}
+
+#[derive(Resource)]
+struct CanvasUi {
+    status_text_entity: Entity,
+}
+
+#[derive(Component)]
+#[require(Button)]
+struct OpenCanvasButton;
+
+#[derive(Component)]
+#[require(Button)]
+struct SaveCanvasButton;
+
+fn create_canvas_ui(
+    mut commands: Commands,
+    main_menu: Res<MainMenuUI>,
+    control_panel: Res<ControlPanelUi>,
+) {
+    let sub_menu_entity = commands
+        .spawn((Node {
+            display: Display::None,
+            flex_direction: FlexDirection::Column,
+            ..default()
+        },))
+        .set_parent(control_panel.control_panel_entity)
+        .id();
+
+    commands
+        .spawn((
+            MainMenuItem {
+                sub_menu_entity,
+                menu: MenuName::Canvas,
+            },
+            Text::new("Canvas"),
+            Node {
+                width: Val::Percent(100.0),
+                ..default()
+            },
+        ))
+        .set_parent(main_menu.main_menu_entity);
+
+    commands
+        .spawn((OpenCanvasButton, Text::new("Open Canvas")))
+        .set_parent(sub_menu_entity);
+
+    commands
+        .spawn((SaveCanvasButton, Text::new("Save Canvas")))
+        .set_parent(sub_menu_entity);
+
+    let status_text_entity = commands
+        .spawn((
+            Text::default(),
+            Node {
+                max_width: Val::Px(300.),
+                ..default()
+            },
+        ))
+        .set_parent(sub_menu_entity)
+        .id();
+
+    commands.insert_resource(CanvasUi { status_text_entity });
+}
+
+/// Whether a button was just pressed or an action was sent, for things that can be done either way.
+fn pressed_or_sent<T: Component>(
+    button_q: &Query<&Interaction, (Changed<Interaction>, With<T>)>,
+    actions: &mut EventReader<AppAction>,
+    action: AppAction,
+) -> bool {
+    action_sent(actions, action)
+        || button_q
+            .iter()
+            .any(|interaction| *interaction == Interaction::Pressed)
+}
+
+#[derive(Component)]
+struct OpenCanvasTask {
+    task: Task<Option<String>>,
+}
+
+fn start_open_canvas_dialogue(
+    mut commands: Commands,
+    button_q: Query<&Interaction, (Changed<Interaction>, With<OpenCanvasButton>)>,
+    mut actions: EventReader<AppAction>,
+) {
+    if !pressed_or_sent(&button_q, &mut actions, AppAction::OpenCanvas) {
+        return;
+    }
+
+    commands.spawn(OpenCanvasTask {
+        task: IoTaskPool::get().spawn(open_json_async("Canvas")),
+    });
+}
+
+/// Replaces everything on the plane with the boxes of the opened canvas.
+#[allow(clippy::too_many_arguments)]
+fn finish_open_canvas_dialogue(
+    mut commands: Commands,
+    mut task_q: Query<(Entity, &mut OpenCanvasTask)>,
+    drag_box_q: Query<Entity, With<DragBox>>,
+    plane_q: Query<Entity, With<DragBoxPlane>>,
+    mut world: ResMut<FactoryWorld>,
+    mut plane_scroll: ResMut<PlaneScroll>,
+    canvas_ui: Res<CanvasUi>,
+    mut text_q: Query<&mut Text>,
+) {
+    for (task_entity, mut task) in task_q.iter_mut() {
+        if task.task.is_finished() {
+            let Some(task_result) = block_on(poll_once(&mut task.task)) else {
+                error!("Expected task to be finished");
+                continue;
+            };
+
+            commands.entity(task_entity).despawn_recursive();
+
+            let Some(json) = task_result else {
+                continue;
+            };
+
+            let mut status_text = text_q
+                .get_mut(canvas_ui.status_text_entity)
+                .expect("Should be a text");
+
+            let canvas = match serde_json::from_str::<Blueprint>(&json) {
+                Ok(canvas) => canvas,
+                Err(err) => {
+                    error!("Invalid canvas: {}", err);
+                    status_text.0 = "Invalid canvas".into();
+                    continue;
+                }
+            };
+
+            for drag_box_entity in drag_box_q.iter() {
+                commands.entity(drag_box_entity).despawn_recursive();
+            }
+
+            plane_scroll.scroll_position = Vec2::ZERO;
+
+            let root_plane_entity = plane_q.get_single().expect("Should be one root plane");
+
+            let (box_entities, missing) = insert_blueprint(
+                &mut commands,
+                root_plane_entity,
+                Vec2::ZERO,
+                &canvas,
+                world.as_mut(),
+            );
+
+            status_text.0 = if missing.is_empty() {
+                format!("Opened canvas with {} boxes", box_entities.len())
+            } else {
+                format!(
+                    "Opened canvas with {} boxes, {}",
+                    box_entities.len(),
+                    missing.describe()
+                )
+            };
+        }
+    }
+}
+
+#[derive(Component)]
+struct SaveCanvasTask {
+    task: Task<std::io::Result<bool>>,
+}
+
+/// Saves every box on the plane as a blueprint, with groups flattened into their members.
+#[allow(clippy::type_complexity)]
+fn start_save_canvas_dialogue(
+    mut commands: Commands,
+    button_q: Query<&Interaction, (Changed<Interaction>, With<SaveCanvasButton>)>,
+    mut actions: EventReader<AppAction>,
+    top_level_q: Query<Entity, (With<DragBox>, Without<GroupedBox>)>,
+    drag_box_q: Query<(
+        &DragBox,
+        Option<&RecipeBox>,
+        Option<&BoundaryBox>,
+        Option<&LogisticsBox>,
+        Option<&BoxGroup>,
+    )>,
+    link_q: Query<(&BoxLink, Option<&SplitRatio>, Option<&LinkShare>)>,
+    world: Res<FactoryWorld>,
+) {
+    if !pressed_or_sent(&button_q, &mut actions, AppAction::SaveCanvas) {
+        return;
+    }
+
+    let canvas = capture_blueprint(
+        "Canvas".into(),
+        top_level_q.iter(),
+        &drag_box_q,
+        &link_q,
+        world.as_ref(),
+    );
+
+    let json = serde_json::to_string_pretty(&canvas).expect("Canvas should serialize");
+
+    commands.spawn(SaveCanvasTask {
+        task: IoTaskPool::get().spawn(save_json_async(json, "Canvas", "canvas.json")),
+    });
+}
+
+fn finish_save_canvas_dialogue(
+    mut commands: Commands,
+    mut task_q: Query<(Entity, &mut SaveCanvasTask)>,
+    canvas_ui: Res<CanvasUi>,
+    mut text_q: Query<&mut Text>,
+) {
+    for (task_entity, mut task) in task_q.iter_mut() {
+        if task.task.is_finished() {
+            let Some(task_result) = block_on(poll_once(&mut task.task)) else {
+                error!("Expected task to be finished");
+                continue;
+            };
+
+            commands.entity(task_entity).despawn_recursive();
+
+            let mut status_text = text_q
+                .get_mut(canvas_ui.status_text_entity)
+                .expect("Should be a text");
+
+            match task_result {
+                Ok(true) => status_text.0 = "Saved canvas".into(),
+                Ok(false) => (),
+                Err(err) => {
+                    error!("Failed to save canvas: {}", err);
+                    status_text.0 = "Failed to save canvas".into();
+                }
+            }
+        }
+    }
+}
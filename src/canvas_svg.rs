@@ -0,0 +1,201 @@
+use std::fmt::Write;
+
+use bevy::prelude::*;
+
+use crate::{
+    box_links::{BoxLink, node_rect},
+    drag_plane::{DragBox, DragBoxPlane, PlaneScroll},
+};
+
+/// Space left around the boxes at the edge of the image.
+const MARGIN: f32 = 20.;
+
+/// Draws every shown box and link on the plane as SVG, whatever part of it is scrolled into view.
+///
+/// Boxes are drawn from their laid out ui nodes, so they need to have been on screen for a frame.
+pub fn canvas_to_svg(
+    plane_q: &Query<(&ComputedNode, &GlobalTransform, &BackgroundColor), With<DragBoxPlane>>,
+    box_q: &Query<(Entity, &Node), With<DragBox>>,
+    element_q: &Query<(
+        &ComputedNode,
+        &GlobalTransform,
+        Option<&BackgroundColor>,
+        Option<&Children>,
+    )>,
+    text_q: &Query<(&Text, &TextFont, &TextColor, &TextLayout)>,
+    link_q: &Query<(&ComputedNode, &GlobalTransform, &BackgroundColor, &Node), With<BoxLink>>,
+    plane_scroll: &PlaneScroll,
+) -> String {
+    let Ok((plane_node, plane_transform, plane_color)) = plane_q.get_single() else {
+        return String::new();
+    };
+
+    // ui positions are on screen, this puts them back where they are on the plane
+    let offset = node_rect(plane_node, plane_transform).min + plane_scroll.scroll_position;
+
+    let mut bounds: Option<Rect> = None;
+    let mut body = String::new();
+
+    for (computed_node, transform, color, node) in link_q.iter() {
+        if node.display == Display::None || computed_node.is_empty() {
+            continue;
+        }
+
+        let scale = computed_node.inverse_scale_factor();
+        let center = transform.translation().truncate() * scale - offset;
+        let direction = (transform.rotation() * Vec3::X).truncate();
+        let half_length = direction * computed_node.size().x * scale * 0.5;
+        let (start, end) = (center - half_length, center + half_length);
+
+        writeln!(
+            body,
+            r#"<line x1="{}" y1="{}" x2="{}" y2="{}" stroke="{}" stroke-width="{}"/>"#,
+            start.x,
+            start.y,
+            end.x,
+            end.y,
+            svg_color(color.0),
+            computed_node.size().y * scale,
+        )
+        .unwrap();
+
+        let rect = Rect::from_corners(start, end);
+        bounds = Some(bounds.map_or(rect, |bounds| bounds.union(rect)));
+    }
+
+    for (box_entity, node) in box_q.iter() {
+        if node.display == Display::None {
+            continue;
+        }
+
+        let Ok((computed_node, transform, ..)) = element_q.get(box_entity) else {
+            continue;
+        };
+
+        if computed_node.is_empty() {
+            continue;
+        }
+
+        let mut rect = node_rect(computed_node, transform);
+        rect.min -= offset;
+        rect.max -= offset;
+        bounds = Some(bounds.map_or(rect, |bounds| bounds.union(rect)));
+
+        write_element(&mut body, box_entity, offset, element_q, text_q);
+    }
+
+    let bounds = bounds
+        .unwrap_or(Rect::from_corners(Vec2::ZERO, Vec2::ZERO))
+        .inflate(MARGIN);
+
+    let mut svg = String::new();
+
+    writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="{} {} {} {}" width="{}" height="{}" font-family="sans-serif">"#,
+        bounds.min.x,
+        bounds.min.y,
+        bounds.width(),
+        bounds.height(),
+        bounds.width(),
+        bounds.height(),
+    )
+    .unwrap();
+
+    writeln!(
+        svg,
+        r#"<rect x="{}" y="{}" width="{}" height="{}" fill="{}"/>"#,
+        bounds.min.x,
+        bounds.min.y,
+        bounds.width(),
+        bounds.height(),
+        svg_color(plane_color.0),
+    )
+    .unwrap();
+
+    svg.push_str(&body);
+    svg.push_str("</svg>\n");
+
+    svg
+}
+
+/// Writes the background and text of a ui node, then its children on top.
+fn write_element(
+    svg: &mut String,
+    entity: Entity,
+    offset: Vec2,
+    element_q: &Query<(
+        &ComputedNode,
+        &GlobalTransform,
+        Option<&BackgroundColor>,
+        Option<&Children>,
+    )>,
+    text_q: &Query<(&Text, &TextFont, &TextColor, &TextLayout)>,
+) {
+    let Ok((computed_node, transform, background, children)) = element_q.get(entity) else {
+        return;
+    };
+
+    // hidden nodes are laid out with no size
+    if computed_node.is_empty() {
+        return;
+    }
+
+    let mut rect = node_rect(computed_node, transform);
+    rect.min -= offset;
+    rect.max -= offset;
+
+    if let Some(background) = background
+        && background.0.alpha() > 0.
+    {
+        writeln!(
+            svg,
+            r#"<rect x="{}" y="{}" width="{}" height="{}" fill="{}"/>"#,
+            rect.min.x,
+            rect.min.y,
+            rect.width(),
+            rect.height(),
+            svg_color(background.0),
+        )
+        .unwrap();
+    }
+
+    if let Ok((text, font, color, layout)) = text_q.get(entity)
+        && !text.0.is_empty()
+    {
+        let (x, anchor) = match layout.justify {
+            JustifyText::Center => (rect.center().x, "middle"),
+            JustifyText::Right => (rect.max.x, "end"),
+            _ => (rect.min.x, "start"),
+        };
+
+        writeln!(
+            svg,
+            r#"<text x="{}" y="{}" font-size="{}" fill="{}" text-anchor="{}" dominant-baseline="central">{}</text>"#,
+            x,
+            rect.center().y,
+            font.font_size,
+            svg_color(color.0),
+            anchor,
+            escape_xml(&text.0),
+        )
+        .unwrap();
+    }
+
+    for &child in children.into_iter().flatten() {
+        write_element(svg, child, offset, element_q, text_q);
+    }
+}
+
+fn svg_color(color: Color) -> String {
+    let [red, green, blue, _] = color.to_srgba().to_u8_array();
+
+    format!("rgba({}, {}, {}, {})", red, green, blue, color.alpha())
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,895 @@
+use bevy::{prelude::*, utils::HashMap};
+
+use crate::{
+    ControlPanelUi, CreateBaseUi, MainMenuItem, MainMenuUI, MenuName,
+    box_links::BoxLink,
+    drag_plane::SelectedBox,
+    factory_world::*,
+    logistics::{LogisticsBox, LogisticsKind, SplitRatio},
+    rational::Rational,
+    recipe_ui::RecipeBox,
+    text_input::{FocusedTextInput, TextInput, TextInputSubmitted},
+};
+
+pub fn build(app: &mut App) {
+    app.insert_resource(ChainTarget::default());
+
+    app.add_systems(Startup, create_chain_ui.after(CreateBaseUi));
+    app.add_systems(
+        Update,
+        (
+            set_chain_target,
+            track_share_box,
+            cycle_chain_resource,
+            update_chain_target_text,
+            submit_link_shares,
+            solve_chain_ui,
+        ),
+    );
+}
+
+/// How much of the demand at the end of a link is asked of the box at its start,
+/// relative to the other links bringing the same resource. Links without one have a share of 1.
+#[derive(Component, Clone, Copy)]
+pub struct LinkShare(pub Rational);
+
+#[derive(Debug)]
+pub enum ChainError {
+    NotARecipeBox,
+    /// The target box doesn't make the resource asked for.
+    DoesNotMake,
+    /// The boxes upstream of the target feed back into each other.
+    Cycle,
+    /// A smart splitter sends nothing down a link the chain needs something from.
+    ZeroRatio,
+}
+
+impl std::fmt::Display for ChainError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            ChainError::NotARecipeBox => "Target isn't a recipe box",
+            ChainError::DoesNotMake => "Target doesn't make that resource",
+            ChainError::Cycle => "The chain loops back on itself, use the solver instead",
+            ChainError::ZeroRatio => "A smart splitter has a ratio of 0 on a link the chain needs",
+        })
+    }
+}
+
+fn side_rate(side: &[(ResourceId, Rational)], resource_id: ResourceId) -> Rational {
+    side.iter()
+        .filter(|(other_id, _)| *other_id == resource_id)
+        .map(|(_, rate)| *rate)
+        .sum()
+}
+
+/// A link as chain solving sees it.
+#[derive(Clone, Copy)]
+pub struct ChainLink {
+    pub link: BoxLink,
+    /// The [`LinkShare`] of the link, 1 without one.
+    pub share: Rational,
+    /// The [`SplitRatio`] of the link, 1 without one. Only links leaving smart splitters use it.
+    pub ratio: Rational,
+}
+
+/// Finds the multipliers that make `target_box` output `rate` of a resource,
+/// and every box upstream of it through links make exactly what is taken from it.
+///
+/// Demand for an input is split between the links bringing it by their share.
+/// Logistics boxes pass on what is asked of them, smart splitters enough that the link
+/// asked for most gets it by its ratio.
+/// Boxes that aren't upstream of the target are left out of the result.
+pub fn back_propagate(
+    target_box: Entity,
+    resource_id: ResourceId,
+    rate: Rational,
+    box_recipes: &HashMap<Entity, &Recipe>,
+    logistics_kinds: &HashMap<Entity, LogisticsKind>,
+    links: &[ChainLink],
+) -> Result<HashMap<Entity, Rational>, ChainError> {
+    let target_recipe = box_recipes
+        .get(&target_box)
+        .ok_or(ChainError::NotARecipeBox)?;
+
+    let target_rate = side_rate(&target_recipe.products, resource_id);
+
+    if !target_rate.is_positive() {
+        return Err(ChainError::DoesNotMake);
+    }
+
+    // every recipe and logistics box the target draws from, directly or not
+    let mut upstream = vec![target_box];
+    let mut index = 0;
+
+    while let Some(&box_entity) = upstream.get(index) {
+        index += 1;
+
+        for chain_link in links.iter() {
+            let from_box = chain_link.link.from_box;
+
+            if chain_link.link.to_box == box_entity
+                && (box_recipes.contains_key(&from_box) || logistics_kinds.contains_key(&from_box))
+                && !upstream.contains(&from_box)
+            {
+                upstream.push(from_box);
+            }
+        }
+    }
+
+    let chain_links: Vec<&ChainLink> = links
+        .iter()
+        .filter(|chain_link| {
+            upstream.contains(&chain_link.link.from_box)
+                && upstream.contains(&chain_link.link.to_box)
+        })
+        .collect();
+
+    if chain_links
+        .iter()
+        .any(|chain_link| chain_link.link.from_box == target_box)
+    {
+        return Err(ChainError::Cycle);
+    }
+
+    // a box is known once everything it feeds in the chain is known
+    let mut waiting_on: HashMap<Entity, usize> = HashMap::default();
+
+    for chain_link in chain_links.iter() {
+        *waiting_on.entry(chain_link.link.from_box).or_default() += 1;
+    }
+
+    // what is asked of each chain link, by its index
+    let mut link_demands = vec![Rational::ZERO; chain_links.len()];
+    let mut multipliers: HashMap<Entity, Rational> = HashMap::default();
+    let mut solved = 0;
+    let mut ready = vec![target_box];
+
+    while let Some(box_entity) = ready.pop() {
+        solved += 1;
+
+        let outgoing = || {
+            chain_links
+                .iter()
+                .enumerate()
+                .filter(move |(_, chain_link)| chain_link.link.from_box == box_entity)
+        };
+
+        // how much of each resource a recipe box asks of the links bringing it,
+        // or how much a logistics box asks of all of them together
+        let (needs, throughput) = if let Some(recipe) = box_recipes.get(&box_entity) {
+            let multiplier = if box_entity == target_box {
+                rate / target_rate
+            } else {
+                // enough of every product to cover whatever is asked of it most
+                recipe
+                    .products
+                    .iter()
+                    .map(|&(resource_id, product_rate)| {
+                        let demand: Rational = outgoing()
+                            .filter(|(_, chain_link)| chain_link.link.resource_id == resource_id)
+                            .map(|(index, _)| link_demands[index])
+                            .sum();
+                        demand / product_rate
+                    })
+                    .max()
+                    .unwrap_or(Rational::ZERO)
+            };
+
+            multipliers.insert(box_entity, multiplier);
+
+            let needs: Vec<(ResourceId, Rational)> = recipe
+                .ingredients
+                .iter()
+                .map(|&(resource_id, rate)| (resource_id, rate * multiplier))
+                .collect();
+
+            (needs, None)
+        } else {
+            let throughput = match logistics_kinds[&box_entity] {
+                LogisticsKind::SmartSplitter => {
+                    let total_ratio: Rational = links
+                        .iter()
+                        .filter(|chain_link| chain_link.link.from_box == box_entity)
+                        .map(|chain_link| chain_link.ratio)
+                        .sum();
+
+                    let mut throughput = Rational::ZERO;
+
+                    for (index, chain_link) in outgoing() {
+                        if !link_demands[index].is_positive() {
+                            continue;
+                        }
+
+                        if !chain_link.ratio.is_positive() {
+                            return Err(ChainError::ZeroRatio);
+                        }
+
+                        throughput =
+                            throughput.max(link_demands[index] * total_ratio / chain_link.ratio);
+                    }
+
+                    throughput
+                }
+                _ => outgoing().map(|(index, _)| link_demands[index]).sum(),
+            };
+
+            (Vec::new(), Some(throughput))
+        };
+
+        let incoming: Vec<(usize, &&ChainLink)> = chain_links
+            .iter()
+            .enumerate()
+            .filter(|(_, chain_link)| chain_link.link.to_box == box_entity)
+            .collect();
+
+        for &(index, chain_link) in incoming.iter() {
+            let resource_id = chain_link.link.resource_id;
+            let need = throughput.unwrap_or_else(|| side_rate(&needs, resource_id));
+
+            let total_share: Rational = incoming
+                .iter()
+                .filter(|(_, other)| throughput.is_some() || other.link.resource_id == resource_id)
+                .map(|(_, other)| other.share)
+                .sum();
+
+            if total_share.is_positive() {
+                link_demands[index] = need * chain_link.share / total_share;
+            }
+
+            let waiting = waiting_on
+                .get_mut(&chain_link.link.from_box)
+                .expect("Every chain link is counted");
+            *waiting -= 1;
+
+            if *waiting == 0 {
+                ready.push(chain_link.link.from_box);
+            }
+        }
+    }
+
+    if solved < upstream.len() {
+        return Err(ChainError::Cycle);
+    }
+
+    Ok(multipliers)
+}
+
+/// Marks the recipe box being solved for.
+///
+/// It stays on the box while others are selected, so the shares of links further upstream can be edited.
+#[derive(Component)]
+pub struct ChainTargetBox;
+
+/// Which product of the target box to solve for.
+#[derive(Resource, Default)]
+struct ChainTarget {
+    resource_index: usize,
+}
+
+#[derive(Resource)]
+struct ChainUi {
+    target_text_entity: Entity,
+    resource_button_entity: Entity,
+    rate_input_entity: Entity,
+    share_title_entity: Entity,
+    share_list_entity: Entity,
+    status_text_entity: Entity,
+}
+
+#[derive(Component)]
+#[require(Button)]
+struct SetChainTargetButton;
+
+#[derive(Component)]
+#[require(Button)]
+struct ChainResourceButton;
+
+#[derive(Component)]
+#[require(Button)]
+struct SolveChainButton;
+
+#[derive(Component)]
+struct LinkShareInput {
+    link_entity: Entity,
+}
+
+fn create_chain_ui(
+    mut commands: Commands,
+    main_menu: Res<MainMenuUI>,
+    control_panel: Res<ControlPanelUi>,
+) {
+    let sub_menu_entity = commands
+        .spawn((Node {
+            display: Display::None,
+            flex_direction: FlexDirection::Column,
+            ..default()
+        },))
+        .set_parent(control_panel.control_panel_entity)
+        .id();
+
+    commands
+        .spawn((
+            MainMenuItem {
+                sub_menu_entity,
+                menu: MenuName::Chain,
+            },
+            Text::new("Chain"),
+            Node {
+                width: Val::Percent(100.0),
+                ..default()
+            },
+        ))
+        .set_parent(main_menu.main_menu_entity);
+
+    let target_text_entity = commands
+        .spawn(Text::new("No target"))
+        .set_parent(sub_menu_entity)
+        .id();
+
+    commands
+        .spawn((SetChainTargetButton, Text::new("Target Selected Box")))
+        .set_parent(sub_menu_entity);
+
+    let resource_button_entity = commands
+        .spawn((ChainResourceButton, Text::default()))
+        .set_parent(sub_menu_entity)
+        .id();
+
+    let rate_input_entity = commands
+        .spawn(TextInput::new("Rate per minute"))
+        .set_parent(sub_menu_entity)
+        .id();
+
+    commands
+        .spawn((SolveChainButton, Text::new("Solve Upstream")))
+        .set_parent(sub_menu_entity);
+
+    let share_title_entity = commands
+        .spawn(Text::new(NO_SHARE_BOX_TEXT))
+        .set_parent(sub_menu_entity)
+        .id();
+
+    let share_list_entity = commands
+        .spawn(Node {
+            flex_direction: FlexDirection::Column,
+            ..default()
+        })
+        .set_parent(sub_menu_entity)
+        .id();
+
+    let status_text_entity = commands
+        .spawn((
+            Text::default(),
+            Node {
+                max_width: Val::Px(300.),
+                ..default()
+            },
+        ))
+        .set_parent(sub_menu_entity)
+        .id();
+
+    commands.insert_resource(ChainUi {
+        target_text_entity,
+        resource_button_entity,
+        rate_input_entity,
+        share_title_entity,
+        share_list_entity,
+        status_text_entity,
+    });
+}
+
+const NO_SHARE_BOX_TEXT: &str = "Select a box to edit the shares of its incoming links";
+
+/// Moves the target to the selected recipe box.
+#[allow(clippy::too_many_arguments)]
+fn set_chain_target(
+    mut commands: Commands,
+    button_q: Query<&Interaction, (Changed<Interaction>, With<SetChainTargetButton>)>,
+    selected_q: Query<Entity, With<SelectedBox>>,
+    recipe_box_q: Query<&RecipeBox>,
+    target_q: Query<Entity, With<ChainTargetBox>>,
+    mut chain_target: ResMut<ChainTarget>,
+    chain_ui: Res<ChainUi>,
+    mut text_q: Query<&mut Text>,
+) {
+    let Ok(Interaction::Pressed) = button_q.get_single() else {
+        return;
+    };
+
+    let mut selected = selected_q.iter();
+    let Some(box_entity) = (match (selected.next(), selected.next()) {
+        (Some(box_entity), None) if recipe_box_q.contains(box_entity) => Some(box_entity),
+        _ => None,
+    }) else {
+        text_q
+            .get_mut(chain_ui.status_text_entity)
+            .expect("Should be a text")
+            .0 = "Select one recipe box".into();
+        return;
+    };
+
+    for target_entity in target_q.iter() {
+        commands.entity(target_entity).remove::<ChainTargetBox>();
+    }
+
+    commands.entity(box_entity).insert(ChainTargetBox);
+    chain_target.resource_index = 0;
+}
+
+/// Follows the selection, and lists the links into the selected box with their shares.
+#[allow(clippy::too_many_arguments)]
+fn track_share_box(
+    mut commands: Commands,
+    selected_q: Query<Entity, With<SelectedBox>>,
+    recipe_box_q: Query<&RecipeBox>,
+    link_q: Query<(Entity, &BoxLink, Option<&LinkShare>)>,
+    mut share_box: Local<Option<Entity>>,
+    chain_ui: Res<ChainUi>,
+    world: Res<FactoryWorld>,
+    mut text_q: Query<&mut Text>,
+) {
+    let mut selected = selected_q.iter();
+    let box_entity = match (selected.next(), selected.next()) {
+        (Some(box_entity), None) if recipe_box_q.contains(box_entity) => Some(box_entity),
+        _ => None,
+    };
+
+    if box_entity == *share_box && !world.is_changed() {
+        return;
+    }
+
+    *share_box = box_entity;
+
+    commands
+        .entity(chain_ui.share_list_entity)
+        .despawn_descendants();
+
+    let recipe = box_entity
+        .and_then(|box_entity| recipe_box_q.get(box_entity).ok())
+        .and_then(|recipe_box| world.get_recipe(recipe_box.recipe_id).ok());
+
+    let mut share_title = text_q
+        .get_mut(chain_ui.share_title_entity)
+        .expect("Should be a text");
+
+    let (Some(box_entity), Some(recipe)) = (box_entity, recipe) else {
+        share_title.0 = NO_SHARE_BOX_TEXT.into();
+        return;
+    };
+
+    share_title.0 = format!("Shares of links into {}:", recipe.name);
+
+    for (link_entity, link, share) in link_q.iter() {
+        if link.to_box != box_entity {
+            continue;
+        }
+
+        let from_name = recipe_box_q
+            .get(link.from_box)
+            .ok()
+            .and_then(|recipe_box| world.get_recipe(recipe_box.recipe_id).ok())
+            .map_or("?", |recipe| recipe.name.as_str());
+
+        let resource_name = world
+            .get_resource_name(link.resource_id)
+            .expect("Resource name should exist");
+
+        commands
+            .spawn(Node {
+                flex_direction: FlexDirection::Row,
+                column_gap: Val::Px(8.),
+                ..default()
+            })
+            .with_children(|builder| {
+                builder.spawn(Text::new(format!("{} from {}", resource_name, from_name)));
+
+                builder.spawn((
+                    LinkShareInput { link_entity },
+                    TextInput {
+                        value: share.map_or(Rational::ONE, |share| share.0).to_string(),
+                        placeholder: "share".into(),
+                    },
+                    BackgroundColor(Color::BLACK),
+                ));
+            })
+            .set_parent(chain_ui.share_list_entity);
+    }
+}
+
+/// The resource the target box should make, out of its products.
+fn chain_resource(
+    chain_target: &ChainTarget,
+    recipe_box: Option<&RecipeBox>,
+    world: &FactoryWorld,
+) -> Option<ResourceId> {
+    let recipe = world.get_recipe(recipe_box?.recipe_id).ok()?;
+
+    recipe
+        .products
+        .get(chain_target.resource_index % recipe.products.len().max(1))
+        .map(|&(resource_id, _)| resource_id)
+}
+
+fn cycle_chain_resource(
+    button_q: Query<&Interaction, (Changed<Interaction>, With<ChainResourceButton>)>,
+    mut chain_target: ResMut<ChainTarget>,
+) {
+    if let Ok(Interaction::Pressed) = button_q.get_single() {
+        chain_target.resource_index += 1;
+    }
+}
+
+/// Shows the target box and the resource it should make.
+fn update_chain_target_text(
+    target_q: Query<&RecipeBox, With<ChainTargetBox>>,
+    chain_target: Res<ChainTarget>,
+    world: Res<FactoryWorld>,
+    chain_ui: Res<ChainUi>,
+    mut text_q: Query<&mut Text>,
+) {
+    let recipe_box = target_q.get_single().ok();
+
+    let target_text =
+        match recipe_box.and_then(|recipe_box| world.get_recipe(recipe_box.recipe_id).ok()) {
+            Some(recipe) => format!("Target: {}", recipe.name),
+            None => "No target".into(),
+        };
+
+    let resource_text = match chain_resource(&chain_target, recipe_box, world.as_ref()) {
+        Some(resource_id) => format!(
+            "Resource: {}",
+            world
+                .get_resource_name(resource_id)
+                .expect("Resource name should exist")
+        ),
+        None => String::new(),
+    };
+
+    for (text_entity, new_text) in [
+        (chain_ui.target_text_entity, target_text),
+        (chain_ui.resource_button_entity, resource_text),
+    ] {
+        let mut text = text_q.get_mut(text_entity).expect("Should be a text");
+
+        if text.0 != new_text {
+            text.0 = new_text;
+        }
+    }
+}
+
+fn submit_link_shares(
+    mut commands: Commands,
+    mut submitted_events: EventReader<TextInputSubmitted>,
+    mut input_q: Query<(&LinkShareInput, &mut TextInput)>,
+    share_q: Query<&LinkShare>,
+    mut focused: ResMut<FocusedTextInput>,
+) {
+    for event in submitted_events.read() {
+        let Ok((share_input, mut text_input)) = input_q.get_mut(event.entity) else {
+            continue;
+        };
+
+        let mut share = share_q
+            .get(share_input.link_entity)
+            .map_or(Rational::ONE, |share| share.0);
+
+        match event.value.parse::<Rational>() {
+            Ok(new_share) if !new_share.is_negative() => {
+                share = new_share;
+
+                if let Some(mut link_commands) = commands.get_entity(share_input.link_entity) {
+                    link_commands.insert(LinkShare(share));
+                }
+            }
+            _ => info!("Invalid link share \"{}\"", event.value),
+        }
+
+        text_input.value = share.to_string();
+        focused.0 = None;
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn solve_chain_ui(
+    button_q: Query<&Interaction, (Changed<Interaction>, With<SolveChainButton>)>,
+    target_q: Query<Entity, With<ChainTargetBox>>,
+    chain_target: Res<ChainTarget>,
+    chain_ui: Res<ChainUi>,
+    input_q: Query<&TextInput>,
+    mut recipe_box_q: Query<(Entity, &mut RecipeBox)>,
+    logistics_box_q: Query<(Entity, &LogisticsBox)>,
+    link_q: Query<(&BoxLink, Option<&LinkShare>, Option<&SplitRatio>)>,
+    world: Res<FactoryWorld>,
+    mut text_q: Query<&mut Text>,
+) {
+    let Ok(Interaction::Pressed) = button_q.get_single() else {
+        return;
+    };
+
+    let mut status_text = text_q
+        .get_mut(chain_ui.status_text_entity)
+        .expect("Should be a text");
+
+    let target_box = target_q.get_single().ok();
+
+    let recipe_box = target_box
+        .and_then(|box_entity| recipe_box_q.get(box_entity).ok())
+        .map(|(_, recipe_box)| recipe_box);
+
+    let (Some(target_box), Some(resource_id)) = (
+        target_box,
+        chain_resource(&chain_target, recipe_box, world.as_ref()),
+    ) else {
+        status_text.0 = "Set a target box first".into();
+        return;
+    };
+
+    let rate_input = input_q
+        .get(chain_ui.rate_input_entity)
+        .expect("Should be a text input");
+
+    let rate = match rate_input.value.parse::<Rational>() {
+        Ok(rate) if rate.is_positive() => rate,
+        _ => {
+            status_text.0 = format!("Invalid rate \"{}\"", rate_input.value);
+            return;
+        }
+    };
+
+    let box_recipes: HashMap<Entity, &Recipe> = recipe_box_q
+        .iter()
+        .filter_map(|(box_entity, recipe_box)| {
+            Some((box_entity, world.get_recipe(recipe_box.recipe_id).ok()?))
+        })
+        .collect();
+
+    let logistics_kinds: HashMap<Entity, LogisticsKind> = logistics_box_q
+        .iter()
+        .map(|(box_entity, logistics_box)| (box_entity, logistics_box.kind))
+        .collect();
+
+    let links: Vec<ChainLink> = link_q
+        .iter()
+        .map(|(link, share, ratio)| ChainLink {
+            link: *link,
+            share: share.map_or(Rational::ONE, |share| share.0),
+            ratio: ratio.map_or(Rational::ONE, |ratio| ratio.0),
+        })
+        .collect();
+
+    let multipliers = match back_propagate(
+        target_box,
+        resource_id,
+        rate,
+        &box_recipes,
+        &logistics_kinds,
+        &links,
+    ) {
+        Ok(multipliers) => multipliers,
+        Err(err) => {
+            status_text.0 = err.to_string();
+            return;
+        }
+    };
+
+    for (&box_entity, &multiplier) in multipliers.iter() {
+        if let Ok((_, mut recipe_box)) = recipe_box_q.get_mut(box_entity) {
+            recipe_box.multiplier = multiplier;
+        }
+    }
+
+    status_text.0 = format!("Set the multipliers of {} boxes", multipliers.len());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn recipe(ingredients: &[(ResourceId, i128)], products: &[(ResourceId, i128)]) -> Recipe {
+        let rates = |side: &[(ResourceId, i128)]| {
+            side.iter()
+                .map(|&(resource_id, rate)| (resource_id, Rational::from_integer(rate)))
+                .collect()
+        };
+
+        Recipe {
+            name: String::new(),
+            ingredients: rates(ingredients),
+            products: rates(products),
+            building: None,
+            cycle_seconds: None,
+        }
+    }
+
+    fn link(from: u32, to: u32, resource_id: ResourceId, share: i128) -> ChainLink {
+        ChainLink {
+            link: BoxLink {
+                from_box: Entity::from_raw(from),
+                to_box: Entity::from_raw(to),
+                resource_id,
+            },
+            share: Rational::from_integer(share),
+            ratio: Rational::ONE,
+        }
+    }
+
+    #[test]
+    fn multipliers_follow_the_chain_upstream() {
+        let mut world = FactoryWorld::default();
+        let ore = world.get_resource_id("Ore");
+        let plate = world.get_resource_id("Plate");
+        let screw = world.get_resource_id("Screw");
+
+        let plates = recipe(&[(ore, 30)], &[(plate, 20)]);
+        let screws = recipe(&[(plate, 10)], &[(screw, 40)]);
+        let unrelated = recipe(&[], &[(ore, 60)]);
+        let box_recipes = HashMap::from_iter([
+            (Entity::from_raw(0), &plates),
+            (Entity::from_raw(1), &screws),
+            (Entity::from_raw(2), &unrelated),
+        ]);
+
+        let multipliers = back_propagate(
+            Entity::from_raw(1),
+            screw,
+            Rational::from_integer(80),
+            &box_recipes,
+            &HashMap::default(),
+            &[link(0, 1, plate, 1)],
+        )
+        .unwrap();
+
+        assert_eq!(multipliers.len(), 2);
+        assert_eq!(multipliers[&Entity::from_raw(1)], Rational::from_integer(2));
+        assert_eq!(multipliers[&Entity::from_raw(0)], Rational::ONE);
+    }
+
+    #[test]
+    fn demand_is_split_by_link_shares() {
+        let mut world = FactoryWorld::default();
+        let plate = world.get_resource_id("Plate");
+        let screw = world.get_resource_id("Screw");
+
+        let plates = recipe(&[], &[(plate, 20)]);
+        let screws = recipe(&[(plate, 20)], &[(screw, 40)]);
+        let box_recipes = HashMap::from_iter([
+            (Entity::from_raw(0), &plates),
+            (Entity::from_raw(1), &plates),
+            (Entity::from_raw(2), &screws),
+        ]);
+
+        let multipliers = back_propagate(
+            Entity::from_raw(2),
+            screw,
+            Rational::from_integer(40),
+            &box_recipes,
+            &HashMap::default(),
+            &[link(0, 2, plate, 1), link(1, 2, plate, 3)],
+        )
+        .unwrap();
+
+        assert_eq!(multipliers[&Entity::from_raw(0)], Rational::new(1, 4));
+        assert_eq!(multipliers[&Entity::from_raw(1)], Rational::new(3, 4));
+    }
+
+    #[test]
+    fn producers_cover_their_largest_demand() {
+        let mut world = FactoryWorld::default();
+        let oil = world.get_resource_id("Oil");
+        let fuel = world.get_resource_id("Fuel");
+        let resin = world.get_resource_id("Resin");
+        let plastic = world.get_resource_id("Plastic");
+
+        let refinery = recipe(&[(oil, 30)], &[(fuel, 10), (resin, 20)]);
+        let plastics = recipe(&[(fuel, 30), (resin, 20)], &[(plastic, 10)]);
+        let box_recipes = HashMap::from_iter([
+            (Entity::from_raw(0), &refinery),
+            (Entity::from_raw(1), &plastics),
+        ]);
+
+        let multipliers = back_propagate(
+            Entity::from_raw(1),
+            plastic,
+            Rational::from_integer(10),
+            &box_recipes,
+            &HashMap::default(),
+            &[link(0, 1, fuel, 1), link(0, 1, resin, 1)],
+        )
+        .unwrap();
+
+        assert_eq!(multipliers[&Entity::from_raw(0)], Rational::from_integer(3));
+    }
+
+    #[test]
+    fn bad_targets_and_loops_are_errors() {
+        let mut world = FactoryWorld::default();
+        let plate = world.get_resource_id("Plate");
+        let screw = world.get_resource_id("Screw");
+
+        let plates = recipe(&[(screw, 10)], &[(plate, 20)]);
+        let screws = recipe(&[(plate, 10)], &[(screw, 40)]);
+        let box_recipes = HashMap::from_iter([
+            (Entity::from_raw(0), &plates),
+            (Entity::from_raw(1), &screws),
+        ]);
+        let links = [link(0, 1, plate, 1), link(1, 0, screw, 1)];
+        let rate = Rational::from_integer(10);
+
+        assert!(matches!(
+            back_propagate(
+                Entity::from_raw(5),
+                screw,
+                rate,
+                &box_recipes,
+                &HashMap::default(),
+                &links
+            ),
+            Err(ChainError::NotARecipeBox)
+        ));
+        assert!(matches!(
+            back_propagate(
+                Entity::from_raw(1),
+                plate,
+                rate,
+                &box_recipes,
+                &HashMap::default(),
+                &links
+            ),
+            Err(ChainError::DoesNotMake)
+        ));
+        assert!(matches!(
+            back_propagate(
+                Entity::from_raw(1),
+                screw,
+                rate,
+                &box_recipes,
+                &HashMap::default(),
+                &links
+            ),
+            Err(ChainError::Cycle)
+        ));
+    }
+
+    #[test]
+    fn demand_passes_through_logistics_boxes() {
+        let mut world = FactoryWorld::default();
+        let plate = world.get_resource_id("Plate");
+        let screw = world.get_resource_id("Screw");
+
+        // two plate boxes merged, then a smart splitter sending a third of it to the screws
+        let plates = recipe(&[], &[(plate, 20)]);
+        let screws = recipe(&[(plate, 20)], &[(screw, 40)]);
+        let box_recipes = HashMap::from_iter([
+            (Entity::from_raw(0), &plates),
+            (Entity::from_raw(1), &plates),
+            (Entity::from_raw(4), &screws),
+        ]);
+        let logistics_kinds = HashMap::from_iter([
+            (Entity::from_raw(2), LogisticsKind::Merger),
+            (Entity::from_raw(3), LogisticsKind::SmartSplitter),
+        ]);
+        let mut to_storage = link(3, 5, plate, 1);
+        to_storage.ratio = Rational::from_integer(2);
+
+        let multipliers = back_propagate(
+            Entity::from_raw(4),
+            screw,
+            Rational::from_integer(40),
+            &box_recipes,
+            &logistics_kinds,
+            &[
+                link(0, 2, plate, 1),
+                link(1, 2, plate, 1),
+                link(2, 3, plate, 1),
+                link(3, 4, plate, 1),
+                to_storage,
+            ],
+        )
+        .unwrap();
+
+        assert_eq!(multipliers.len(), 3);
+        assert_eq!(multipliers[&Entity::from_raw(4)], Rational::ONE);
+        assert_eq!(multipliers[&Entity::from_raw(0)], Rational::new(3, 2));
+        assert_eq!(multipliers[&Entity::from_raw(1)], Rational::new(3, 2));
+    }
+}
@@ -2,6 +2,11 @@ use bevy::{
     input::mouse::AccumulatedMouseScroll, prelude::*, ui::FocusPolicy, window::PrimaryWindow,
 };
 
+use crate::keymap::AppAction;
+
+/// How fast the pan keys scroll the plane, in pixels per second.
+const KEY_PAN_SPEED: f32 = 600.;
+
 pub fn build(app: &mut App) {
     app.insert_resource(PlaneScroll {
         scroll_position: Vec2::ZERO,
@@ -10,9 +15,12 @@ pub fn build(app: &mut App) {
     app.add_systems(
         Update,
         (
+            select_drag_boxes,
+            update_selection_outlines,
             move_drag_boxes,
             update_plane_drag,
             update_plane_scroll,
+            pan_plane_with_actions,
             update_drag_box_positions,
         ),
     );
@@ -33,6 +41,10 @@ pub struct DragBox {
     pub position: Vec2,
 }
 
+/// Marks a drag box as part of the current selection.
+#[derive(Component)]
+pub struct SelectedBox;
+
 struct CurrentBoxDrag {
     drag_box_entity: Entity,
     start_mouse_position: Vec2,
@@ -95,11 +107,32 @@ fn update_drag_box_positions(
     }
 }
 
+fn pan_plane_with_actions(
+    mut actions: EventReader<AppAction>,
+    time: Res<Time>,
+    mut plane_scroll: ResMut<PlaneScroll>,
+) {
+    let pan_distance = KEY_PAN_SPEED * time.delta_secs();
+
+    for action in actions.read() {
+        // panning right moves the boxes left
+        match action {
+            AppAction::PanUp => plane_scroll.scroll_position.y += pan_distance,
+            AppAction::PanDown => plane_scroll.scroll_position.y -= pan_distance,
+            AppAction::PanLeft => plane_scroll.scroll_position.x += pan_distance,
+            AppAction::PanRight => plane_scroll.scroll_position.x -= pan_distance,
+            AppAction::ResetView => plane_scroll.scroll_position = Vec2::ZERO,
+            _ => (),
+        }
+    }
+}
+
 struct CurrentPlaneDrag {
     start_mouse_position: Vec2,
     start_plane_position: Vec2,
 }
 
+#[allow(clippy::collapsible_if)]
 fn update_plane_drag(
     mouse_input: Res<ButtonInput<MouseButton>>,
     window_q: Query<&Window, With<PrimaryWindow>>,
@@ -149,3 +182,65 @@ fn update_plane_scroll(
         plane_scroll.scroll_position += scroll_input.delta;
     }
 }
+
+/// Clicking a box selects it, ctrl clicking toggles it in the selection,
+/// and clicking the empty plane clears the selection.
+fn select_drag_boxes(
+    mut commands: Commands,
+    mouse_input: Res<ButtonInput<MouseButton>>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    drag_box_q: Query<(Entity, &Interaction, Has<SelectedBox>), With<DragBox>>,
+    drag_box_plane_q: Query<&Interaction, With<DragBoxPlane>>,
+    selected_q: Query<Entity, With<SelectedBox>>,
+) {
+    if !mouse_input.just_pressed(MouseButton::Left) {
+        return;
+    }
+
+    let toggle = keyboard_input.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+
+    let pressed_box = drag_box_q
+        .iter()
+        .find(|(_, interaction, _)| **interaction == Interaction::Pressed);
+
+    match pressed_box {
+        Some((drag_box_entity, _, true)) if toggle => {
+            commands.entity(drag_box_entity).remove::<SelectedBox>();
+        }
+        Some((drag_box_entity, _, false)) => {
+            if !toggle {
+                for selected_entity in selected_q.iter() {
+                    commands.entity(selected_entity).remove::<SelectedBox>();
+                }
+            }
+
+            commands.entity(drag_box_entity).insert(SelectedBox);
+        }
+        Some(_) => (),
+        None => {
+            if let Ok(Interaction::Pressed) = drag_box_plane_q.get_single() {
+                for selected_entity in selected_q.iter() {
+                    commands.entity(selected_entity).remove::<SelectedBox>();
+                }
+            }
+        }
+    }
+}
+
+fn update_selection_outlines(
+    mut commands: Commands,
+    selected_q: Query<Entity, Added<SelectedBox>>,
+    mut deselected: RemovedComponents<SelectedBox>,
+) {
+    for drag_box_entity in selected_q.iter() {
+        commands
+            .entity(drag_box_entity)
+            .insert(Outline::new(Val::Px(2.), Val::ZERO, Color::WHITE));
+    }
+
+    for drag_box_entity in deselected.read() {
+        if let Some(mut entity_commands) = commands.get_entity(drag_box_entity) {
+            entity_commands.remove::<Outline>();
+        }
+    }
+}
@@ -0,0 +1,74 @@
+use bevy::prelude::*;
+
+use crate::{
+    blueprints::{DUPLICATE_OFFSET, capture_blueprint, flatten_boxes, insert_blueprint},
+    boundary_boxes::BoundaryBox,
+    box_groups::BoxGroup,
+    box_links::BoxLink,
+    chain_solve::LinkShare,
+    drag_plane::{DragBox, DragBoxPlane, SelectedBox},
+    factory_world::FactoryWorld,
+    keymap::{AppAction, action_sent},
+    logistics::{LogisticsBox, SplitRatio},
+    recipe_ui::RecipeBox,
+};
+
+pub fn build(app: &mut App) {
+    app.add_systems(Update, duplicate_selected_boxes);
+}
+
+/// Copies the selected boxes and the links between them, and selects the copies instead.
+#[allow(clippy::type_complexity)]
+fn duplicate_selected_boxes(
+    mut commands: Commands,
+    mut actions: EventReader<AppAction>,
+    selected_q: Query<Entity, With<SelectedBox>>,
+    drag_box_q: Query<(
+        &DragBox,
+        Option<&RecipeBox>,
+        Option<&BoundaryBox>,
+        Option<&LogisticsBox>,
+        Option<&BoxGroup>,
+    )>,
+    link_q: Query<(&BoxLink, Option<&SplitRatio>, Option<&LinkShare>)>,
+    plane_q: Query<Entity, With<DragBoxPlane>>,
+    mut world: ResMut<FactoryWorld>,
+) {
+    if !action_sent(&mut actions, AppAction::DuplicateSelected) {
+        return;
+    }
+
+    let Some(origin) = flatten_boxes(selected_q.iter(), &drag_box_q)
+        .into_iter()
+        .map(|(_, position)| position)
+        .reduce(Vec2::min)
+    else {
+        return;
+    };
+
+    let blueprint = capture_blueprint(
+        "Duplicate".into(),
+        selected_q.iter(),
+        &drag_box_q,
+        &link_q,
+        world.as_ref(),
+    );
+
+    let root_plane_entity = plane_q.get_single().expect("Should be one root plane");
+
+    let (box_entities, _) = insert_blueprint(
+        &mut commands,
+        root_plane_entity,
+        origin + DUPLICATE_OFFSET,
+        &blueprint,
+        world.as_mut(),
+    );
+
+    for selected_entity in selected_q.iter() {
+        commands.entity(selected_entity).remove::<SelectedBox>();
+    }
+
+    for box_entity in box_entities {
+        commands.entity(box_entity).insert(SelectedBox);
+    }
+}
@@ -1,9 +1,32 @@
-use bevy::{prelude::*, utils::HashMap};
+use bevy::{
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::rational::Rational;
+
+/// Where the names of locked recipes are kept between runs, relative to the working directory.
+const LOCKS_PATH: &str = "locked_recipes.json";
 
 pub fn build(app: &mut App) {
-    app.insert_resource(FactoryWorld::default());
+    let mut world = FactoryWorld::default();
+
+    for extractor in default_extractors() {
+        world.insert_extractor(extractor);
+    }
+
+    world.locked_recipes = load_recipe_locks(LOCKS_PATH);
+
+    app.insert_resource(world);
+    app.add_event::<RecipeEvent>();
+
+    app.add_systems(Update, save_recipe_locks);
 }
 
+/// The resource recipes use to stand for power, in MJ per minute.
+pub const POWER_RESOURCE: &str = "Mega Joule";
+
 #[derive(Default, Resource)]
 pub struct FactoryWorld {
     next_resource_id: u32,
@@ -12,6 +35,11 @@ pub struct FactoryWorld {
     next_recipe_id: u32,
     recipe_id_map: HashMap<String, RecipeId>,
     recipes: HashMap<RecipeId, Recipe>,
+    /// Names of the recipes the solver can't use yet, like alternates that haven't been researched.
+    ///
+    /// Kept by name so a lock outlives its recipe being removed and loaded again.
+    locked_recipes: HashSet<String>,
+    extractors: Vec<Extractor>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -20,9 +48,108 @@ pub struct ResourceId(u32);
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct RecipeId(u32);
 
+/// A recipe consumes its ingredients and makes its products.
+///
+/// The same resource can be on both sides, like a catalyst that's used up and given back.
 pub struct Recipe {
     pub name: String,
-    pub ratios: Vec<(ResourceId, f32)>,
+    /// Per minute rates of the resources used, all positive.
+    pub ingredients: Vec<(ResourceId, Rational)>,
+    /// Per minute rates of the resources made, all positive.
+    pub products: Vec<(ResourceId, Rational)>,
+    /// The building the recipe is made in, if known.
+    pub building: Option<String>,
+    /// How long one cycle of the recipe takes, if it was given per cycle.
+    pub cycle_seconds: Option<Rational>,
+}
+
+/// How rich a resource node is, which scales what an extractor on it makes.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Purity {
+    Impure,
+    Normal,
+    Pure,
+}
+
+impl Purity {
+    pub fn factor(self) -> Rational {
+        match self {
+            Purity::Impure => Rational::new(1, 2),
+            Purity::Normal => Rational::ONE,
+            Purity::Pure => Rational::from_integer(2),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Purity::Impure => "Impure",
+            Purity::Normal => "Normal",
+            Purity::Pure => "Pure",
+        }
+    }
+
+    pub fn next(self) -> Purity {
+        match self {
+            Purity::Impure => Purity::Normal,
+            Purity::Normal => Purity::Pure,
+            Purity::Pure => Purity::Impure,
+        }
+    }
+}
+
+/// A building that takes a raw resource out of a node, like a miner or a water extractor.
+pub struct Extractor {
+    pub name: String,
+    /// Per minute on a normal node at 100% clock speed.
+    pub base_rate: Rational,
+    /// Whether the purity of the node matters, water extractors make the same anywhere.
+    pub uses_purity: bool,
+}
+
+impl Extractor {
+    /// The fastest an extractor can be overclocked, where one is 100%.
+    pub fn max_clock() -> Rational {
+        Rational::new(5, 2)
+    }
+
+    /// Per minute made by one extractor on each of `node_count` nodes, all running at `clock`.
+    pub fn rate(&self, purity: Purity, node_count: u32, clock: Rational) -> Rational {
+        let purity_factor = if self.uses_purity {
+            purity.factor()
+        } else {
+            Rational::ONE
+        };
+
+        self.base_rate * purity_factor * clock * Rational::from_integer(node_count.into())
+    }
+}
+
+/// The extractors of the base game, satellite nodes of a resource well count as nodes
+/// for the pressurizer.
+pub fn default_extractors() -> Vec<Extractor> {
+    [
+        ("Miner Mk.1", 60, true),
+        ("Miner Mk.2", 120, true),
+        ("Miner Mk.3", 240, true),
+        ("Water Extractor", 120, false),
+        ("Oil Extractor", 120, true),
+        ("Resource Well Pressurizer", 60, true),
+    ]
+    .into_iter()
+    .map(|(name, base_rate, uses_purity)| Extractor {
+        name: name.into(),
+        base_rate: Rational::from_integer(base_rate),
+        uses_purity,
+    })
+    .collect()
+}
+
+/// Sent when a recipe already in the [`FactoryWorld`] is changed or removed,
+/// so anything showing it can catch up.
+#[derive(Event, Debug, Clone, Copy)]
+pub enum RecipeEvent {
+    Updated(RecipeId),
+    Removed(RecipeId),
 }
 
 impl FactoryWorld {
@@ -38,6 +165,11 @@ impl FactoryWorld {
         }
     }
 
+    /// Looks up a resource without adding it if it doesn't exist.
+    pub fn find_resource_id(&self, resource_name: &str) -> Option<ResourceId> {
+        self.resource_id_map.get(resource_name).copied()
+    }
+
     pub fn get_resource_name(&self, resource_id: ResourceId) -> Option<&str> {
         self.resource_name_map.get(&resource_id).map(String::as_str)
     }
@@ -66,6 +198,24 @@ impl FactoryWorld {
         self.recipes.get(&recipe_id).ok_or(InvalidRecipeError)
     }
 
+    /// replaces the ingredients, products, building and cycle time of a recipe, keeping its name and id
+    pub fn update_recipe(
+        &mut self,
+        recipe_id: RecipeId,
+        new_recipe: Recipe,
+    ) -> Result<(), InvalidRecipeError> {
+        let recipe = self.recipes.get_mut(&recipe_id).ok_or(InvalidRecipeError)?;
+        recipe.ingredients = new_recipe.ingredients;
+        recipe.products = new_recipe.products;
+        recipe.building = new_recipe.building;
+        recipe.cycle_seconds = new_recipe.cycle_seconds;
+        Ok(())
+    }
+
+    pub fn iter_recipes(&self) -> impl Iterator<Item = (RecipeId, &Recipe)> {
+        self.recipes.iter().map(|(id, recipe)| (*id, recipe))
+    }
+
     pub fn remove_recipe(&mut self, recipe_id: RecipeId) -> Option<Recipe> {
         let recipe = self.recipes.remove(&recipe_id)?;
 
@@ -75,14 +225,186 @@ impl FactoryWorld {
 
         Some(recipe)
     }
+
+    pub fn is_recipe_locked(&self, recipe_id: RecipeId) -> bool {
+        self.recipes
+            .get(&recipe_id)
+            .is_some_and(|recipe| self.locked_recipes.contains(&recipe.name))
+    }
+
+    /// Locked recipes stay in the world, but the solver won't use them.
+    pub fn set_recipe_locked(&mut self, recipe_id: RecipeId, locked: bool) {
+        let Some(recipe) = self.recipes.get(&recipe_id) else {
+            return;
+        };
+
+        if locked {
+            self.locked_recipes.insert(recipe.name.clone());
+        } else {
+            self.locked_recipes.remove(&recipe.name);
+        }
+    }
+
+    /// Adds an extractor, replacing any with the same name.
+    pub fn insert_extractor(&mut self, extractor: Extractor) {
+        match self
+            .extractors
+            .iter_mut()
+            .find(|existing| existing.name == extractor.name)
+        {
+            Some(existing) => *existing = extractor,
+            None => self.extractors.push(extractor),
+        }
+    }
+
+    pub fn get_extractor(&self, index: usize) -> Option<&Extractor> {
+        self.extractors.get(index)
+    }
+
+    pub fn iter_extractors(&self) -> impl Iterator<Item = &Extractor> {
+        self.extractors.iter()
+    }
+
+    /// Writes the recipes as a Graphviz DOT graph, with an edge from each ingredient to the
+    /// recipe and from the recipe to each product, labelled with the per minute rate.
+    pub fn recipe_graph_dot(&self) -> String {
+        let mut recipes: Vec<(RecipeId, &Recipe)> = self.iter_recipes().collect();
+        recipes.sort_by(|(_, a), (_, b)| a.name.cmp(&b.name));
+
+        let mut resources: Vec<(&ResourceId, &String)> = self.resource_name_map.iter().collect();
+        resources.sort_by_key(|(_, name)| *name);
+
+        let mut dot = String::from("digraph recipes {\n    rankdir=LR;\n");
+
+        for (resource_id, name) in resources {
+            dot += &format!(
+                "    resource_{} [label={}, shape=ellipse];\n",
+                resource_id.0,
+                dot_quote(name)
+            );
+        }
+
+        for (recipe_id, recipe) in recipes {
+            dot += &format!(
+                "    recipe_{} [label={}, shape=box];\n",
+                recipe_id.0,
+                dot_quote(&recipe.name)
+            );
+
+            for (resource_id, rate) in recipe.ingredients.iter() {
+                dot += &format!(
+                    "    resource_{} -> recipe_{} [label=\"{}\"];\n",
+                    resource_id.0, recipe_id.0, rate
+                );
+            }
+
+            for (resource_id, rate) in recipe.products.iter() {
+                dot += &format!(
+                    "    recipe_{} -> resource_{} [label=\"{}\"];\n",
+                    recipe_id.0, resource_id.0, rate
+                );
+            }
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
 }
 
 impl Recipe {
-    pub fn iter_ratios(&self) -> impl Iterator<Item = (ResourceId, f32)> {
-        self.ratios.iter().copied()
+    /// Builds a recipe from signed per minute rates, where inputs are negative.
+    pub fn from_rates(
+        name: String,
+        rates: impl IntoIterator<Item = (ResourceId, Rational)>,
+    ) -> Recipe {
+        let (ingredients, products): (Vec<_>, Vec<_>) = rates
+            .into_iter()
+            .filter(|(_, rate)| !rate.is_zero())
+            .partition(|(_, rate)| rate.is_negative());
+
+        Recipe {
+            name,
+            ingredients: ingredients
+                .into_iter()
+                .map(|(resource_id, rate)| (resource_id, -rate))
+                .collect(),
+            products,
+            building: None,
+            cycle_seconds: None,
+        }
+    }
+
+    /// Builds a recipe from the quantities used and made in one cycle,
+    /// normalising them into per minute rates.
+    pub fn from_cycle(
+        name: String,
+        cycle_seconds: Rational,
+        ingredients: impl IntoIterator<Item = (ResourceId, Rational)>,
+        products: impl IntoIterator<Item = (ResourceId, Rational)>,
+    ) -> Recipe {
+        let cycles_per_minute = Rational::from_integer(60) / cycle_seconds;
+
+        let per_minute = |(resource_id, quantity): (ResourceId, Rational)| {
+            (resource_id, quantity * cycles_per_minute)
+        };
+
+        Recipe {
+            name,
+            ingredients: ingredients.into_iter().map(per_minute).collect(),
+            products: products.into_iter().map(per_minute).collect(),
+            building: None,
+            cycle_seconds: Some(cycle_seconds),
+        }
+    }
+
+    /// Converts a per minute rate of this recipe into the quantity per cycle,
+    /// if the recipe has a cycle time.
+    pub fn per_cycle(&self, rate: Rational) -> Option<Rational> {
+        self.cycle_seconds
+            .map(|cycle_seconds| rate * cycle_seconds / Rational::from_integer(60))
+    }
+
+    /// Whether the ingredients, products, building and cycle time are the same, ignoring the name.
+    pub fn same_rates(&self, other: &Recipe) -> bool {
+        self.ingredients == other.ingredients
+            && self.products == other.products
+            && self.building == other.building
+            && self.cycle_seconds == other.cycle_seconds
+    }
+
+    /// How much of a resource the recipe makes per minute, minus how much it uses.
+    pub fn net_rate(&self, resource_id: ResourceId) -> Rational {
+        let total = |side: &[(ResourceId, Rational)]| {
+            side.iter()
+                .filter(|(other_id, _)| *other_id == resource_id)
+                .map(|(_, rate)| *rate)
+                .sum::<Rational>()
+        };
+
+        total(&self.products) - total(&self.ingredients)
+    }
+
+    /// The net rate of each resource the recipe touches, once per resource.
+    pub fn iter_net_rates(&self) -> impl Iterator<Item = (ResourceId, Rational)> + '_ {
+        let mut resources = Vec::new();
+
+        for &(resource_id, _) in self.ingredients.iter().chain(self.products.iter()) {
+            if !resources.contains(&resource_id) {
+                resources.push(resource_id);
+            }
+        }
+
+        resources
+            .into_iter()
+            .map(|resource_id| (resource_id, self.net_rate(resource_id)))
     }
 }
 
+/// Quotes a name for use as a Graphviz DOT id or label.
+pub fn dot_quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
 pub struct InvalidRecipeError;
 
 impl std::fmt::Debug for InvalidRecipeError {
@@ -90,3 +412,58 @@ impl std::fmt::Debug for InvalidRecipeError {
         f.write_str("InvalidRecipeError")
     }
 }
+
+fn load_recipe_locks(path: &str) -> HashSet<String> {
+    let json = match std::fs::read_to_string(path) {
+        Ok(json) => json,
+        Err(_) => {
+            info!(
+                "No recipe locks at {:?}, every recipe starts unlocked",
+                path
+            );
+            return HashSet::new();
+        }
+    };
+
+    match serde_json::from_str::<Vec<String>>(&json) {
+        Ok(names) => {
+            info!("Loaded {} recipe locks from {:?}", names.len(), path);
+            names.into_iter().collect()
+        }
+        Err(err) => {
+            error!(
+                "Invalid recipe locks {:?}, every recipe starts unlocked: {}",
+                path, err
+            );
+            HashSet::new()
+        }
+    }
+}
+
+/// Writes the locked recipe names out whenever they change, so research carries over to the next run.
+fn save_recipe_locks(world: Res<FactoryWorld>, mut saved_names: Local<Option<Vec<String>>>) {
+    if !world.is_changed() {
+        return;
+    }
+
+    let mut names: Vec<String> = world.locked_recipes.iter().cloned().collect();
+    names.sort();
+
+    let Some(saved) = saved_names.as_mut() else {
+        // The first run only sees what was just loaded.
+        *saved_names = Some(names);
+        return;
+    };
+
+    if *saved == names {
+        return;
+    }
+
+    let json = serde_json::to_string_pretty(&names).expect("Should serialize names");
+
+    if let Err(err) = std::fs::write(LOCKS_PATH, json) {
+        error!("Couldn't save recipe locks to {:?}: {}", LOCKS_PATH, err);
+    }
+
+    *saved = names;
+}
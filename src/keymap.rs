@@ -0,0 +1,342 @@
+use bevy::{prelude::*, utils::HashMap};
+use serde::Deserialize;
+
+use crate::{MenuName, plan_export::ExportFormat, text_input::FocusedTextInput};
+
+/// Where the keymap is read from, relative to the working directory.
+const KEYMAP_PATH: &str = "keymap.json";
+
+pub fn build(app: &mut App) {
+    app.add_event::<AppAction>();
+    app.insert_resource(load_keymap(KEYMAP_PATH));
+
+    app.add_systems(Update, send_key_actions);
+}
+
+/// Something the user asked for from the keyboard, or anywhere else that isn't its own button.
+#[derive(Event, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AppAction {
+    LoadRecipes,
+    SaveCanvas,
+    OpenCanvas,
+    DeleteSelected,
+    DuplicateSelected,
+    /// Copies the selected boxes to the system clipboard as a blueprint.
+    CopySelected,
+    /// Pastes boxes copied from this or another running instance.
+    Paste,
+    PanUp,
+    PanDown,
+    PanLeft,
+    PanRight,
+    ResetView,
+    /// Switches every box between showing everything and showing just its title.
+    ToggleIconMode,
+    /// Opens or closes a menu of the main menu.
+    ToggleMenu(MenuName),
+    Export(ExportFormat),
+    OpenPalette,
+    /// Solves for the sink boxes on the canvas.
+    Solve,
+}
+
+impl AppAction {
+    /// Held actions are sent every frame the keys are down, rather than once per press.
+    pub fn is_held(self) -> bool {
+        matches!(
+            self,
+            AppAction::PanUp | AppAction::PanDown | AppAction::PanLeft | AppAction::PanRight
+        )
+    }
+}
+
+/// Whether `action` was sent, reading every event so none are left over for the next frame.
+pub fn action_sent(actions: &mut EventReader<AppAction>, action: AppAction) -> bool {
+    actions.read().filter(|sent| **sent == action).count() > 0
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyBinding {
+    pub key: KeyCode,
+    pub ctrl: bool,
+    pub shift: bool,
+    pub alt: bool,
+}
+
+impl KeyBinding {
+    /// Parses bindings like `"Ctrl+Shift+S"`, `"Delete"` or `"F1"`.
+    pub fn parse(binding: &str) -> Option<KeyBinding> {
+        let mut parts: Vec<&str> = binding.split('+').map(str::trim).collect();
+        let key = parse_key(parts.pop()?)?;
+
+        let mut key_binding = KeyBinding {
+            key,
+            ctrl: false,
+            shift: false,
+            alt: false,
+        };
+
+        for modifier in parts {
+            match modifier.to_ascii_lowercase().as_str() {
+                "ctrl" | "control" => key_binding.ctrl = true,
+                "shift" => key_binding.shift = true,
+                "alt" => key_binding.alt = true,
+                _ => return None,
+            }
+        }
+
+        Some(key_binding)
+    }
+}
+
+fn parse_key(name: &str) -> Option<KeyCode> {
+    const LETTERS: [KeyCode; 26] = [
+        KeyCode::KeyA,
+        KeyCode::KeyB,
+        KeyCode::KeyC,
+        KeyCode::KeyD,
+        KeyCode::KeyE,
+        KeyCode::KeyF,
+        KeyCode::KeyG,
+        KeyCode::KeyH,
+        KeyCode::KeyI,
+        KeyCode::KeyJ,
+        KeyCode::KeyK,
+        KeyCode::KeyL,
+        KeyCode::KeyM,
+        KeyCode::KeyN,
+        KeyCode::KeyO,
+        KeyCode::KeyP,
+        KeyCode::KeyQ,
+        KeyCode::KeyR,
+        KeyCode::KeyS,
+        KeyCode::KeyT,
+        KeyCode::KeyU,
+        KeyCode::KeyV,
+        KeyCode::KeyW,
+        KeyCode::KeyX,
+        KeyCode::KeyY,
+        KeyCode::KeyZ,
+    ];
+
+    const DIGITS: [KeyCode; 10] = [
+        KeyCode::Digit0,
+        KeyCode::Digit1,
+        KeyCode::Digit2,
+        KeyCode::Digit3,
+        KeyCode::Digit4,
+        KeyCode::Digit5,
+        KeyCode::Digit6,
+        KeyCode::Digit7,
+        KeyCode::Digit8,
+        KeyCode::Digit9,
+    ];
+
+    const FUNCTION_KEYS: [KeyCode; 12] = [
+        KeyCode::F1,
+        KeyCode::F2,
+        KeyCode::F3,
+        KeyCode::F4,
+        KeyCode::F5,
+        KeyCode::F6,
+        KeyCode::F7,
+        KeyCode::F8,
+        KeyCode::F9,
+        KeyCode::F10,
+        KeyCode::F11,
+        KeyCode::F12,
+    ];
+
+    let name = name.to_ascii_uppercase();
+
+    if let [character] = name.as_bytes() {
+        return match character {
+            b'A'..=b'Z' => Some(LETTERS[(character - b'A') as usize]),
+            b'0'..=b'9' => Some(DIGITS[(character - b'0') as usize]),
+            b'=' => Some(KeyCode::Equal),
+            b'-' => Some(KeyCode::Minus),
+            _ => None,
+        };
+    }
+
+    if let Some(number) = name.strip_prefix('F')
+        && let Ok(number) = number.parse::<usize>()
+    {
+        return FUNCTION_KEYS.get(number.checked_sub(1)?).copied();
+    }
+
+    match name.as_str() {
+        "UP" => Some(KeyCode::ArrowUp),
+        "DOWN" => Some(KeyCode::ArrowDown),
+        "LEFT" => Some(KeyCode::ArrowLeft),
+        "RIGHT" => Some(KeyCode::ArrowRight),
+        "DELETE" => Some(KeyCode::Delete),
+        "BACKSPACE" => Some(KeyCode::Backspace),
+        "ESCAPE" => Some(KeyCode::Escape),
+        "ENTER" => Some(KeyCode::Enter),
+        "TAB" => Some(KeyCode::Tab),
+        "SPACE" => Some(KeyCode::Space),
+        "HOME" => Some(KeyCode::Home),
+        "END" => Some(KeyCode::End),
+        _ => None,
+    }
+}
+
+/// Which keys send which actions.
+#[derive(Resource)]
+pub struct Keymap {
+    pub bindings: Vec<(KeyBinding, AppAction)>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        let mut bindings = vec![
+            ("Ctrl+L", AppAction::LoadRecipes),
+            ("Ctrl+S", AppAction::SaveCanvas),
+            ("Ctrl+O", AppAction::OpenCanvas),
+            ("Delete", AppAction::DeleteSelected),
+            ("Ctrl+D", AppAction::DuplicateSelected),
+            ("Ctrl+C", AppAction::CopySelected),
+            ("Ctrl+V", AppAction::Paste),
+            ("Up", AppAction::PanUp),
+            ("W", AppAction::PanUp),
+            ("Down", AppAction::PanDown),
+            ("S", AppAction::PanDown),
+            ("Left", AppAction::PanLeft),
+            ("A", AppAction::PanLeft),
+            ("Right", AppAction::PanRight),
+            ("D", AppAction::PanRight),
+            ("Home", AppAction::ResetView),
+            ("Ctrl+I", AppAction::ToggleIconMode),
+            ("Ctrl+P", AppAction::OpenPalette),
+            ("Ctrl+Enter", AppAction::Solve),
+        ];
+
+        let menu_keys = [
+            "F1", "F2", "F3", "F4", "F5", "F6", "F7", "F8", "F9", "F10", "F11", "F12",
+        ];
+        bindings.extend(
+            menu_keys
+                .into_iter()
+                .zip(MenuName::ALL)
+                .map(|(key, menu)| (key, AppAction::ToggleMenu(menu))),
+        );
+
+        Keymap {
+            bindings: bindings
+                .into_iter()
+                .map(|(binding, action)| {
+                    let binding =
+                        KeyBinding::parse(binding).expect("Default bindings should parse");
+                    (binding, action)
+                })
+                .collect(),
+        }
+    }
+}
+
+/// The keymap file maps bindings to actions, like `{ "Ctrl+S": "SaveCanvas" }`,
+/// and replaces the default keymap completely.
+#[derive(Deserialize)]
+struct KeymapJson {
+    bindings: HashMap<String, AppAction>,
+}
+
+/// Reads the keymap file, falling back to the defaults if it's missing or invalid.
+fn load_keymap(path: &str) -> Keymap {
+    let json = match std::fs::read_to_string(path) {
+        Ok(json) => json,
+        Err(_) => {
+            info!("No keymap at {:?}, using the default keys", path);
+            return Keymap::default();
+        }
+    };
+
+    let keymap_json: KeymapJson = match serde_json::from_str(&json) {
+        Ok(keymap_json) => keymap_json,
+        Err(err) => {
+            error!("Invalid keymap {:?}, using the default keys: {}", path, err);
+            return Keymap::default();
+        }
+    };
+
+    let bindings = keymap_json
+        .bindings
+        .into_iter()
+        .filter_map(|(binding, action)| match KeyBinding::parse(&binding) {
+            Some(key_binding) => Some((key_binding, action)),
+            None => {
+                warn!("Unknown key binding \"{}\" in keymap", binding);
+                None
+            }
+        })
+        .collect();
+
+    info!("Loaded keymap from {:?}", path);
+
+    Keymap { bindings }
+}
+
+fn send_key_actions(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    keymap: Res<Keymap>,
+    focused: Res<FocusedTextInput>,
+    mut actions: EventWriter<AppAction>,
+) {
+    if focused.0.is_some() {
+        return;
+    }
+
+    let ctrl = keyboard_input.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+    let shift = keyboard_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    let alt = keyboard_input.any_pressed([KeyCode::AltLeft, KeyCode::AltRight]);
+
+    for &(binding, action) in keymap.bindings.iter() {
+        if binding.ctrl != ctrl || binding.shift != shift || binding.alt != alt {
+            continue;
+        }
+
+        let triggered = if action.is_held() {
+            keyboard_input.pressed(binding.key)
+        } else {
+            keyboard_input.just_pressed(binding.key)
+        };
+
+        if triggered {
+            actions.send(action);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_menu_has_a_default_key() {
+        let keymap = Keymap::default();
+
+        for menu in MenuName::ALL {
+            assert!(
+                keymap
+                    .bindings
+                    .iter()
+                    .any(|&(_, action)| action == AppAction::ToggleMenu(menu)),
+                "{:?} has no key",
+                menu
+            );
+        }
+    }
+
+    #[test]
+    fn example_keymap_parses() {
+        let keymap_json: KeymapJson = serde_json::from_str(include_str!("../keymap.json")).unwrap();
+
+        assert!(
+            keymap_json
+                .bindings
+                .keys()
+                .all(|binding| KeyBinding::parse(binding).is_some())
+        );
+    }
+}
@@ -0,0 +1,498 @@
+use std::fmt;
+
+use crate::rational::Rational;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Relation {
+    LessOrEqual,
+    Equal,
+    GreaterOrEqual,
+}
+
+impl Relation {
+    fn flipped(self) -> Relation {
+        match self {
+            Relation::LessOrEqual => Relation::GreaterOrEqual,
+            Relation::Equal => Relation::Equal,
+            Relation::GreaterOrEqual => Relation::LessOrEqual,
+        }
+    }
+}
+
+pub struct Constraint {
+    /// Coefficients by variable index, variables that aren't listed have a coefficient of zero.
+    pub coefficients: Vec<(usize, Rational)>,
+    pub relation: Relation,
+    pub rhs: Rational,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LpError {
+    Infeasible,
+    Unbounded,
+    /// The fractions in the tableau got too big for exact arithmetic.
+    Overflow,
+}
+
+impl fmt::Display for LpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            LpError::Infeasible => "No plan meets all the constraints",
+            LpError::Unbounded => "The objective can be made as small as you like",
+            LpError::Overflow => "The numbers got too big to solve exactly",
+        })
+    }
+}
+
+pub struct LpSolution {
+    pub values: Vec<Rational>,
+    pub objective: Rational,
+    /// How much the objective goes up per unit the right hand side of each constraint goes up.
+    pub duals: Vec<Rational>,
+    /// The lowest and highest each variable's cost can be before a different solution
+    /// would be better, `None` where there's no limit.
+    pub cost_ranges: Vec<(Option<Rational>, Option<Rational>)>,
+}
+
+/// Turns a `checked_` result of the tableau arithmetic into an error.
+fn exact(value: Option<Rational>) -> Result<Rational, LpError> {
+    value.ok_or(LpError::Overflow)
+}
+
+/// Minimises `objective · x` over non-negative variables `x`, subject to linear constraints.
+///
+/// Solved exactly with the two phase simplex method, using Bland's rule so it can't cycle.
+pub struct LinearProgram {
+    pub objective: Vec<Rational>,
+    pub constraints: Vec<Constraint>,
+}
+
+impl LinearProgram {
+    pub fn new(variable_count: usize) -> Self {
+        LinearProgram {
+            objective: vec![Rational::ZERO; variable_count],
+            constraints: Vec::new(),
+        }
+    }
+
+    pub fn variable_count(&self) -> usize {
+        self.objective.len()
+    }
+
+    pub fn add_constraint(
+        &mut self,
+        coefficients: Vec<(usize, Rational)>,
+        relation: Relation,
+        rhs: Rational,
+    ) {
+        self.constraints.push(Constraint {
+            coefficients,
+            relation,
+            rhs,
+        });
+    }
+
+    pub fn solve(&self) -> Result<LpSolution, LpError> {
+        let variable_count = self.variable_count();
+        let row_count = self.constraints.len();
+
+        let slack_count = self
+            .constraints
+            .iter()
+            .filter(|constraint| constraint.relation != Relation::Equal)
+            .count();
+
+        // variables, then a slack for each inequality, then an artificial variable for each row
+        let artificial_start = variable_count + slack_count;
+        let column_count = artificial_start + row_count;
+
+        let mut tableau = Tableau {
+            rows: Vec::with_capacity(row_count),
+            basis: Vec::with_capacity(row_count),
+            column_count,
+        };
+
+        let mut slack_column = variable_count;
+        let mut flipped_rows = vec![false; row_count];
+
+        for (row_index, constraint) in self.constraints.iter().enumerate() {
+            let mut row = vec![Rational::ZERO; column_count + 1];
+
+            for &(variable, coefficient) in constraint.coefficients.iter() {
+                row[variable] = exact(row[variable].checked_add(coefficient))?;
+            }
+
+            row[column_count] = constraint.rhs;
+
+            // the starting basis needs every right hand side to be non-negative
+            let mut relation = constraint.relation;
+
+            if constraint.rhs.is_negative() {
+                for value in row.iter_mut() {
+                    *value = exact(value.checked_neg())?;
+                }
+
+                relation = relation.flipped();
+                flipped_rows[row_index] = true;
+            }
+
+            match relation {
+                Relation::LessOrEqual => row[slack_column] = Rational::ONE,
+                Relation::GreaterOrEqual => row[slack_column] = -Rational::ONE,
+                Relation::Equal => (),
+            }
+
+            if relation != Relation::Equal {
+                slack_column += 1;
+            }
+
+            row[artificial_start + row_index] = Rational::ONE;
+
+            tableau.rows.push(row);
+            tableau.basis.push(artificial_start + row_index);
+        }
+
+        // phase one finds a feasible point by driving the artificial variables to zero
+        let mut costs = vec![Rational::ZERO; column_count];
+
+        for cost in costs[artificial_start..].iter_mut() {
+            *cost = Rational::ONE;
+        }
+
+        tableau.optimise(&costs, |_| true)?;
+
+        if tableau.objective(&costs)?.is_positive() {
+            return Err(LpError::Infeasible);
+        }
+
+        // artificial variables left in the basis are at zero, swap them out where possible
+        for row_index in 0..row_count {
+            if tableau.basis[row_index] < artificial_start {
+                continue;
+            }
+
+            if let Some(column) =
+                (0..artificial_start).find(|&column| !tableau.rows[row_index][column].is_zero())
+            {
+                tableau.pivot(row_index, column)?;
+            }
+        }
+
+        // phase two optimises the real objective, never letting artificial variables back in
+        let mut costs = vec![Rational::ZERO; column_count];
+        costs[..variable_count].copy_from_slice(&self.objective);
+
+        tableau.optimise(&costs, |column| column < artificial_start)?;
+
+        let mut values = vec![Rational::ZERO; variable_count];
+
+        for (row, &column) in tableau.rows.iter().zip(tableau.basis.iter()) {
+            if column < variable_count {
+                values[column] = row[column_count];
+            }
+        }
+
+        let mut objective = Rational::ZERO;
+
+        for (&value, &cost) in values.iter().zip(self.objective.iter()) {
+            objective = exact(objective.checked_add(exact(value.checked_mul(cost))?))?;
+        }
+
+        // each artificial column started out as its row of the identity, so now it holds that
+        // column of the inverse basis and its reduced cost is minus the dual of its row
+        let mut duals = Vec::with_capacity(row_count);
+
+        for (row_index, &flipped) in flipped_rows.iter().enumerate() {
+            let reduced_cost = tableau.reduced_cost(&costs, artificial_start + row_index)?;
+
+            duals.push(if flipped {
+                reduced_cost
+            } else {
+                exact(reduced_cost.checked_neg())?
+            });
+        }
+
+        let reduced_costs = (0..artificial_start)
+            .map(|column| tableau.reduced_cost(&costs, column))
+            .collect::<Result<Vec<Rational>, LpError>>()?;
+
+        let mut cost_ranges = Vec::with_capacity(variable_count);
+
+        for variable in 0..variable_count {
+            let cost = self.objective[variable];
+
+            let Some(row_index) = tableau.basis.iter().position(|&column| column == variable)
+            else {
+                // a variable left out of the solution could only come in if it got cheaper
+                cost_ranges.push((
+                    Some(exact(cost.checked_sub(reduced_costs[variable]))?),
+                    None,
+                ));
+                continue;
+            };
+
+            // a variable in the solution stays there while no other column would improve it
+            let row = &tableau.rows[row_index];
+            let (mut lower, mut upper): (Option<Rational>, Option<Rational>) = (None, None);
+
+            for column in 0..artificial_start {
+                if row[column].is_zero() || tableau.basis.contains(&column) {
+                    continue;
+                }
+
+                let change = exact(reduced_costs[column].checked_div(row[column]))?;
+
+                if row[column].is_positive() {
+                    upper = Some(upper.map_or(change, |upper| upper.min(change)));
+                } else {
+                    lower = Some(lower.map_or(change, |lower| lower.max(change)));
+                }
+            }
+
+            cost_ranges.push((
+                lower
+                    .map(|lower| exact(cost.checked_add(lower)))
+                    .transpose()?,
+                upper
+                    .map(|upper| exact(cost.checked_add(upper)))
+                    .transpose()?,
+            ));
+        }
+
+        Ok(LpSolution {
+            values,
+            objective,
+            duals,
+            cost_ranges,
+        })
+    }
+}
+
+/// A simplex tableau in canonical form, each row has a right hand side as its last value.
+struct Tableau {
+    rows: Vec<Vec<Rational>>,
+    /// The basic column of each row.
+    basis: Vec<usize>,
+    column_count: usize,
+}
+
+impl Tableau {
+    fn objective(&self, costs: &[Rational]) -> Result<Rational, LpError> {
+        let mut objective = Rational::ZERO;
+
+        for (row, &column) in self.rows.iter().zip(self.basis.iter()) {
+            objective = exact(
+                objective.checked_add(exact(costs[column].checked_mul(row[self.column_count]))?),
+            )?;
+        }
+
+        Ok(objective)
+    }
+
+    fn reduced_cost(&self, costs: &[Rational], column: usize) -> Result<Rational, LpError> {
+        let mut reduced_cost = costs[column];
+
+        for (row, &basic_column) in self.rows.iter().zip(self.basis.iter()) {
+            if row[column].is_zero() || costs[basic_column].is_zero() {
+                continue;
+            }
+
+            reduced_cost = exact(
+                reduced_cost.checked_sub(exact(costs[basic_column].checked_mul(row[column]))?),
+            )?;
+        }
+
+        Ok(reduced_cost)
+    }
+
+    fn pivot(&mut self, pivot_row: usize, pivot_column: usize) -> Result<(), LpError> {
+        let pivot = self.rows[pivot_row][pivot_column];
+
+        for value in self.rows[pivot_row].iter_mut() {
+            if !value.is_zero() {
+                *value = exact(value.checked_div(pivot))?;
+            }
+        }
+
+        let pivot_values = self.rows[pivot_row].clone();
+
+        for (row_index, row) in self.rows.iter_mut().enumerate() {
+            let factor = row[pivot_column];
+
+            if row_index == pivot_row || factor.is_zero() {
+                continue;
+            }
+
+            for (value, &pivot_value) in row.iter_mut().zip(pivot_values.iter()) {
+                if !pivot_value.is_zero() {
+                    *value = exact(value.checked_sub(exact(factor.checked_mul(pivot_value))?))?;
+                }
+            }
+        }
+
+        self.basis[pivot_row] = pivot_column;
+
+        Ok(())
+    }
+
+    /// Pivots until no allowed column can lower the cost.
+    fn optimise(
+        &mut self,
+        costs: &[Rational],
+        allowed: impl Fn(usize) -> bool,
+    ) -> Result<(), LpError> {
+        loop {
+            // Bland's rule, the lowest column that improves enters
+            let mut entering = None;
+
+            for column in 0..self.column_count {
+                if allowed(column)
+                    && !self.basis.contains(&column)
+                    && self.reduced_cost(costs, column)?.is_negative()
+                {
+                    entering = Some(column);
+                    break;
+                }
+            }
+
+            let Some(entering) = entering else {
+                return Ok(());
+            };
+
+            // and ties in the ratio test go to the lowest basic column
+            let mut leaving = None;
+
+            for (row_index, row) in self.rows.iter().enumerate() {
+                if !row[entering].is_positive() {
+                    continue;
+                }
+
+                let ratio = exact(row[self.column_count].checked_div(row[entering]))?;
+                let candidate = (ratio, self.basis[row_index], row_index);
+
+                if leaving.is_none_or(|leaving| candidate < leaving) {
+                    leaving = Some(candidate);
+                }
+            }
+
+            let (_, _, leaving) = leaving.ok_or(LpError::Unbounded)?;
+
+            self.pivot(leaving, entering)?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn int(value: i128) -> Rational {
+        Rational::from_integer(value)
+    }
+
+    /// Maximise 3x + 5y subject to x <= 4, 2y <= 12 and 3x + 2y <= 18,
+    /// written as minimising -3x - 5y. The optimum is x = 2, y = 6.
+    fn textbook_program() -> LinearProgram {
+        let mut program = LinearProgram::new(2);
+        program.objective = vec![int(-3), int(-5)];
+        program.add_constraint(vec![(0, int(1))], Relation::LessOrEqual, int(4));
+        program.add_constraint(vec![(1, int(2))], Relation::LessOrEqual, int(12));
+        program.add_constraint(
+            vec![(0, int(3)), (1, int(2))],
+            Relation::LessOrEqual,
+            int(18),
+        );
+        program
+    }
+
+    #[test]
+    fn finds_the_optimum() {
+        let solution = textbook_program().solve().unwrap();
+
+        assert_eq!(solution.values, vec![int(2), int(6)]);
+        assert_eq!(solution.objective, int(-36));
+    }
+
+    #[test]
+    fn duals_are_the_objective_change_per_unit_of_each_limit() {
+        let solution = textbook_program().solve().unwrap();
+
+        assert_eq!(solution.duals, vec![int(0), Rational::new(-3, 2), int(-1)]);
+    }
+
+    #[test]
+    fn cost_ranges_keep_the_same_optimum() {
+        let solution = textbook_program().solve().unwrap();
+
+        assert_eq!(
+            solution.cost_ranges,
+            vec![
+                (Some(Rational::new(-15, 2)), Some(int(0))),
+                (None, Some(int(-2))),
+            ]
+        );
+    }
+
+    #[test]
+    fn handles_equalities_and_lower_bounds() {
+        // minimise x + y with x + y >= 2 and x = y
+        let mut program = LinearProgram::new(2);
+        program.objective = vec![int(1), int(1)];
+        program.add_constraint(
+            vec![(0, int(1)), (1, int(1))],
+            Relation::GreaterOrEqual,
+            int(2),
+        );
+        program.add_constraint(vec![(0, int(1)), (1, int(-1))], Relation::Equal, int(0));
+
+        let solution = program.solve().unwrap();
+
+        assert_eq!(solution.values, vec![int(1), int(1)]);
+        assert_eq!(solution.objective, int(2));
+        assert_eq!(solution.duals, vec![int(1), int(0)]);
+    }
+
+    #[test]
+    fn duals_of_negative_limits_keep_their_sign() {
+        // minimise x with -x <= -3, so x >= 3
+        let mut program = LinearProgram::new(1);
+        program.objective = vec![int(1)];
+        program.add_constraint(vec![(0, int(-1))], Relation::LessOrEqual, int(-3));
+
+        let solution = program.solve().unwrap();
+
+        assert_eq!(solution.values, vec![int(3)]);
+        assert_eq!(solution.duals, vec![int(-1)]);
+    }
+
+    #[test]
+    fn works_with_fractions() {
+        // minimise x with 3x >= 1
+        let mut program = LinearProgram::new(1);
+        program.objective = vec![int(1)];
+        program.add_constraint(vec![(0, int(3))], Relation::GreaterOrEqual, int(1));
+
+        let solution = program.solve().unwrap();
+
+        assert_eq!(solution.values, vec![Rational::new(1, 3)]);
+        assert_eq!(solution.duals, vec![Rational::new(1, 3)]);
+    }
+
+    #[test]
+    fn reports_infeasible_programs() {
+        let mut program = LinearProgram::new(1);
+        program.add_constraint(vec![(0, int(1))], Relation::LessOrEqual, int(1));
+        program.add_constraint(vec![(0, int(1))], Relation::GreaterOrEqual, int(2));
+
+        assert_eq!(program.solve().err(), Some(LpError::Infeasible));
+    }
+
+    #[test]
+    fn reports_unbounded_programs() {
+        let mut program = LinearProgram::new(1);
+        program.objective = vec![int(-1)];
+        program.add_constraint(vec![(0, int(1))], Relation::GreaterOrEqual, int(1));
+
+        assert_eq!(program.solve().err(), Some(LpError::Unbounded));
+    }
+}
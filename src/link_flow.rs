@@ -0,0 +1,609 @@
+use bevy::{color::palettes::css::*, prelude::*, utils::HashMap, window::PrimaryWindow};
+
+use crate::{
+    boundary_boxes::BoundaryBox,
+    box_links::{BoxLink, node_rect},
+    chain_solve::LinkShare,
+    factory_world::*,
+    logistics::{LogisticsBox, LogisticsKind, SplitRatio},
+    rational::Rational,
+    recipe_ui::RecipeBox,
+};
+
+/// How close the cursor has to be to a link to show its flow, in pixels.
+const HOVER_DISTANCE: f32 = 6.;
+
+pub fn build(app: &mut App) {
+    app.add_systems(Startup, create_flow_tooltip);
+    app.add_systems(Update, (update_link_flows, show_flow_tooltip).chain());
+}
+
+/// How much of its resource a link is given by the box at its start,
+/// against how much the box at its end wants from it, per minute.
+#[derive(Component, Clone, Copy, Debug, PartialEq)]
+pub struct LinkFlow {
+    pub supply: Rational,
+    pub demand: Rational,
+}
+
+impl LinkFlow {
+    /// Positive when more is supplied than needed.
+    pub fn surplus(self) -> Rational {
+        self.supply - self.demand
+    }
+
+    pub fn color(self) -> Color {
+        match self.supply.cmp(&self.demand) {
+            std::cmp::Ordering::Equal => LIMEGREEN.into(),
+            std::cmp::Ordering::Greater => GOLD.into(),
+            std::cmp::Ordering::Less => CRIMSON.into(),
+        }
+    }
+}
+
+/// How much of a resource a box makes, or uses if `output` is false, per minute.
+pub fn box_port_rate(
+    box_entity: Entity,
+    resource_id: ResourceId,
+    output: bool,
+    recipe_box_q: &Query<&RecipeBox>,
+    boundary_box_q: &Query<&BoundaryBox>,
+    world: &FactoryWorld,
+) -> Rational {
+    if let Ok(boundary_box) = boundary_box_q.get(box_entity) {
+        return if boundary_box.resource_id == resource_id && boundary_box.is_source() == output {
+            boundary_box.actual_rate()
+        } else {
+            Rational::ZERO
+        };
+    }
+
+    let Ok(recipe_box) = recipe_box_q.get(box_entity) else {
+        return Rational::ZERO;
+    };
+
+    let Ok(recipe) = world.get_recipe(recipe_box.recipe_id) else {
+        return Rational::ZERO;
+    };
+
+    let side = if output {
+        &recipe.products
+    } else {
+        &recipe.ingredients
+    };
+
+    side.iter()
+        .filter(|(other_id, _)| *other_id == resource_id)
+        .map(|(_, rate)| *rate * recipe_box.multiplier)
+        .sum()
+}
+
+/// The links of the plane and what the boxes at their ends make and use,
+/// for passing rates through logistics boxes.
+struct FlowGraph {
+    links: Vec<(Entity, BoxLink)>,
+    /// What the ports of every box that isn't a logistics box make or use, per minute.
+    port_rates: HashMap<(Entity, ResourceId, bool), Rational>,
+    logistics_kinds: HashMap<Entity, LogisticsKind>,
+    split_ratios: HashMap<Entity, Rational>,
+    /// Links without one have a share of 1.
+    link_shares: HashMap<Entity, Rational>,
+}
+
+/// Ports already worked out, or `None` for ones being worked out,
+/// so links going round in a loop add nothing.
+type PortCache = HashMap<(Entity, ResourceId), Option<Rational>>;
+
+impl FlowGraph {
+    fn links_from(
+        &self,
+        box_entity: Entity,
+        resource_id: ResourceId,
+    ) -> impl Iterator<Item = &(Entity, BoxLink)> {
+        self.links
+            .iter()
+            .filter(move |(_, link)| link.from_box == box_entity && link.resource_id == resource_id)
+    }
+
+    fn links_to(
+        &self,
+        box_entity: Entity,
+        resource_id: ResourceId,
+    ) -> impl Iterator<Item = &(Entity, BoxLink)> {
+        self.links
+            .iter()
+            .filter(move |(_, link)| link.to_box == box_entity && link.resource_id == resource_id)
+    }
+
+    /// How much of the supply of its start a link gets, against the other links from the same port.
+    ///
+    /// Smart splitters go by their ratios, and everything else by what each link wants.
+    fn link_weight(
+        &self,
+        link_entity: Entity,
+        link: &BoxLink,
+        demands: &mut PortCache,
+    ) -> Rational {
+        match self.logistics_kinds.get(&link.from_box) {
+            Some(LogisticsKind::SmartSplitter) => self
+                .split_ratios
+                .get(&link_entity)
+                .copied()
+                .unwrap_or(Rational::ONE),
+            _ => self.link_wanted(link_entity, link, demands),
+        }
+    }
+
+    fn link_supply(
+        &self,
+        link_entity: Entity,
+        link: &BoxLink,
+        supplies: &mut PortCache,
+        demands: &mut PortCache,
+    ) -> Rational {
+        let siblings: Vec<_> = self.links_from(link.from_box, link.resource_id).collect();
+
+        let total_weight: Rational = siblings
+            .iter()
+            .map(|(other_entity, other)| self.link_weight(*other_entity, other, demands))
+            .sum();
+
+        let port_supply = self.port_supply(link.from_box, link.resource_id, supplies, demands);
+
+        if total_weight.is_positive() {
+            port_supply * self.link_weight(link_entity, link, demands) / total_weight
+        } else if self.logistics_kinds.get(&link.from_box) == Some(&LogisticsKind::SmartSplitter) {
+            Rational::ZERO
+        } else {
+            // nothing is wanted, so share it evenly
+            port_supply / Rational::from_integer(siblings.len().max(1) as i128)
+        }
+    }
+
+    /// What an output port makes, which for logistics boxes is everything coming into them.
+    fn port_supply(
+        &self,
+        box_entity: Entity,
+        resource_id: ResourceId,
+        supplies: &mut PortCache,
+        demands: &mut PortCache,
+    ) -> Rational {
+        if !self.logistics_kinds.contains_key(&box_entity) {
+            return self
+                .port_rates
+                .get(&(box_entity, resource_id, true))
+                .copied()
+                .unwrap_or(Rational::ZERO);
+        }
+
+        if let Some(supply) = supplies.get(&(box_entity, resource_id)) {
+            return supply.unwrap_or(Rational::ZERO);
+        }
+
+        supplies.insert((box_entity, resource_id), None);
+
+        let supply = self
+            .links_to(box_entity, resource_id)
+            .map(|(link_entity, link)| self.link_supply(*link_entity, link, supplies, demands))
+            .sum();
+
+        supplies.insert((box_entity, resource_id), Some(supply));
+
+        supply
+    }
+
+    fn link_share(&self, link_entity: Entity) -> Rational {
+        self.link_shares
+            .get(&link_entity)
+            .copied()
+            .unwrap_or(Rational::ONE)
+    }
+
+    /// How much the end of a link wants from it, sharing its demand between its links
+    /// by their [`LinkShare`].
+    ///
+    /// This doesn't look at supply, so supply can be shared out by it.
+    fn link_wanted(
+        &self,
+        link_entity: Entity,
+        link: &BoxLink,
+        demands: &mut PortCache,
+    ) -> Rational {
+        let total_share: Rational = self
+            .links_to(link.to_box, link.resource_id)
+            .map(|(other_entity, _)| self.link_share(*other_entity))
+            .sum();
+
+        if !total_share.is_positive() {
+            return Rational::ZERO;
+        }
+
+        self.port_demand(link.to_box, link.resource_id, demands) * self.link_share(link_entity)
+            / total_share
+    }
+
+    /// How much of the demand of its end a link is asked for,
+    /// sharing it between the links into the same port by what each supplies.
+    fn link_demand(
+        &self,
+        link_entity: Entity,
+        link: &BoxLink,
+        supplies: &mut PortCache,
+        demands: &mut PortCache,
+    ) -> Rational {
+        let total_supply: Rational = self
+            .links_to(link.to_box, link.resource_id)
+            .map(|(other_entity, other)| self.link_supply(*other_entity, other, supplies, demands))
+            .sum();
+
+        if !total_supply.is_positive() {
+            return self.link_wanted(link_entity, link, demands);
+        }
+
+        self.port_demand(link.to_box, link.resource_id, demands)
+            * self.link_supply(link_entity, link, supplies, demands)
+            / total_supply
+    }
+
+    /// What an input port uses, which for logistics boxes is everything wanted from them.
+    fn port_demand(
+        &self,
+        box_entity: Entity,
+        resource_id: ResourceId,
+        demands: &mut PortCache,
+    ) -> Rational {
+        if !self.logistics_kinds.contains_key(&box_entity) {
+            return self
+                .port_rates
+                .get(&(box_entity, resource_id, false))
+                .copied()
+                .unwrap_or(Rational::ZERO);
+        }
+
+        if let Some(demand) = demands.get(&(box_entity, resource_id)) {
+            return demand.unwrap_or(Rational::ZERO);
+        }
+
+        demands.insert((box_entity, resource_id), None);
+
+        let demand = self
+            .links_from(box_entity, resource_id)
+            .map(|(link_entity, link)| self.link_wanted(*link_entity, link, demands))
+            .sum();
+
+        demands.insert((box_entity, resource_id), Some(demand));
+
+        demand
+    }
+}
+
+/// Works out the flow on every link and colours it by whether it's balanced.
+///
+/// An input port wants from each of its links by their share. An output port with several links
+/// shares what it makes by what each link wants, except for smart splitters which share it by the
+/// ratios of their links. The demand shown on a link is then shared by what each link supplies,
+/// so a balanced fan out or fan in shows as balanced.
+/// Logistics boxes pass on everything they're given.
+#[allow(clippy::type_complexity)]
+fn update_link_flows(
+    mut commands: Commands,
+    mut link_q: Query<(
+        Entity,
+        &BoxLink,
+        Option<&SplitRatio>,
+        Option<&LinkShare>,
+        Option<&mut LinkFlow>,
+        &mut BackgroundColor,
+    )>,
+    recipe_box_q: Query<&RecipeBox>,
+    boundary_box_q: Query<&BoundaryBox>,
+    mut logistics_box_q: Query<(Entity, &mut LogisticsBox)>,
+    world: Res<FactoryWorld>,
+) {
+    let mut graph = FlowGraph {
+        links: Vec::new(),
+        port_rates: HashMap::default(),
+        logistics_kinds: logistics_box_q
+            .iter()
+            .map(|(box_entity, logistics_box)| (box_entity, logistics_box.kind))
+            .collect(),
+        split_ratios: HashMap::default(),
+        link_shares: HashMap::default(),
+    };
+
+    for (link_entity, link, split_ratio, link_share, ..) in link_q.iter() {
+        graph.links.push((link_entity, *link));
+
+        if let Some(split_ratio) = split_ratio {
+            graph.split_ratios.insert(link_entity, split_ratio.0);
+        }
+
+        if let Some(link_share) = link_share {
+            graph.link_shares.insert(link_entity, link_share.0);
+        }
+
+        for (box_entity, output) in [(link.from_box, true), (link.to_box, false)] {
+            graph
+                .port_rates
+                .entry((box_entity, link.resource_id, output))
+                .or_insert_with(|| {
+                    box_port_rate(
+                        box_entity,
+                        link.resource_id,
+                        output,
+                        &recipe_box_q,
+                        &boundary_box_q,
+                        world.as_ref(),
+                    )
+                });
+        }
+    }
+
+    let mut supplies = HashMap::default();
+    let mut demands = HashMap::default();
+
+    for (link_entity, link, _, _, flow, mut background) in link_q.iter_mut() {
+        let new_flow = LinkFlow {
+            supply: graph.link_supply(link_entity, link, &mut supplies, &mut demands),
+            demand: graph.link_demand(link_entity, link, &mut supplies, &mut demands),
+        };
+
+        match flow {
+            Some(mut flow) if *flow != new_flow => *flow = new_flow,
+            Some(_) => (),
+            None => {
+                commands.entity(link_entity).insert(new_flow);
+            }
+        }
+
+        let color = new_flow.color();
+
+        if background.0 != color {
+            background.0 = color;
+        }
+    }
+
+    for (box_entity, mut logistics_box) in logistics_box_q.iter_mut() {
+        let throughput = graph.port_supply(
+            box_entity,
+            logistics_box.resource_id,
+            &mut supplies,
+            &mut demands,
+        );
+
+        if logistics_box.throughput != throughput {
+            logistics_box.throughput = throughput;
+        }
+    }
+}
+
+#[derive(Resource)]
+struct FlowTooltip {
+    tooltip_entity: Entity,
+}
+
+fn create_flow_tooltip(mut commands: Commands) {
+    let tooltip_entity = commands
+        .spawn((
+            Text::default(),
+            Node {
+                display: Display::None,
+                position_type: PositionType::Absolute,
+                padding: UiRect::all(Val::Px(4.)),
+                ..default()
+            },
+            BackgroundColor(Color::BLACK),
+            GlobalZIndex(3),
+        ))
+        .id();
+
+    commands.insert_resource(FlowTooltip { tooltip_entity });
+}
+
+/// Shows the supply and demand of the link under the cursor.
+fn show_flow_tooltip(
+    window_q: Query<&Window, With<PrimaryWindow>>,
+    link_q: Query<(&BoxLink, &LinkFlow, &Node, &ComputedNode, &GlobalTransform)>,
+    mut tooltip_q: Query<(&mut Node, &mut Text), Without<BoxLink>>,
+    flow_tooltip: Res<FlowTooltip>,
+    world: Res<FactoryWorld>,
+) {
+    let Ok((mut tooltip_node, mut tooltip_text)) = tooltip_q.get_mut(flow_tooltip.tooltip_entity)
+    else {
+        return;
+    };
+
+    let cursor_position = window_q.get_single().ok().and_then(Window::cursor_position);
+
+    let hovered_link = cursor_position.and_then(|cursor_position| {
+        link_q
+            .iter()
+            .filter(|(_, _, node, computed_node, _)| {
+                node.display != Display::None && !computed_node.is_empty()
+            })
+            .map(|(link, flow, _, computed_node, transform)| {
+                let center = node_rect(computed_node, transform).center();
+                let direction = (transform.rotation() * Vec3::X).truncate();
+                let half_length =
+                    computed_node.size().x * computed_node.inverse_scale_factor() * 0.5;
+
+                // distance from the cursor to the line segment
+                let along = (cursor_position - center)
+                    .dot(direction)
+                    .clamp(-half_length, half_length);
+                let distance = cursor_position.distance(center + direction * along);
+
+                (distance, link, flow)
+            })
+            .filter(|(distance, ..)| *distance <= HOVER_DISTANCE)
+            .min_by(|(a, ..), (b, ..)| a.total_cmp(b))
+    });
+
+    let Some((_, link, flow)) = hovered_link else {
+        if tooltip_node.display != Display::None {
+            tooltip_node.display = Display::None;
+        }
+
+        return;
+    };
+
+    let cursor_position = cursor_position.expect("Hovering needs a cursor");
+    let resource_name = world
+        .get_resource_name(link.resource_id)
+        .expect("Resource name should exist");
+
+    let surplus = flow.surplus();
+    let sign = if surplus.is_negative() { "" } else { "+" };
+
+    tooltip_text.0 = format!(
+        "{}: {} supplied, {} needed ({}{})",
+        resource_name, flow.supply, flow.demand, sign, surplus
+    );
+
+    tooltip_node.display = Display::Flex;
+    tooltip_node.left = Val::Px(cursor_position.x + 12.);
+    tooltip_node.top = Val::Px(cursor_position.y + 12.);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn box_entity(index: u32) -> Entity {
+        Entity::from_raw(index)
+    }
+
+    fn link(index: u32, from: u32, to: u32, resource_id: ResourceId) -> (Entity, BoxLink) {
+        (
+            Entity::from_raw(100 + index),
+            BoxLink {
+                from_box: box_entity(from),
+                to_box: box_entity(to),
+                resource_id,
+            },
+        )
+    }
+
+    fn graph(
+        links: Vec<(Entity, BoxLink)>,
+        rates: &[(u32, ResourceId, bool, i128)],
+        logistics_kinds: &[(u32, LogisticsKind)],
+    ) -> FlowGraph {
+        FlowGraph {
+            links,
+            port_rates: rates
+                .iter()
+                .map(|&(index, resource_id, output, rate)| {
+                    (
+                        (box_entity(index), resource_id, output),
+                        Rational::from_integer(rate),
+                    )
+                })
+                .collect(),
+            logistics_kinds: logistics_kinds
+                .iter()
+                .map(|&(index, kind)| (box_entity(index), kind))
+                .collect(),
+            split_ratios: HashMap::default(),
+            link_shares: HashMap::default(),
+        }
+    }
+
+    fn flows(graph: &FlowGraph) -> Vec<(Rational, Rational)> {
+        let mut supplies = HashMap::default();
+        let mut demands = HashMap::default();
+
+        graph
+            .links
+            .iter()
+            .map(|(link_entity, link)| {
+                let supply = graph.link_supply(*link_entity, link, &mut supplies, &mut demands);
+                let demand = graph.link_demand(*link_entity, link, &mut supplies, &mut demands);
+                (supply, demand)
+            })
+            .collect()
+    }
+
+    fn rates(pairs: &[(i128, i128)]) -> Vec<(Rational, Rational)> {
+        pairs
+            .iter()
+            .map(|&(supply, demand)| {
+                (
+                    Rational::from_integer(supply),
+                    Rational::from_integer(demand),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn balanced_fan_out_is_balanced() {
+        let ore = FactoryWorld::default().get_resource_id("Ore");
+        let graph = graph(
+            vec![link(0, 0, 1, ore), link(1, 0, 2, ore)],
+            &[(0, ore, true, 60), (1, ore, false, 40), (2, ore, false, 20)],
+            &[],
+        );
+
+        assert_eq!(flows(&graph), rates(&[(40, 40), (20, 20)]));
+    }
+
+    #[test]
+    fn balanced_fan_in_is_balanced() {
+        let ore = FactoryWorld::default().get_resource_id("Ore");
+        let graph = graph(
+            vec![link(0, 0, 2, ore), link(1, 1, 2, ore)],
+            &[(0, ore, true, 40), (1, ore, true, 20), (2, ore, false, 60)],
+            &[],
+        );
+
+        assert_eq!(flows(&graph), rates(&[(40, 40), (20, 20)]));
+    }
+
+    #[test]
+    fn splitter_shares_by_what_each_output_wants() {
+        let ore = FactoryWorld::default().get_resource_id("Ore");
+        let graph = graph(
+            vec![link(0, 0, 1, ore), link(1, 1, 2, ore), link(2, 1, 3, ore)],
+            &[(0, ore, true, 90), (2, ore, false, 40), (3, ore, false, 20)],
+            &[(1, LogisticsKind::Splitter)],
+        );
+
+        assert_eq!(flows(&graph), rates(&[(90, 60), (60, 40), (30, 20)]));
+    }
+
+    #[test]
+    fn link_shares_split_what_is_wanted() {
+        let ore = FactoryWorld::default().get_resource_id("Ore");
+        let mut graph = graph(
+            vec![link(0, 0, 2, ore), link(1, 1, 2, ore), link(2, 1, 3, ore)],
+            &[
+                (0, ore, true, 30),
+                (1, ore, true, 30),
+                (2, ore, false, 40),
+                (3, ore, false, 20),
+            ],
+            &[],
+        );
+        graph
+            .link_shares
+            .insert(Entity::from_raw(100), Rational::from_integer(30));
+        graph
+            .link_shares
+            .insert(Entity::from_raw(101), Rational::from_integer(10));
+
+        assert_eq!(flows(&graph), rates(&[(30, 30), (10, 10), (20, 20)]));
+    }
+
+    #[test]
+    fn unwanted_supply_is_shared_evenly() {
+        let ore = FactoryWorld::default().get_resource_id("Ore");
+        let graph = graph(
+            vec![link(0, 0, 1, ore), link(1, 0, 2, ore)],
+            &[(0, ore, true, 60)],
+            &[],
+        );
+
+        assert_eq!(flows(&graph), rates(&[(30, 0), (30, 0)]));
+    }
+}
@@ -0,0 +1,385 @@
+use bevy::{color::palettes::css::*, prelude::*};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ControlPanelUi, CreateBaseUi, MainMenuItem, MainMenuUI, MenuName,
+    box_links::BoxLink,
+    drag_plane::{DragBox, DragBoxPlane, PlaneScroll, SelectedBox},
+    factory_world::*,
+    rational::Rational,
+    recipe_ui::{RecipeBox, spawn_box_contents},
+    text_input::{FocusedTextInput, TextInput, TextInputSubmitted},
+};
+
+pub fn build(app: &mut App) {
+    app.insert_resource(RatioTarget(None));
+
+    app.add_systems(Startup, create_logistics_ui.after(CreateBaseUi));
+    app.add_systems(
+        Update,
+        (
+            add_logistics_boxes,
+            track_ratio_target,
+            submit_split_ratios,
+            refresh_logistics_boxes,
+        )
+            .chain(),
+    );
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogisticsKind {
+    /// Shares what comes in between its outputs by how much each wants.
+    Splitter,
+    /// Brings several inputs together.
+    Merger,
+    /// Shares what comes in between its outputs by their [`SplitRatio`].
+    SmartSplitter,
+}
+
+impl LogisticsKind {
+    pub const ALL: [LogisticsKind; 3] = [
+        LogisticsKind::Splitter,
+        LogisticsKind::Merger,
+        LogisticsKind::SmartSplitter,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            LogisticsKind::Splitter => "Splitter",
+            LogisticsKind::Merger => "Merger",
+            LogisticsKind::SmartSplitter => "Smart Splitter",
+        }
+    }
+}
+
+/// A drag box that passes a single resource from the links going into it to the links leaving it.
+#[derive(Component, Clone, Copy)]
+pub struct LogisticsBox {
+    pub kind: LogisticsKind,
+    pub resource_id: ResourceId,
+    /// Per minute passing through, kept up to date by the link flows.
+    pub throughput: Rational,
+}
+
+/// How much of what a smart splitter gets is sent down a link leaving it,
+/// relative to its other outputs. Links without one have a ratio of 1.
+#[derive(Component, Clone, Copy)]
+pub struct SplitRatio(pub Rational);
+
+#[derive(Resource)]
+struct LogisticsUi {
+    resource_input_entity: Entity,
+    ratio_list_entity: Entity,
+    status_text_entity: Entity,
+}
+
+/// The selected smart splitter, whose output ratios are listed in the menu.
+#[derive(Resource)]
+struct RatioTarget(Option<Entity>);
+
+#[derive(Component)]
+#[require(Button)]
+struct AddLogisticsButton {
+    kind: LogisticsKind,
+}
+
+#[derive(Component)]
+struct SplitRatioInput {
+    link_entity: Entity,
+}
+
+fn create_logistics_ui(
+    mut commands: Commands,
+    main_menu: Res<MainMenuUI>,
+    control_panel: Res<ControlPanelUi>,
+) {
+    let sub_menu_entity = commands
+        .spawn((Node {
+            display: Display::None,
+            flex_direction: FlexDirection::Column,
+            ..default()
+        },))
+        .set_parent(control_panel.control_panel_entity)
+        .id();
+
+    commands
+        .spawn((
+            MainMenuItem {
+                sub_menu_entity,
+                menu: MenuName::Logistics,
+            },
+            Text::new("Logistics"),
+            Node {
+                width: Val::Percent(100.0),
+                ..default()
+            },
+        ))
+        .set_parent(main_menu.main_menu_entity);
+
+    let resource_input_entity = commands
+        .spawn(TextInput::new("Resource name"))
+        .set_parent(sub_menu_entity)
+        .id();
+
+    for kind in LogisticsKind::ALL {
+        commands
+            .spawn((
+                AddLogisticsButton { kind },
+                Text::new(format!("Add {}", kind.name())),
+            ))
+            .set_parent(sub_menu_entity);
+    }
+
+    let status_text_entity = commands
+        .spawn(Text::default())
+        .set_parent(sub_menu_entity)
+        .id();
+
+    commands
+        .spawn(Text::new("Ratios of the selected smart splitter:"))
+        .set_parent(sub_menu_entity);
+
+    let ratio_list_entity = commands
+        .spawn(Node {
+            flex_direction: FlexDirection::Column,
+            ..default()
+        })
+        .set_parent(sub_menu_entity)
+        .id();
+
+    commands.insert_resource(LogisticsUi {
+        resource_input_entity,
+        ratio_list_entity,
+        status_text_entity,
+    });
+}
+
+/// Spawns a splitter or merger for a resource on the plane.
+pub fn spawn_logistics_box(
+    commands: &mut Commands,
+    plane_entity: Entity,
+    position: Vec2,
+    kind: LogisticsKind,
+    resource_id: ResourceId,
+    world: &FactoryWorld,
+) -> Entity {
+    let logistics_box = LogisticsBox {
+        kind,
+        resource_id,
+        throughput: Rational::ZERO,
+    };
+
+    let box_entity = commands
+        .spawn((
+            DragBox { position },
+            logistics_box,
+            Node {
+                position_type: PositionType::Absolute,
+                ..default()
+            },
+        ))
+        .set_parent(plane_entity)
+        .id();
+
+    spawn_logistics_box_contents(commands, box_entity, &logistics_box, world);
+
+    box_entity
+}
+
+fn spawn_logistics_box_contents(
+    commands: &mut Commands,
+    box_entity: Entity,
+    logistics_box: &LogisticsBox,
+    world: &FactoryWorld,
+) {
+    let rates = [(logistics_box.resource_id, logistics_box.throughput)];
+
+    spawn_box_contents(
+        commands,
+        box_entity,
+        logistics_box.kind.name(),
+        DARK_SLATE_GRAY.into(),
+        &rates,
+        &rates,
+        true,
+        world,
+        |_| (),
+    );
+}
+
+#[allow(clippy::too_many_arguments)]
+fn add_logistics_boxes(
+    mut commands: Commands,
+    button_q: Query<(&Interaction, &AddLogisticsButton), Changed<Interaction>>,
+    logistics_ui: Res<LogisticsUi>,
+    input_q: Query<&TextInput>,
+    mut text_q: Query<&mut Text>,
+    plane_q: Query<Entity, With<DragBoxPlane>>,
+    plane_scroll: Res<PlaneScroll>,
+    world: Res<FactoryWorld>,
+) {
+    for (interaction, button) in button_q.iter() {
+        let Interaction::Pressed = interaction else {
+            continue;
+        };
+
+        let resource_input = input_q
+            .get(logistics_ui.resource_input_entity)
+            .expect("Should be a text input");
+
+        let mut status_text = text_q
+            .get_mut(logistics_ui.status_text_entity)
+            .expect("Should be a text");
+
+        let resource_name = resource_input.value.trim();
+
+        let Some(resource_id) = world.find_resource_id(resource_name) else {
+            status_text.0 = format!("No resource called \"{}\"", resource_name);
+            continue;
+        };
+
+        status_text.0.clear();
+
+        let root_plane_entity = plane_q.get_single().expect("Should be one root plane");
+
+        spawn_logistics_box(
+            &mut commands,
+            root_plane_entity,
+            -plane_scroll.scroll_position,
+            button.kind,
+            resource_id,
+            world.as_ref(),
+        );
+    }
+}
+
+/// Lists the links leaving the selected smart splitter with their ratios,
+/// whenever the selection or the links change.
+#[allow(clippy::too_many_arguments)]
+fn track_ratio_target(
+    mut commands: Commands,
+    selected_q: Query<Entity, With<SelectedBox>>,
+    logistics_box_q: Query<&LogisticsBox>,
+    recipe_box_q: Query<&RecipeBox>,
+    link_q: Query<(Entity, &BoxLink, Option<&SplitRatio>)>,
+    added_link_q: Query<(), Added<BoxLink>>,
+    mut removed_links: RemovedComponents<BoxLink>,
+    mut ratio_target: ResMut<RatioTarget>,
+    logistics_ui: Res<LogisticsUi>,
+    world: Res<FactoryWorld>,
+) {
+    let mut selected = selected_q.iter();
+    let splitter_entity = match (selected.next(), selected.next()) {
+        (Some(box_entity), None)
+            if logistics_box_q
+                .get(box_entity)
+                .is_ok_and(|logistics_box| logistics_box.kind == LogisticsKind::SmartSplitter) =>
+        {
+            Some(box_entity)
+        }
+        _ => None,
+    };
+
+    let links_changed = !added_link_q.is_empty() || removed_links.read().count() > 0;
+
+    if splitter_entity == ratio_target.0 && !links_changed {
+        return;
+    }
+
+    ratio_target.0 = splitter_entity;
+
+    commands
+        .entity(logistics_ui.ratio_list_entity)
+        .despawn_descendants();
+
+    let Some(splitter_entity) = splitter_entity else {
+        return;
+    };
+
+    for (link_entity, link, ratio) in link_q.iter() {
+        if link.from_box != splitter_entity {
+            continue;
+        }
+
+        let to_name = match (
+            recipe_box_q.get(link.to_box),
+            logistics_box_q.get(link.to_box),
+        ) {
+            (Ok(recipe_box), _) => world
+                .get_recipe(recipe_box.recipe_id)
+                .map_or("?", |recipe| recipe.name.as_str()),
+            (_, Ok(logistics_box)) => logistics_box.kind.name(),
+            _ => "?",
+        };
+
+        commands
+            .spawn(Node {
+                flex_direction: FlexDirection::Row,
+                column_gap: Val::Px(8.),
+                ..default()
+            })
+            .with_children(|builder| {
+                builder.spawn(Text::new(format!("To {}", to_name)));
+
+                builder.spawn((
+                    SplitRatioInput { link_entity },
+                    TextInput {
+                        value: ratio.map_or(Rational::ONE, |ratio| ratio.0).to_string(),
+                        placeholder: "ratio".into(),
+                    },
+                    BackgroundColor(Color::BLACK),
+                ));
+            })
+            .set_parent(logistics_ui.ratio_list_entity);
+    }
+}
+
+fn submit_split_ratios(
+    mut commands: Commands,
+    mut submitted_events: EventReader<TextInputSubmitted>,
+    mut input_q: Query<(&SplitRatioInput, &mut TextInput)>,
+    ratio_q: Query<&SplitRatio>,
+    mut focused: ResMut<FocusedTextInput>,
+) {
+    for event in submitted_events.read() {
+        let Ok((ratio_input, mut text_input)) = input_q.get_mut(event.entity) else {
+            continue;
+        };
+
+        let mut ratio = ratio_q
+            .get(ratio_input.link_entity)
+            .map_or(Rational::ONE, |ratio| ratio.0);
+
+        match event.value.parse::<Rational>() {
+            Ok(new_ratio) if !new_ratio.is_negative() => {
+                ratio = new_ratio;
+
+                if let Some(mut link_commands) = commands.get_entity(ratio_input.link_entity) {
+                    link_commands.insert(SplitRatio(ratio));
+                }
+            }
+            _ => info!("Invalid split ratio \"{}\"", event.value),
+        }
+
+        text_input.value = ratio.to_string();
+        focused.0 = None;
+    }
+}
+
+/// Rebuilds boxes whose throughput changed, so they show it.
+fn refresh_logistics_boxes(
+    mut commands: Commands,
+    logistics_box_q: Query<(Entity, Ref<LogisticsBox>)>,
+    world: Res<FactoryWorld>,
+) {
+    for (box_entity, logistics_box) in logistics_box_q.iter() {
+        if !logistics_box.is_changed() || logistics_box.is_added() {
+            continue;
+        }
+
+        commands.entity(box_entity).despawn_descendants();
+
+        spawn_logistics_box_contents(&mut commands, box_entity, &logistics_box, world.as_ref());
+    }
+}
@@ -1,9 +1,32 @@
 use bevy::{prelude::*, ui::FocusPolicy};
-
+use serde::Deserialize;
+
+pub mod alternates;
+pub mod blueprints;
+pub mod boundary_boxes;
+pub mod box_groups;
+pub mod box_links;
+pub mod canvas_file;
+pub mod canvas_svg;
+pub mod chain_solve;
 pub mod drag_plane;
+pub mod duplicate_boxes;
 pub mod factory_world;
+pub mod keymap;
+pub mod linear_program;
+pub mod link_flow;
+pub mod logistics;
+pub mod minimap;
+pub mod palette;
+pub mod plan_compare;
+pub mod plan_export;
+pub mod rational;
 pub mod recipe_json;
 pub mod recipe_ui;
+pub mod recipe_watch;
+pub mod sensitivity;
+pub mod solver;
+pub mod text_input;
 
 fn main() {
     let mut app = App::new();
@@ -12,7 +35,26 @@ fn main() {
 
     drag_plane::build(&mut app);
     recipe_ui::build(&mut app);
+    recipe_watch::build(&mut app);
     factory_world::build(&mut app);
+    minimap::build(&mut app);
+    text_input::build(&mut app);
+    keymap::build(&mut app);
+    palette::build(&mut app);
+    box_groups::build(&mut app);
+    box_links::build(&mut app);
+    link_flow::build(&mut app);
+    blueprints::build(&mut app);
+    canvas_file::build(&mut app);
+    duplicate_boxes::build(&mut app);
+    plan_export::build(&mut app);
+    chain_solve::build(&mut app);
+    boundary_boxes::build(&mut app);
+    logistics::build(&mut app);
+    solver::build(&mut app);
+    plan_compare::build(&mut app);
+    sensitivity::build(&mut app);
+    alternates::build(&mut app);
 
     app.insert_resource(ClearColor(Color::BLACK));
     app.add_systems(Startup, create_ui.in_set(CreateBaseUi));
@@ -40,8 +82,45 @@ pub struct MainMenuUI {
 #[require(Button)]
 pub struct MainMenuItem {
     pub sub_menu_entity: Entity,
+    pub menu: MenuName,
+}
+
+/// Names each menu in the main menu, so keys can open it wherever it ends up in the list.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MenuName {
+    Recipes,
+    SourcesAndSinks,
+    Solver,
+    Chain,
+    Logistics,
+    Groups,
+    Blueprints,
+    Canvas,
+    Export,
+    Compare,
+    Sensitivity,
+    Alternates,
 }
 
+impl MenuName {
+    /// In the order of their default keys, F1 to F12.
+    pub const ALL: [MenuName; 12] = [
+        MenuName::Recipes,
+        MenuName::SourcesAndSinks,
+        MenuName::Solver,
+        MenuName::Chain,
+        MenuName::Logistics,
+        MenuName::Groups,
+        MenuName::Blueprints,
+        MenuName::Canvas,
+        MenuName::Export,
+        MenuName::Compare,
+        MenuName::Sensitivity,
+        MenuName::Alternates,
+    ];
+}
+
+#[allow(clippy::default_constructed_unit_structs)]
 fn create_ui(mut commands: Commands) {
     commands.spawn(Camera2d::default());
 
@@ -92,17 +171,33 @@ fn create_ui(mut commands: Commands) {
     commands.insert_resource(MainMenuUI { main_menu_entity });
 }
 
+#[allow(clippy::type_complexity)]
 fn select_top_menus(
     interaction_q: Query<(Entity, &Interaction), (Changed<Interaction>, With<MainMenuItem>)>,
     menu_item_q: Query<&MainMenuItem>,
+    all_menu_items_q: Query<(Entity, &MainMenuItem)>,
     mut node_q: Query<&mut Node>,
+    mut actions: EventReader<keymap::AppAction>,
     mut active_item: Local<Option<Entity>>,
 ) {
-    for (menu_item_entity, interaction) in interaction_q.iter() {
-        let Interaction::Pressed = interaction else {
-            continue;
-        };
+    let pressed_items = interaction_q
+        .iter()
+        .filter(|(_, interaction)| **interaction == Interaction::Pressed)
+        .map(|(menu_item_entity, _)| menu_item_entity);
+
+    // menus toggled from the keyboard are found by their name
+    let toggled_items: Vec<Entity> = actions
+        .read()
+        .filter_map(|action| match action {
+            keymap::AppAction::ToggleMenu(menu) => all_menu_items_q
+                .iter()
+                .find(|(_, menu_item)| menu_item.menu == *menu)
+                .map(|(menu_item_entity, _)| menu_item_entity),
+            _ => None,
+        })
+        .collect();
 
+    for menu_item_entity in pressed_items.chain(toggled_items) {
         if let Some(previous_active_item_entity) = active_item.take() {
             let menu_item = menu_item_q
                 .get(previous_active_item_entity)
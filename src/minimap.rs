@@ -0,0 +1,237 @@
+use bevy::{
+    prelude::*,
+    ui::{FocusPolicy, RelativeCursorPosition},
+    utils::HashMap,
+};
+
+use crate::{
+    CreateBaseUi,
+    drag_plane::{DragBox, DragBoxPlane, PlaneScroll},
+};
+
+const MINIMAP_SIZE: Vec2 = Vec2::new(200., 150.);
+const MINIMAP_PADDING: f32 = 50.;
+
+pub fn build(app: &mut App) {
+    app.add_systems(Startup, create_minimap.after(CreateBaseUi));
+    app.add_systems(Update, (drag_minimap, update_minimap).chain());
+}
+
+/// Overlay in the corner of the [`DragBoxPlane`] showing where all the boxes are.
+#[derive(Component)]
+#[require(Node, Interaction, RelativeCursorPosition, FocusPolicy(|| FocusPolicy::Block))]
+pub struct Minimap {
+    /// Maps a position on the minimap, in pixels from the top left, to a position on the plane.
+    ///
+    /// Kept as it is while dragging, so the same point on the minimap stays the same point on the plane.
+    view_offset: Vec2,
+    view_scale: f32,
+    /// Whether the viewport is being dragged around on the minimap.
+    dragging: bool,
+    /// The minimap representation of each drag box.
+    box_markers: HashMap<Entity, Entity>,
+}
+
+/// Rectangle on the minimap showing the visible part of the plane.
+#[derive(Component)]
+struct MinimapViewport;
+
+fn create_minimap(mut commands: Commands, plane_q: Query<Entity, With<DragBoxPlane>>) {
+    let Ok(plane_entity) = plane_q.get_single() else {
+        error!("Couldn't find drag box plane to put minimap on");
+        return;
+    };
+
+    commands
+        .spawn((
+            Minimap {
+                view_offset: Vec2::ZERO,
+                view_scale: 1.,
+                dragging: false,
+                box_markers: HashMap::default(),
+            },
+            Node {
+                position_type: PositionType::Absolute,
+                right: Val::Px(8.),
+                bottom: Val::Px(8.),
+                width: Val::Px(MINIMAP_SIZE.x),
+                height: Val::Px(MINIMAP_SIZE.y),
+                border: UiRect::all(Val::Px(1.)),
+                overflow: Overflow::clip(),
+                ..default()
+            },
+            BackgroundColor(Srgba::new(0., 0., 0., 0.7).into()),
+            BorderColor(Srgba::gray(0.5).into()),
+            GlobalZIndex(1),
+        ))
+        .set_parent(plane_entity)
+        .with_children(|builder| {
+            builder.spawn((
+                MinimapViewport,
+                Node {
+                    position_type: PositionType::Absolute,
+                    border: UiRect::all(Val::Px(1.)),
+                    ..default()
+                },
+                BorderColor(Color::WHITE),
+                FocusPolicy::Pass,
+            ));
+        });
+}
+
+/// Recalculates the minimap bounds from all the drag boxes and the viewport, unless the viewport
+/// is being dragged, and repositions the markers.
+fn update_minimap(
+    mut commands: Commands,
+    mut minimap_q: Query<(Entity, &mut Minimap)>,
+    plane_q: Query<&ComputedNode, With<DragBoxPlane>>,
+    drag_box_q: Query<(Entity, &DragBox, &ComputedNode, &Node)>,
+    mut marker_q: Query<&mut Node, (Without<DragBox>, Without<MinimapViewport>)>,
+    mut viewport_q: Query<&mut Node, (With<MinimapViewport>, Without<DragBox>)>,
+    plane_scroll: Res<PlaneScroll>,
+) {
+    let Ok((minimap_entity, mut minimap)) = minimap_q.get_single_mut() else {
+        return;
+    };
+
+    let Ok(plane_node) = plane_q.get_single() else {
+        error!("Couldn't query drag box plane");
+        return;
+    };
+
+    let viewport = Rect::from_corners(
+        -plane_scroll.scroll_position,
+        -plane_scroll.scroll_position + plane_node.size() * plane_node.inverse_scale_factor(),
+    );
+
+    let mut bounds = viewport;
+    let mut box_rects = Vec::new();
+
+    for (drag_box_entity, drag_box, computed_node, node) in drag_box_q.iter() {
+        if node.display == Display::None {
+            continue;
+        }
+
+        let rect = Rect::from_corners(
+            drag_box.position,
+            drag_box.position + computed_node.size() * computed_node.inverse_scale_factor(),
+        );
+
+        bounds = bounds.union(rect);
+        box_rects.push((drag_box_entity, rect));
+    }
+
+    // fit the bounds to the minimap keeping the aspect ratio, centered on both axes
+    if !minimap.dragging {
+        let bounds = bounds.inflate(MINIMAP_PADDING);
+        let scale = (MINIMAP_SIZE / bounds.size()).min_element();
+        minimap.view_scale = scale;
+        minimap.view_offset = bounds.center() - MINIMAP_SIZE * 0.5 / scale;
+    }
+
+    let scale = minimap.view_scale;
+    let view_offset = minimap.view_offset;
+
+    let to_minimap = |rect: Rect| {
+        (
+            (rect.min - view_offset) * scale,
+            (rect.size() * scale).max(Vec2::splat(2.)),
+        )
+    };
+
+    // remove markers for boxes that no longer exist or are hidden
+    let mut box_markers = std::mem::take(&mut minimap.box_markers);
+
+    box_markers.retain(|drag_box_entity, marker_entity| {
+        let keep = box_rects
+            .iter()
+            .any(|(entity, _)| entity == drag_box_entity);
+
+        if !keep && let Some(marker_commands) = commands.get_entity(*marker_entity) {
+            marker_commands.despawn_recursive();
+        }
+
+        keep
+    });
+
+    for (drag_box_entity, rect) in box_rects {
+        let (position, size) = to_minimap(rect);
+
+        if let Some(mut marker_node) = box_markers
+            .get(&drag_box_entity)
+            .and_then(|marker_entity| marker_q.get_mut(*marker_entity).ok())
+        {
+            marker_node.left = Val::Px(position.x);
+            marker_node.top = Val::Px(position.y);
+            marker_node.width = Val::Px(size.x);
+            marker_node.height = Val::Px(size.y);
+            continue;
+        }
+
+        let marker_entity = commands
+            .spawn((
+                Node {
+                    position_type: PositionType::Absolute,
+                    left: Val::Px(position.x),
+                    top: Val::Px(position.y),
+                    width: Val::Px(size.x),
+                    height: Val::Px(size.y),
+                    ..default()
+                },
+                BackgroundColor(Srgba::rgb(0.8, 0.2, 0.2).into()),
+                FocusPolicy::Pass,
+            ))
+            .set_parent(minimap_entity)
+            .id();
+
+        box_markers.insert(drag_box_entity, marker_entity);
+    }
+
+    minimap.box_markers = box_markers;
+
+    if let Ok(mut viewport_node) = viewport_q.get_single_mut() {
+        let (position, size) = to_minimap(viewport);
+        viewport_node.left = Val::Px(position.x);
+        viewport_node.top = Val::Px(position.y);
+        viewport_node.width = Val::Px(size.x);
+        viewport_node.height = Val::Px(size.y);
+    }
+}
+
+/// Clicking or dragging on the minimap centers the viewport on that point of the plane.
+fn drag_minimap(
+    mouse_input: Res<ButtonInput<MouseButton>>,
+    mut minimap_q: Query<(&mut Minimap, &Interaction, &RelativeCursorPosition)>,
+    plane_q: Query<&ComputedNode, With<DragBoxPlane>>,
+    mut plane_scroll: ResMut<PlaneScroll>,
+) {
+    let Ok((mut minimap, interaction, cursor_position)) = minimap_q.get_single_mut() else {
+        return;
+    };
+
+    if mouse_input.just_pressed(MouseButton::Left) {
+        minimap.dragging = *interaction == Interaction::Pressed;
+    }
+
+    if !mouse_input.pressed(MouseButton::Left) {
+        minimap.dragging = false;
+    }
+
+    if !minimap.dragging {
+        return;
+    }
+
+    let Some(normalized) = cursor_position.normalized else {
+        return;
+    };
+
+    let Ok(plane_node) = plane_q.get_single() else {
+        error!("Couldn't query drag box plane");
+        return;
+    };
+
+    let plane_position = minimap.view_offset + normalized * MINIMAP_SIZE / minimap.view_scale;
+    let viewport_size = plane_node.size() * plane_node.inverse_scale_factor();
+
+    plane_scroll.scroll_position = -(plane_position - viewport_size * 0.5);
+}
@@ -0,0 +1,385 @@
+use bevy::prelude::*;
+
+use crate::{
+    drag_plane::{DragBoxPlane, PlaneScroll},
+    factory_world::*,
+    keymap::{AppAction, action_sent},
+    plan_export::ExportFormat,
+    rational::Rational,
+    recipe_ui::spawn_recipe_box,
+    text_input::{FocusedTextInput, TextInput, TextInputSubmitted, TextInputSystems},
+};
+
+/// How many matches are listed under the search.
+const MAX_RESULTS: usize = 12;
+
+pub fn build(app: &mut App) {
+    app.insert_resource(PaletteResults(Vec::new()));
+
+    app.add_systems(Startup, create_palette_ui);
+    app.add_systems(
+        Update,
+        (
+            open_palette,
+            update_palette_results,
+            run_palette_entries,
+            close_palette,
+        )
+            .chain()
+            .after(TextInputSystems),
+    );
+}
+
+/// Something that can be searched for and run from the palette.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PaletteEntry {
+    Action(AppAction),
+    InsertRecipe(RecipeId),
+    /// Inserts a recipe that makes the resource.
+    Produce(ResourceId, RecipeId),
+}
+
+impl PaletteEntry {
+    fn label(self, world: &FactoryWorld) -> Option<String> {
+        match self {
+            PaletteEntry::Action(action) => action_label(action).map(str::to_string),
+            PaletteEntry::InsertRecipe(recipe_id) => {
+                Some(format!("Insert {}", world.get_recipe(recipe_id).ok()?.name))
+            }
+            PaletteEntry::Produce(resource_id, recipe_id) => Some(format!(
+                "Produce {} with {}",
+                world.get_resource_name(resource_id)?,
+                world.get_recipe(recipe_id).ok()?.name
+            )),
+        }
+    }
+}
+
+fn action_label(action: AppAction) -> Option<&'static str> {
+    match action {
+        AppAction::LoadRecipes => Some("Load Recipes"),
+        AppAction::SaveCanvas => Some("Save Canvas"),
+        AppAction::OpenCanvas => Some("Open Canvas"),
+        AppAction::DeleteSelected => Some("Delete Selected Boxes"),
+        AppAction::DuplicateSelected => Some("Duplicate Selected Boxes"),
+        AppAction::CopySelected => Some("Copy Selected Boxes"),
+        AppAction::Paste => Some("Paste Boxes"),
+        AppAction::ResetView => Some("Reset View"),
+        AppAction::ToggleIconMode => Some("Toggle Icon Mode"),
+        AppAction::Solve => Some("Solve for Sinks"),
+        AppAction::Export(ExportFormat::Csv) => Some("Export CSV"),
+        AppAction::Export(ExportFormat::Markdown) => Some("Export Markdown"),
+        AppAction::Export(ExportFormat::SolutionCsv) => Some("Export Solution CSV"),
+        AppAction::Export(ExportFormat::SolutionMarkdown) => Some("Export Solution Markdown"),
+        AppAction::Export(ExportFormat::Svg) => Some("Export SVG"),
+        AppAction::Export(ExportFormat::RecipeGraph) => Some("Export Recipe Graph"),
+        AppAction::Export(ExportFormat::LinkGraph) => Some("Export Link Graph"),
+        AppAction::PanUp
+        | AppAction::PanDown
+        | AppAction::PanLeft
+        | AppAction::PanRight
+        | AppAction::ToggleMenu(_)
+        | AppAction::OpenPalette => None,
+    }
+}
+
+/// Every entry the palette can offer, with its label.
+fn palette_entries(world: &FactoryWorld) -> Vec<(PaletteEntry, String)> {
+    let actions = [
+        AppAction::LoadRecipes,
+        AppAction::SaveCanvas,
+        AppAction::OpenCanvas,
+        AppAction::DeleteSelected,
+        AppAction::DuplicateSelected,
+        AppAction::CopySelected,
+        AppAction::Paste,
+        AppAction::ResetView,
+        AppAction::ToggleIconMode,
+        AppAction::Solve,
+    ]
+    .into_iter()
+    .chain(ExportFormat::ALL.into_iter().map(AppAction::Export))
+    .map(PaletteEntry::Action);
+
+    let recipes = world.iter_recipes().flat_map(|(recipe_id, recipe)| {
+        std::iter::once(PaletteEntry::InsertRecipe(recipe_id)).chain(
+            recipe
+                .products
+                .iter()
+                .map(move |&(resource_id, _)| PaletteEntry::Produce(resource_id, recipe_id)),
+        )
+    });
+
+    actions
+        .chain(recipes)
+        .filter_map(|entry| Some((entry, entry.label(world)?)))
+        .collect()
+}
+
+/// Scores how well a search matches some text by finding its characters in order,
+/// favouring matches at the start of words and runs of matching characters.
+fn fuzzy_score(search: &str, text: &str) -> Option<i32> {
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    let mut score = 0;
+    let mut text_index = 0;
+    let mut previous_match = None;
+
+    for search_char in search.to_lowercase().chars().filter(|c| !c.is_whitespace()) {
+        let match_index = text_index + text[text_index..].iter().position(|&c| c == search_char)?;
+
+        score += 1;
+
+        if match_index == 0 || !text[match_index - 1].is_alphanumeric() {
+            score += 8;
+        }
+
+        if previous_match.is_some_and(|previous| previous + 1 == match_index) {
+            score += 4;
+        }
+
+        previous_match = Some(match_index);
+        text_index = match_index + 1;
+    }
+
+    // shorter texts are closer matches
+    Some(score * 100 - text.len() as i32)
+}
+
+#[derive(Resource)]
+struct PaletteUi {
+    root_entity: Entity,
+    input_entity: Entity,
+    result_list_entity: Entity,
+}
+
+/// The entries currently listed, best match first.
+#[derive(Resource)]
+struct PaletteResults(Vec<PaletteEntry>);
+
+#[derive(Component)]
+#[require(Button)]
+struct PaletteResultButton {
+    entry: PaletteEntry,
+}
+
+fn create_palette_ui(mut commands: Commands) {
+    let root_entity = commands
+        .spawn((
+            Node {
+                display: Display::None,
+                position_type: PositionType::Absolute,
+                top: Val::Px(40.),
+                left: Val::Percent(30.),
+                width: Val::Percent(40.),
+                flex_direction: FlexDirection::Column,
+                padding: UiRect::all(Val::Px(4.)),
+                ..default()
+            },
+            BackgroundColor(Srgba::rgb(0.15, 0.15, 0.15).into()),
+            GlobalZIndex(2),
+        ))
+        .id();
+
+    let input_entity = commands
+        .spawn((
+            TextInput::new("Search actions, recipes and resources"),
+            BackgroundColor(Color::BLACK),
+        ))
+        .set_parent(root_entity)
+        .id();
+
+    let result_list_entity = commands
+        .spawn(Node {
+            flex_direction: FlexDirection::Column,
+            ..default()
+        })
+        .set_parent(root_entity)
+        .id();
+
+    commands.insert_resource(PaletteUi {
+        root_entity,
+        input_entity,
+        result_list_entity,
+    });
+}
+
+fn open_palette(
+    mut actions: EventReader<AppAction>,
+    palette_ui: Res<PaletteUi>,
+    mut node_q: Query<&mut Node>,
+    mut input_q: Query<&mut TextInput>,
+    mut focused: ResMut<FocusedTextInput>,
+) {
+    if !action_sent(&mut actions, AppAction::OpenPalette) {
+        return;
+    }
+
+    let mut node = node_q
+        .get_mut(palette_ui.root_entity)
+        .expect("Should be a node");
+    node.display = Display::Flex;
+
+    let mut input = input_q
+        .get_mut(palette_ui.input_entity)
+        .expect("Should be a text input");
+    input.value.clear();
+
+    focused.0 = Some(palette_ui.input_entity);
+}
+
+/// Relists the best matches whenever the search changes.
+fn update_palette_results(
+    mut commands: Commands,
+    palette_ui: Res<PaletteUi>,
+    input_q: Query<Ref<TextInput>>,
+    world: Res<FactoryWorld>,
+    mut results: ResMut<PaletteResults>,
+) {
+    let input = input_q
+        .get(palette_ui.input_entity)
+        .expect("Should be a text input");
+
+    if !input.is_changed() && !world.is_changed() {
+        return;
+    }
+
+    let mut matches: Vec<(i32, PaletteEntry, String)> = palette_entries(world.as_ref())
+        .into_iter()
+        .filter_map(|(entry, label)| Some((fuzzy_score(&input.value, &label)?, entry, label)))
+        .collect();
+
+    matches.sort_by(|(a_score, _, a_label), (b_score, _, b_label)| {
+        b_score.cmp(a_score).then_with(|| a_label.cmp(b_label))
+    });
+    matches.truncate(MAX_RESULTS);
+
+    commands
+        .entity(palette_ui.result_list_entity)
+        .despawn_descendants();
+
+    results.0.clear();
+
+    for (_, entry, label) in matches {
+        commands
+            .spawn((PaletteResultButton { entry }, Text::new(label)))
+            .set_parent(palette_ui.result_list_entity);
+
+        results.0.push(entry);
+    }
+}
+
+/// Runs an entry when it's clicked, or the best match when enter is pressed.
+#[allow(clippy::too_many_arguments)]
+fn run_palette_entries(
+    mut commands: Commands,
+    button_q: Query<(&Interaction, &PaletteResultButton), Changed<Interaction>>,
+    mut submitted_events: EventReader<TextInputSubmitted>,
+    palette_ui: Res<PaletteUi>,
+    results: Res<PaletteResults>,
+    mut focused: ResMut<FocusedTextInput>,
+    mut actions: EventWriter<AppAction>,
+    world: Res<FactoryWorld>,
+    plane_q: Query<Entity, With<DragBoxPlane>>,
+    plane_scroll: Res<PlaneScroll>,
+) {
+    let clicked_entries = button_q
+        .iter()
+        .filter(|(interaction, _)| **interaction == Interaction::Pressed)
+        .map(|(_, button)| button.entry);
+
+    let submitted_entries: Vec<PaletteEntry> = submitted_events
+        .read()
+        .filter(|event| event.entity == palette_ui.input_entity)
+        .filter_map(|_| results.0.first().copied())
+        .collect();
+
+    for entry in clicked_entries.chain(submitted_entries) {
+        focused.0 = None;
+
+        match entry {
+            PaletteEntry::Action(action) => {
+                actions.send(action);
+            }
+            PaletteEntry::InsertRecipe(recipe_id) | PaletteEntry::Produce(_, recipe_id) => {
+                let root_plane_entity = plane_q.get_single().expect("Should be one root plane");
+
+                if spawn_recipe_box(
+                    &mut commands,
+                    root_plane_entity,
+                    -plane_scroll.scroll_position,
+                    recipe_id,
+                    Rational::ONE,
+                    world.as_ref(),
+                )
+                .is_err()
+                {
+                    warn!("Recipe {:?} from the palette no longer exists", recipe_id);
+                }
+            }
+        }
+    }
+}
+
+/// The palette closes whenever its search loses focus, like after escape or clicking elsewhere.
+fn close_palette(
+    palette_ui: Res<PaletteUi>,
+    focused: Res<FocusedTextInput>,
+    mut node_q: Query<&mut Node>,
+) {
+    if focused.0 == Some(palette_ui.input_entity) {
+        return;
+    }
+
+    let mut node = node_q
+        .get_mut(palette_ui.root_entity)
+        .expect("Should be a node");
+
+    if node.display != Display::None {
+        node.display = Display::None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fuzzy_score_needs_every_character_in_order() {
+        assert!(fuzzy_score("iron", "Iron Plate").is_some());
+        assert!(fuzzy_score("ipl", "Iron Plate").is_some());
+        assert_eq!(fuzzy_score("plate iron", "Iron Plate"), None);
+        assert_eq!(fuzzy_score("ironx", "Iron Plate"), None);
+    }
+
+    #[test]
+    fn fuzzy_score_ignores_case_and_spaces() {
+        assert_eq!(
+            fuzzy_score("IRON PLATE", "iron plate"),
+            fuzzy_score("ironplate", "Iron Plate")
+        );
+    }
+
+    #[test]
+    fn fuzzy_score_prefers_word_starts_and_runs() {
+        let word_start = fuzzy_score("p", "Iron Plate").unwrap();
+        let mid_word = fuzzy_score("p", "Copper Ore").unwrap();
+        assert!(word_start > mid_word);
+
+        let run = fuzzy_score("ro", "Iron").unwrap();
+        let scattered = fuzzy_score("ro", "Irxo").unwrap();
+        assert!(run > scattered);
+    }
+
+    #[test]
+    fn fuzzy_score_prefers_shorter_texts() {
+        assert!(
+            fuzzy_score("plate", "Plate").unwrap() > fuzzy_score("plate", "Plate Maker").unwrap()
+        );
+    }
+
+    #[test]
+    fn empty_search_matches_everything() {
+        assert!(fuzzy_score("", "Anything").is_some());
+        assert!(fuzzy_score("  ", "").is_some());
+    }
+}
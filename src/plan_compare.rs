@@ -0,0 +1,501 @@
+use std::fmt::Write;
+
+use bevy::prelude::*;
+
+use crate::{
+    ControlPanelUi, CreateBaseUi, MainMenuItem, MainMenuUI, MenuName,
+    boundary_boxes::BoundaryBox,
+    factory_world::*,
+    plan_export::Plan,
+    rational::Rational,
+    recipe_ui::RecipeBox,
+    solver::{LastSolution, ProductionSolution},
+};
+
+pub fn build(app: &mut App) {
+    app.insert_resource(PlanSnapshots([None, None]));
+
+    app.add_systems(Startup, create_compare_ui.after(CreateBaseUi));
+    app.add_systems(Update, take_snapshots);
+}
+
+/// A plan boiled down to what's worth comparing between plans.
+#[derive(Clone)]
+pub struct PlanSnapshot {
+    /// Where the snapshot was taken from.
+    pub label: String,
+    /// Machines running each recipe, summed by recipe name and sorted.
+    pub machines: Vec<(String, Rational)>,
+    /// Per minute brought in from outside the plan, apart from power.
+    pub raw_inputs: Vec<(ResourceId, Rational)>,
+    /// Per minute the sinks want that the plan doesn't make.
+    pub unmet_sinks: Vec<(ResourceId, Rational)>,
+    /// Machines and AWESOME sinks, counted by [`crate::solver::plan_totals`].
+    pub building_count: Rational,
+    /// MW used by the machines and sinks.
+    pub power_used: Rational,
+}
+
+impl PlanSnapshot {
+    /// Raw inputs are what the sources bring in and what the boxes use more of than they make.
+    /// What the sinks want beyond that is counted as unmet instead.
+    pub fn from_plan(label: &str, plan: &Plan, world: &FactoryWorld) -> Self {
+        let mut raw_inputs: Vec<(ResourceId, Rational)> = plan.sources.clone();
+        let mut unmet_sinks = Vec::new();
+        let totals = plan.totals(world);
+
+        for (resource_id, rate) in plan.net_rates(world) {
+            let sunk: Rational = plan
+                .sinks
+                .iter()
+                .filter(|&&(id, _)| id == resource_id)
+                .map(|&(_, rate)| rate)
+                .sum();
+
+            // what's left for the sinks after the boxes have taken what they use
+            let available = rate + sunk;
+
+            if available.is_negative() {
+                add_rate(&mut raw_inputs, resource_id, -available);
+            }
+
+            let unmet = sunk - available.max(Rational::ZERO);
+
+            if unmet.is_positive() {
+                add_rate(&mut unmet_sinks, resource_id, unmet);
+            }
+        }
+
+        PlanSnapshot {
+            label: label.to_string(),
+            machines: plan
+                .boxes
+                .iter()
+                .map(|plan_box| (plan_box.recipe_name.clone(), plan_box.multiplier))
+                .collect(),
+            raw_inputs,
+            unmet_sinks,
+            building_count: totals.building_count,
+            power_used: totals.power_used,
+        }
+        .normalised(world)
+    }
+
+    pub fn from_solution(label: &str, solution: &ProductionSolution, world: &FactoryWorld) -> Self {
+        PlanSnapshot {
+            label: label.to_string(),
+            machines: solution
+                .multipliers
+                .iter()
+                .filter_map(|&(recipe_id, multiplier)| {
+                    Some((world.get_recipe(recipe_id).ok()?.name.clone(), multiplier))
+                })
+                .collect(),
+            raw_inputs: solution.raw_inputs.clone(),
+            unmet_sinks: Vec::new(),
+            building_count: solution.building_count,
+            power_used: solution.power_used,
+        }
+        .normalised(world)
+    }
+
+    /// Merges repeated recipes and resources, drops power from the raw inputs and sorts by name.
+    fn normalised(mut self, world: &FactoryWorld) -> Self {
+        let mut machines: Vec<(String, Rational)> = Vec::new();
+
+        for (recipe_name, multiplier) in self.machines {
+            match machines.iter_mut().find(|(name, _)| *name == recipe_name) {
+                Some((_, total)) => *total += multiplier,
+                None => machines.push((recipe_name, multiplier)),
+            }
+        }
+
+        machines.sort();
+        self.machines = machines;
+
+        let mut raw_inputs = Vec::new();
+
+        for (resource_id, rate) in self.raw_inputs {
+            if world.get_resource_name(resource_id) != Some(POWER_RESOURCE) {
+                add_rate(&mut raw_inputs, resource_id, rate);
+            }
+        }
+
+        raw_inputs.sort_by_key(|&(resource_id, _)| world.get_resource_name(resource_id));
+        self.raw_inputs = raw_inputs;
+
+        self.unmet_sinks
+            .sort_by_key(|&(resource_id, _)| world.get_resource_name(resource_id));
+
+        self
+    }
+}
+
+fn add_rate(rates: &mut Vec<(ResourceId, Rational)>, resource_id: ResourceId, rate: Rational) {
+    match rates.iter_mut().find(|(id, _)| *id == resource_id) {
+        Some((_, total)) => *total += rate,
+        None => rates.push((resource_id, rate)),
+    }
+}
+
+/// Formats a change with its sign, even when it's positive.
+pub fn signed(delta: Rational) -> String {
+    if delta.is_negative() {
+        delta.to_string()
+    } else {
+        format!("+{}", delta)
+    }
+}
+
+/// Everything in either list whose amount differs, with the amount in each.
+fn amount_changes<K: PartialEq + Copy>(
+    a: &[(K, Rational)],
+    b: &[(K, Rational)],
+) -> Vec<(K, Rational, Rational)> {
+    let amount = |side: &[(K, Rational)], key: K| {
+        side.iter()
+            .find(|(other, _)| *other == key)
+            .map_or(Rational::ZERO, |&(_, amount)| amount)
+    };
+
+    let mut keys: Vec<K> = Vec::new();
+
+    for &(key, _) in a.iter().chain(b.iter()) {
+        if !keys.contains(&key) {
+            keys.push(key);
+        }
+    }
+
+    keys.into_iter()
+        .map(|key| (key, amount(a, key), amount(b, key)))
+        .filter(|(_, a_amount, b_amount)| a_amount != b_amount)
+        .collect()
+}
+
+fn machine_amounts(snapshot: &PlanSnapshot) -> Vec<(&str, Rational)> {
+    snapshot
+        .machines
+        .iter()
+        .map(|(recipe_name, multiplier)| (recipe_name.as_str(), *multiplier))
+        .collect()
+}
+
+/// A report of what changes going from plan `a` to plan `b`, leaving out whatever stays the same.
+pub fn compare_plans(a: &PlanSnapshot, b: &PlanSnapshot, world: &FactoryWorld) -> String {
+    let mut report = format!("A: {} -> B: {}\n", a.label, b.label);
+
+    writeln!(
+        report,
+        "Buildings: {} -> {} ({})",
+        a.building_count,
+        b.building_count,
+        signed(b.building_count - a.building_count)
+    )
+    .unwrap();
+
+    writeln!(
+        report,
+        "Power: {} -> {} MW ({})",
+        a.power_used,
+        b.power_used,
+        signed(b.power_used - a.power_used)
+    )
+    .unwrap();
+
+    let mut machine_changes = amount_changes(&machine_amounts(a), &machine_amounts(b));
+    machine_changes.sort_by_key(|&(recipe_name, _, _)| recipe_name);
+
+    if !machine_changes.is_empty() {
+        report.push_str("Machines:\n");
+
+        for (recipe_name, a_amount, b_amount) in machine_changes {
+            writeln!(
+                report,
+                "  {}: {} -> {} ({})",
+                recipe_name,
+                a_amount,
+                b_amount,
+                signed(b_amount - a_amount)
+            )
+            .unwrap();
+        }
+    }
+
+    for (title, a_rates, b_rates) in [
+        ("Raw inputs per minute", &a.raw_inputs, &b.raw_inputs),
+        ("Unmet sinks per minute", &a.unmet_sinks, &b.unmet_sinks),
+    ] {
+        let mut changes = amount_changes(a_rates, b_rates);
+        changes.sort_by_key(|&(resource_id, _, _)| world.get_resource_name(resource_id));
+
+        if changes.is_empty() {
+            continue;
+        }
+
+        writeln!(report, "{}:", title).unwrap();
+
+        for (resource_id, a_amount, b_amount) in changes {
+            writeln!(
+                report,
+                "  {}: {} -> {} ({})",
+                world.get_resource_name(resource_id).unwrap_or("?"),
+                a_amount,
+                b_amount,
+                signed(b_amount - a_amount)
+            )
+            .unwrap();
+        }
+    }
+
+    report
+}
+
+/// The two plans being compared.
+#[derive(Resource)]
+pub struct PlanSnapshots(pub [Option<PlanSnapshot>; 2]);
+
+const SLOT_NAMES: [&str; 2] = ["A", "B"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SnapshotSource {
+    Canvas,
+    Solution,
+}
+
+#[derive(Component)]
+#[require(Button)]
+struct SnapshotButton {
+    slot: usize,
+    source: SnapshotSource,
+}
+
+#[derive(Resource)]
+struct CompareUi {
+    slot_text_entities: [Entity; 2],
+    comparison_text_entity: Entity,
+}
+
+fn create_compare_ui(
+    mut commands: Commands,
+    main_menu: Res<MainMenuUI>,
+    control_panel: Res<ControlPanelUi>,
+) {
+    let sub_menu_entity = commands
+        .spawn((Node {
+            display: Display::None,
+            flex_direction: FlexDirection::Column,
+            ..default()
+        },))
+        .set_parent(control_panel.control_panel_entity)
+        .id();
+
+    commands
+        .spawn((
+            MainMenuItem {
+                sub_menu_entity,
+                menu: MenuName::Compare,
+            },
+            Text::new("Compare"),
+            Node {
+                width: Val::Percent(100.0),
+                ..default()
+            },
+        ))
+        .set_parent(main_menu.main_menu_entity);
+
+    let mut slot_text_entities = [Entity::PLACEHOLDER; 2];
+
+    for (slot, slot_name) in SLOT_NAMES.into_iter().enumerate() {
+        slot_text_entities[slot] = commands
+            .spawn(Text::new(format!("{}: nothing yet", slot_name)))
+            .set_parent(sub_menu_entity)
+            .id();
+
+        for (source, source_name) in [
+            (SnapshotSource::Canvas, "Canvas"),
+            (SnapshotSource::Solution, "Solution"),
+        ] {
+            commands
+                .spawn((
+                    SnapshotButton { slot, source },
+                    Text::new(format!("Snapshot {} as {}", source_name, slot_name)),
+                ))
+                .set_parent(sub_menu_entity);
+        }
+    }
+
+    let comparison_text_entity = commands
+        .spawn(Text::default())
+        .set_parent(sub_menu_entity)
+        .id();
+
+    commands.insert_resource(CompareUi {
+        slot_text_entities,
+        comparison_text_entity,
+    });
+}
+
+#[allow(clippy::too_many_arguments)]
+fn take_snapshots(
+    button_q: Query<(&Interaction, &SnapshotButton), Changed<Interaction>>,
+    recipe_box_q: Query<&RecipeBox>,
+    boundary_box_q: Query<&BoundaryBox>,
+    last_solution: Res<LastSolution>,
+    mut snapshots: ResMut<PlanSnapshots>,
+    mut snapshot_count: Local<usize>,
+    compare_ui: Res<CompareUi>,
+    mut text_q: Query<&mut Text>,
+    world: Res<FactoryWorld>,
+) {
+    for (interaction, button) in button_q.iter() {
+        let Interaction::Pressed = interaction else {
+            continue;
+        };
+
+        let mut slot_text = text_q
+            .get_mut(compare_ui.slot_text_entities[button.slot])
+            .expect("Should be a text");
+
+        let slot_name = SLOT_NAMES[button.slot];
+
+        // numbered so snapshots from the same place can be told apart
+        let label = |source_name: &str| format!("{} #{}", source_name, *snapshot_count + 1);
+
+        let snapshot = match button.source {
+            SnapshotSource::Canvas => {
+                let plan = Plan::from_recipe_boxes(recipe_box_q.iter(), world.as_ref())
+                    .with_boundaries(boundary_box_q.iter(), world.as_ref());
+
+                PlanSnapshot::from_plan(&label("Canvas"), &plan, world.as_ref())
+            }
+            SnapshotSource::Solution => {
+                let Some(solution) = &last_solution.0 else {
+                    slot_text.0 = format!("{}: solve something first", slot_name);
+                    continue;
+                };
+
+                PlanSnapshot::from_solution(&label("Solution"), solution, world.as_ref())
+            }
+        };
+
+        *snapshot_count += 1;
+
+        slot_text.0 = format!(
+            "{}: {}, {} buildings, {} MW",
+            slot_name, snapshot.label, snapshot.building_count, snapshot.power_used
+        );
+
+        snapshots.0[button.slot] = Some(snapshot);
+
+        let mut comparison_text = text_q
+            .get_mut(compare_ui.comparison_text_entity)
+            .expect("Should be a text");
+
+        comparison_text.0 = match &snapshots.0 {
+            [Some(a), Some(b)] => compare_plans(a, b, world.as_ref()),
+            _ => String::new(),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        boundary_boxes::{BoundaryKind, SinkKind},
+        plan_export::PlanBox,
+        recipe_json::{LoadOptions, load_recipes},
+        solver::{
+            ByproductPolicies, ByproductPolicy, ObjectiveMetric, ObjectiveWeights,
+            ProductionProblem, solve_production,
+        },
+    };
+    use std::collections::HashMap;
+
+    #[test]
+    fn unmet_sinks_are_not_raw_inputs() {
+        let mut world = FactoryWorld::default();
+        let ore = world.get_resource_id("Ore");
+        let plate = world.get_resource_id("Plate");
+        let int = Rational::from_integer;
+
+        let plan = Plan {
+            boxes: vec![PlanBox {
+                recipe_name: "Plate".into(),
+                building: None,
+                multiplier: int(1),
+                inputs: vec![(ore, int(30))],
+                outputs: vec![(plate, int(20))],
+            }],
+            sources: vec![(ore, int(20))],
+            sinks: vec![(plate, int(50))],
+            surplus_sinks: Vec::new(),
+        };
+
+        let snapshot = PlanSnapshot::from_plan("Canvas", &plan, &world);
+
+        assert_eq!(snapshot.raw_inputs, vec![(ore, int(30))]);
+        assert_eq!(snapshot.unmet_sinks, vec![(plate, int(30))]);
+    }
+
+    #[test]
+    fn canvas_and_solution_snapshots_count_the_same() {
+        let mut world = FactoryWorld::default();
+        load_recipes(
+            &mut world,
+            r#"{"recipes": [{"name": "Refine", "rates":
+                [["Oil", -30], ["Mega Joule", -1800], ["Fuel", 20], ["Residue", 10]]}]}"#,
+            LoadOptions::default(),
+        )
+        .unwrap();
+
+        let fuel = world.get_resource_id("Fuel");
+        let residue = world.get_resource_id("Residue");
+        let refine = world.get_recipe_id("Refine").unwrap();
+        let int = Rational::from_integer;
+
+        let problem = ProductionProblem {
+            targets: HashMap::from([(fuel, int(30))]),
+            raw_limits: HashMap::new(),
+        };
+        let policies = ByproductPolicies(HashMap::from([(residue, ByproductPolicy::Sink)]));
+        let solution = solve_production(
+            &problem,
+            &policies,
+            &ObjectiveWeights::only(ObjectiveMetric::Buildings),
+            &world,
+        )
+        .unwrap();
+
+        let sink = |kind, resource_id, rate| BoundaryBox {
+            kind: BoundaryKind::Sink(kind),
+            resource_id,
+            rate,
+            uses_purity: false,
+        };
+        let plan = Plan::from_recipe_boxes(
+            &[RecipeBox {
+                recipe_id: refine,
+                multiplier: Rational::new(3, 2),
+            }],
+            &world,
+        )
+        .with_boundaries(
+            &[
+                sink(SinkKind::Output, fuel, int(30)),
+                sink(SinkKind::AwesomeSink, residue, int(60)),
+            ],
+            &world,
+        );
+
+        let from_solution = PlanSnapshot::from_solution("Solution", &solution, &world);
+        let from_plan = PlanSnapshot::from_plan("Canvas", &plan, &world);
+
+        assert_eq!(from_plan.building_count, from_solution.building_count);
+        assert_eq!(from_plan.power_used, from_solution.power_used);
+        assert_eq!(
+            from_plan.power_used,
+            int(45) + Rational::new(15, 780) * int(30)
+        );
+    }
+}
@@ -0,0 +1,931 @@
+use std::fmt::Write;
+
+use bevy::{
+    prelude::*,
+    tasks::{IoTaskPool, Task, block_on, poll_once},
+    utils::HashMap,
+};
+use serde::Deserialize;
+
+use crate::{
+    ControlPanelUi, CreateBaseUi, MainMenuItem, MainMenuUI, MenuName,
+    boundary_boxes::{BoundaryBox, SinkKind},
+    box_links::{BoxLink, link_graph_dot},
+    canvas_svg::canvas_to_svg,
+    drag_plane::{DragBox, DragBoxPlane, PlaneScroll},
+    factory_world::*,
+    keymap::AppAction,
+    link_flow::LinkFlow,
+    rational::Rational,
+    recipe_ui::RecipeBox,
+    solver::{ByproductPolicy, LastSolution, PlanTotals, ProductionSolution, plan_totals},
+};
+
+pub fn build(app: &mut App) {
+    app.add_systems(Startup, create_export_ui.after(CreateBaseUi));
+    app.add_systems(Update, (start_export_dialogue, finish_export_dialogue));
+}
+
+/// The boxes of a production plan with their multiplied rates, ready to be written out.
+pub struct Plan {
+    pub boxes: Vec<PlanBox>,
+    /// Per minute rates brought in by source boxes.
+    pub sources: Vec<(ResourceId, Rational)>,
+    /// Per minute rates taken out by output boxes, the outputs the plan is meant to have.
+    pub sinks: Vec<(ResourceId, Rational)>,
+    /// Where what's left over of a resource goes, for resources with an AWESOME sink or storage box.
+    pub surplus_sinks: Vec<(ResourceId, SinkKind)>,
+}
+
+pub struct PlanBox {
+    pub recipe_name: String,
+    pub building: Option<String>,
+    pub multiplier: Rational,
+    /// Per minute rates with the multiplier applied, all positive.
+    pub inputs: Vec<(ResourceId, Rational)>,
+    pub outputs: Vec<(ResourceId, Rational)>,
+}
+
+impl PlanBox {
+    /// Whole buildings needed to run the box.
+    pub fn building_count(&self) -> i128 {
+        self.multiplier.ceil()
+    }
+
+    /// The clock speed each building runs at, where one is 100%.
+    pub fn clock(&self) -> Rational {
+        match self.building_count() {
+            0 => Rational::ZERO,
+            count => self.multiplier / Rational::from_integer(count),
+        }
+    }
+}
+
+impl Plan {
+    pub fn from_recipe_boxes<'a>(
+        recipe_boxes: impl IntoIterator<Item = &'a RecipeBox>,
+        world: &FactoryWorld,
+    ) -> Plan {
+        let mut boxes: Vec<PlanBox> = recipe_boxes
+            .into_iter()
+            .filter_map(|recipe_box| {
+                let recipe = world.get_recipe(recipe_box.recipe_id).ok()?;
+                let multiplied = |side: &[(ResourceId, Rational)]| {
+                    side.iter()
+                        .map(|&(resource_id, rate)| (resource_id, rate * recipe_box.multiplier))
+                        .collect()
+                };
+
+                Some(PlanBox {
+                    recipe_name: recipe.name.clone(),
+                    building: recipe.building.clone(),
+                    multiplier: recipe_box.multiplier,
+                    inputs: multiplied(&recipe.ingredients),
+                    outputs: multiplied(&recipe.products),
+                })
+            })
+            .collect();
+
+        boxes.sort_by(|a, b| a.recipe_name.cmp(&b.recipe_name));
+
+        Plan {
+            boxes,
+            sources: Vec::new(),
+            sinks: Vec::new(),
+            surplus_sinks: Vec::new(),
+        }
+    }
+
+    /// The plan a solution describes, without applying it to the canvas.
+    ///
+    /// The raw inputs are its sources and the targets its outputs. Surpluses go to AWESOME sinks
+    /// or storage by their policy.
+    pub fn from_solution(solution: &ProductionSolution, world: &FactoryWorld) -> Plan {
+        let recipe_boxes: Vec<RecipeBox> = solution
+            .multipliers
+            .iter()
+            .map(|&(recipe_id, multiplier)| RecipeBox {
+                recipe_id,
+                multiplier,
+            })
+            .collect();
+
+        let mut plan = Plan::from_recipe_boxes(&recipe_boxes, world);
+        plan.sources = solution.raw_inputs.clone();
+        plan.sources
+            .sort_by_key(|&(resource_id, _)| world.get_resource_name(resource_id));
+
+        let surplus = |resource_id: ResourceId| {
+            solution
+                .surpluses
+                .iter()
+                .filter(|&&(id, ..)| id == resource_id)
+                .map(|&(_, rate, _)| rate)
+                .sum::<Rational>()
+        };
+
+        // whatever isn't left over of a target is what the plan makes of it
+        plan.sinks = plan
+            .net_rates(world)
+            .into_iter()
+            .filter(|(resource_id, _)| solution.sensitivity.targets.contains(resource_id))
+            .map(|(resource_id, rate)| (resource_id, rate - surplus(resource_id)))
+            .filter(|(_, rate)| rate.is_positive())
+            .collect();
+
+        plan.surplus_sinks = solution
+            .surpluses
+            .iter()
+            .filter_map(|&(resource_id, _, policy)| match policy {
+                ByproductPolicy::Sink => Some((resource_id, SinkKind::AwesomeSink)),
+                ByproductPolicy::Accumulate => Some((resource_id, SinkKind::Storage)),
+                ByproductPolicy::Consume => None,
+            })
+            .collect();
+        plan.surplus_sinks
+            .sort_by_key(|&(resource_id, _)| world.get_resource_name(resource_id));
+
+        plan
+    }
+
+    /// Adds the rates of source and output boxes to the plan, summed by resource.
+    ///
+    /// AWESOME sink and storage boxes don't take a rate, they only say where leftovers go.
+    pub fn with_boundaries<'a>(
+        mut self,
+        boundary_boxes: impl IntoIterator<Item = &'a BoundaryBox>,
+        world: &FactoryWorld,
+    ) -> Plan {
+        let mut sources: HashMap<ResourceId, Rational> = HashMap::default();
+        let mut sinks: HashMap<ResourceId, Rational> = HashMap::default();
+
+        for boundary_box in boundary_boxes {
+            if let Some(sink_kind) = boundary_box.surplus_sink() {
+                if !self
+                    .surplus_sinks
+                    .iter()
+                    .any(|&(resource_id, _)| resource_id == boundary_box.resource_id)
+                {
+                    self.surplus_sinks
+                        .push((boundary_box.resource_id, sink_kind));
+                }
+
+                continue;
+            }
+
+            let side = if boundary_box.is_source() {
+                &mut sources
+            } else {
+                &mut sinks
+            };
+
+            *side.entry(boundary_box.resource_id).or_default() += boundary_box.actual_rate();
+        }
+
+        for (side, rates) in [(&mut self.sources, sources), (&mut self.sinks, sinks)] {
+            *side = rates.into_iter().collect();
+            side.sort_by_key(|&(resource_id, _)| world.get_resource_name(resource_id));
+        }
+
+        self.surplus_sinks
+            .sort_by_key(|&(resource_id, _)| world.get_resource_name(resource_id));
+
+        self
+    }
+
+    /// Produced minus consumed per minute for every resource in the plan, sorted by name.
+    ///
+    /// Sources count as produced and sinks as consumed.
+    pub fn net_rates(&self, world: &FactoryWorld) -> Vec<(ResourceId, Rational)> {
+        let mut net_rates: HashMap<ResourceId, Rational> = HashMap::default();
+
+        for &(resource_id, rate) in self.sources.iter() {
+            *net_rates.entry(resource_id).or_default() += rate;
+        }
+
+        for &(resource_id, rate) in self.sinks.iter() {
+            *net_rates.entry(resource_id).or_default() -= rate;
+        }
+
+        for plan_box in self.boxes.iter() {
+            for &(resource_id, rate) in plan_box.inputs.iter() {
+                *net_rates.entry(resource_id).or_default() -= rate;
+            }
+
+            for &(resource_id, rate) in plan_box.outputs.iter() {
+                *net_rates.entry(resource_id).or_default() += rate;
+            }
+        }
+
+        let mut net_rates: Vec<_> = net_rates.into_iter().collect();
+        net_rates.sort_by_key(|&(resource_id, _)| world.get_resource_name(resource_id));
+        net_rates
+    }
+
+    /// Whole buildings needed for the boxes.
+    pub fn building_count(&self) -> i128 {
+        self.boxes.iter().map(PlanBox::building_count).sum()
+    }
+
+    /// Per minute left over of the resources that go into AWESOME sink boxes.
+    pub fn sunk(&self, world: &FactoryWorld) -> Rational {
+        self.net_rates(world)
+            .into_iter()
+            .filter(|&(resource_id, rate)| {
+                rate.is_positive()
+                    && self
+                        .surplus_sinks
+                        .contains(&(resource_id, SinkKind::AwesomeSink))
+            })
+            .map(|(_, rate)| rate)
+            .sum()
+    }
+
+    /// Buildings and power of the boxes and the AWESOME sinks taking what's left over,
+    /// counted the same way as for a solution.
+    pub fn totals(&self, world: &FactoryWorld) -> PlanTotals {
+        plan_totals(
+            self.boxes
+                .iter()
+                .map(|plan_box| (plan_box.multiplier, megawatts(&plan_box.inputs, world))),
+            self.sunk(world),
+        )
+    }
+
+    /// Power made by the boxes in MW.
+    pub fn power_made(&self, world: &FactoryWorld) -> Rational {
+        self.boxes
+            .iter()
+            .map(|plan_box| megawatts(&plan_box.outputs, world))
+            .sum()
+    }
+
+    /// One row per box, with a column for each resource used and another for each made.
+    ///
+    /// Inputs and outputs are kept apart so a resource a box both uses and makes still shows up.
+    pub fn to_csv(&self, world: &FactoryWorld) -> String {
+        // sinks use what goes into them and sources make what they bring in
+        let used: Vec<ResourceId> = self
+            .boxes
+            .iter()
+            .flat_map(|plan_box| plan_box.inputs.iter())
+            .chain(self.sinks.iter())
+            .map(|&(resource_id, _)| resource_id)
+            .collect();
+        let made: Vec<ResourceId> = self
+            .boxes
+            .iter()
+            .flat_map(|plan_box| plan_box.outputs.iter())
+            .chain(self.sources.iter())
+            .map(|&(resource_id, _)| resource_id)
+            .collect();
+
+        // each resource gets an input column if anything uses it,
+        // then an output column if anything makes it
+        let mut columns: Vec<(ResourceId, bool)> = Vec::new();
+
+        for (resource_id, _) in self.net_rates(world) {
+            if used.contains(&resource_id) {
+                columns.push((resource_id, false));
+            }
+
+            if made.contains(&resource_id) {
+                columns.push((resource_id, true));
+            }
+        }
+
+        let mut csv = String::new();
+
+        let header = ["Recipe", "Building", "Count", "Clock %"]
+            .into_iter()
+            .map(str::to_string)
+            .chain(columns.iter().map(|&(resource_id, output)| {
+                format!(
+                    "{} {} / min",
+                    resource_name(world, resource_id),
+                    if output { "out" } else { "in" }
+                )
+            }));
+        write_csv_row(&mut csv, header);
+
+        let rate_fields = |inputs: &[(ResourceId, Rational)],
+                           outputs: &[(ResourceId, Rational)]| {
+            columns
+                .iter()
+                .map(|&(resource_id, output)| {
+                    let rate = side_rate(if output { outputs } else { inputs }, resource_id);
+
+                    if rate.is_zero() {
+                        String::new()
+                    } else {
+                        decimal(rate)
+                    }
+                })
+                .collect::<Vec<String>>()
+        };
+
+        for plan_box in self.boxes.iter() {
+            let fields = [
+                plan_box.recipe_name.clone(),
+                plan_box.building.clone().unwrap_or_default(),
+                plan_box.building_count().to_string(),
+                decimal(plan_box.clock() * Rational::from_integer(100)),
+            ]
+            .into_iter()
+            .chain(rate_fields(&plan_box.inputs, &plan_box.outputs));
+            write_csv_row(&mut csv, fields);
+        }
+
+        // one row each for the sources and sinks, in the column of their resource
+        let boundaries = self
+            .sources
+            .iter()
+            .map(|rate| ("Source", &[][..], std::slice::from_ref(rate)))
+            .chain(
+                self.sinks
+                    .iter()
+                    .map(|rate| ("Sink", std::slice::from_ref(rate), &[][..])),
+            );
+
+        for (name, inputs, outputs) in boundaries {
+            let fields = [
+                name.to_string(),
+                String::new(),
+                String::new(),
+                String::new(),
+            ]
+            .into_iter()
+            .chain(rate_fields(inputs, outputs));
+            write_csv_row(&mut csv, fields);
+        }
+
+        csv
+    }
+
+    /// A report with the totals, sources, raw inputs, outputs, power and a table of the boxes.
+    pub fn to_markdown(&self, world: &FactoryWorld) -> String {
+        let net_rates = self.net_rates(world);
+        let totals = self.totals(world);
+        let is_power =
+            |resource_id: ResourceId| world.get_resource_name(resource_id) == Some(POWER_RESOURCE);
+
+        let mut markdown = String::from("# Production Plan\n\n## Summary\n\n");
+
+        writeln!(markdown, "- Boxes: {}", self.boxes.len()).unwrap();
+        writeln!(markdown, "- Buildings: {}", self.building_count()).unwrap();
+
+        if totals.sink_count.is_positive() {
+            writeln!(markdown, "- AWESOME sinks: {}", totals.sink_count).unwrap();
+        }
+
+        writeln!(
+            markdown,
+            "- Power: {} MW used, {} MW made",
+            totals.power_used,
+            self.power_made(world)
+        )
+        .unwrap();
+
+        let surplus_sink = |resource_id: ResourceId| {
+            self.surplus_sinks
+                .iter()
+                .find(|&&(id, _)| id == resource_id)
+                .map(|&(_, sink_kind)| sink_kind)
+        };
+
+        let mut write_rate_table = |title: &str, rates: &[(ResourceId, Rational)]| {
+            write!(
+                markdown,
+                "\n## {}\n\n| Resource | Per Minute |\n| --- | ---: |\n",
+                title
+            )
+            .unwrap();
+
+            for &(resource_id, rate) in rates.iter() {
+                if !rate.is_zero() && !is_power(resource_id) {
+                    // leftovers say where they go, when a box takes them
+                    let destination = match surplus_sink(resource_id) {
+                        Some(sink_kind) if rate.is_positive() => {
+                            format!(" (into {})", sink_kind.name())
+                        }
+                        _ => String::new(),
+                    };
+
+                    writeln!(
+                        markdown,
+                        "| {}{} | {} |",
+                        escape_markdown(resource_name(world, resource_id)),
+                        destination,
+                        rate.abs()
+                    )
+                    .unwrap();
+                }
+            }
+        };
+
+        // resources the plan uses more of than it makes have to come from outside it
+        let (raw_inputs, leftovers): (Vec<_>, Vec<_>) = net_rates
+            .iter()
+            .copied()
+            .partition(|(_, rate)| rate.is_negative());
+
+        if !self.sources.is_empty() {
+            write_rate_table("Sources", &self.sources);
+        }
+
+        write_rate_table("Raw Inputs", &raw_inputs);
+
+        // with sinks the outputs are what goes into output boxes, anything else is left over
+        if self.sinks.is_empty() && self.surplus_sinks.is_empty() {
+            write_rate_table("Outputs", &leftovers);
+        } else {
+            write_rate_table("Outputs", &self.sinks);
+            write_rate_table("Leftovers", &leftovers);
+        }
+
+        markdown.push_str(
+            "\n## Boxes\n\n| Recipe | Building | Count | Clock | Inputs / min | Outputs / min |\n\
+             | --- | --- | ---: | ---: | --- | --- |\n",
+        );
+
+        let list_rates = |side: &[(ResourceId, Rational)]| {
+            side.iter()
+                .map(|&(resource_id, rate)| {
+                    format!(
+                        "{} {}",
+                        rate,
+                        escape_markdown(resource_name(world, resource_id))
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+
+        for plan_box in self.boxes.iter() {
+            writeln!(
+                markdown,
+                "| {} | {} | {} | {}% | {} | {} |",
+                escape_markdown(&plan_box.recipe_name),
+                escape_markdown(plan_box.building.as_deref().unwrap_or("")),
+                plan_box.building_count(),
+                decimal(plan_box.clock() * Rational::from_integer(100)),
+                list_rates(&plan_box.inputs),
+                list_rates(&plan_box.outputs),
+            )
+            .unwrap();
+        }
+
+        markdown
+    }
+}
+
+/// The power in a list of rates, in MW.
+fn megawatts(side: &[(ResourceId, Rational)], world: &FactoryWorld) -> Rational {
+    side.iter()
+        .filter(|&&(resource_id, _)| world.get_resource_name(resource_id) == Some(POWER_RESOURCE))
+        .map(|&(_, rate)| rate / Rational::from_integer(60))
+        .sum()
+}
+
+fn resource_name(world: &FactoryWorld, resource_id: ResourceId) -> &str {
+    world
+        .get_resource_name(resource_id)
+        .expect("Resource name should exist")
+}
+
+fn side_rate(side: &[(ResourceId, Rational)], resource_id: ResourceId) -> Rational {
+    side.iter()
+        .filter(|&&(other_id, _)| other_id == resource_id)
+        .map(|&(_, rate)| rate)
+        .sum()
+}
+
+/// Rounds to 4 decimal places, for spreadsheets that don't understand fractions
+/// and values whose fractions would be unreadable.
+pub fn decimal(value: Rational) -> String {
+    let rounded = format!("{:.4}", value.to_f64());
+    let trimmed = rounded.trim_end_matches('0').trim_end_matches('.');
+
+    match trimmed {
+        "-0" => "0".into(),
+        trimmed => trimmed.into(),
+    }
+}
+
+fn write_csv_row(csv: &mut String, fields: impl IntoIterator<Item = String>) {
+    let fields: Vec<String> = fields
+        .into_iter()
+        .map(|field| {
+            if field.contains([',', '"', '\n']) {
+                format!("\"{}\"", field.replace('"', "\"\""))
+            } else {
+                field
+            }
+        })
+        .collect();
+
+    csv.push_str(&fields.join(","));
+    csv.push('\n');
+}
+
+fn escape_markdown(text: &str) -> String {
+    text.replace('|', "\\|")
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Csv,
+    Markdown,
+    /// The last solution as CSV, whether or not it was applied to the canvas.
+    SolutionCsv,
+    /// The last solution as Markdown, whether or not it was applied to the canvas.
+    SolutionMarkdown,
+    /// A picture of the whole plane rather than a plan.
+    Svg,
+    /// Every loaded recipe with the resources it uses and makes, as Graphviz DOT.
+    RecipeGraph,
+    /// The boxes on the plane and the links between them, as Graphviz DOT.
+    LinkGraph,
+}
+
+impl ExportFormat {
+    pub const ALL: [ExportFormat; 7] = [
+        ExportFormat::Csv,
+        ExportFormat::Markdown,
+        ExportFormat::SolutionCsv,
+        ExportFormat::SolutionMarkdown,
+        ExportFormat::Svg,
+        ExportFormat::RecipeGraph,
+        ExportFormat::LinkGraph,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "CSV",
+            ExportFormat::Markdown => "Markdown",
+            ExportFormat::SolutionCsv => "Solution CSV",
+            ExportFormat::SolutionMarkdown => "Solution Markdown",
+            ExportFormat::Svg => "SVG",
+            ExportFormat::RecipeGraph => "Recipe Graph",
+            ExportFormat::LinkGraph => "Link Graph",
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Csv | ExportFormat::SolutionCsv => "csv",
+            ExportFormat::Markdown | ExportFormat::SolutionMarkdown => "md",
+            ExportFormat::Svg => "svg",
+            ExportFormat::RecipeGraph | ExportFormat::LinkGraph => "dot",
+        }
+    }
+
+    fn file_name(self) -> String {
+        match self {
+            ExportFormat::Svg => "canvas.svg".into(),
+            ExportFormat::RecipeGraph => "recipes.dot".into(),
+            ExportFormat::LinkGraph => "boxes.dot".into(),
+            ExportFormat::SolutionCsv => "solution.csv".into(),
+            ExportFormat::SolutionMarkdown => "solution.md".into(),
+            format => format!("plan.{}", format.extension()),
+        }
+    }
+}
+
+#[derive(Resource)]
+struct ExportUi {
+    status_text_entity: Entity,
+}
+
+#[derive(Component)]
+#[require(Button)]
+struct ExportButton {
+    format: ExportFormat,
+}
+
+fn create_export_ui(
+    mut commands: Commands,
+    main_menu: Res<MainMenuUI>,
+    control_panel: Res<ControlPanelUi>,
+) {
+    let sub_menu_entity = commands
+        .spawn((Node {
+            display: Display::None,
+            flex_direction: FlexDirection::Column,
+            ..default()
+        },))
+        .set_parent(control_panel.control_panel_entity)
+        .id();
+
+    commands
+        .spawn((
+            MainMenuItem {
+                sub_menu_entity,
+                menu: MenuName::Export,
+            },
+            Text::new("Export"),
+            Node {
+                width: Val::Percent(100.0),
+                ..default()
+            },
+        ))
+        .set_parent(main_menu.main_menu_entity);
+
+    for format in ExportFormat::ALL {
+        commands
+            .spawn((
+                ExportButton { format },
+                Text::new(format!("Export {}", format.name())),
+            ))
+            .set_parent(sub_menu_entity);
+    }
+
+    let status_text_entity = commands
+        .spawn((
+            Text::default(),
+            Node {
+                max_width: Val::Px(300.),
+                ..default()
+            },
+        ))
+        .set_parent(sub_menu_entity)
+        .id();
+
+    commands.insert_resource(ExportUi { status_text_entity });
+}
+
+#[derive(Component)]
+struct ExportTask {
+    format: ExportFormat,
+    task: Task<std::io::Result<bool>>,
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn start_export_dialogue(
+    mut commands: Commands,
+    button_q: Query<(&Interaction, &ExportButton), Changed<Interaction>>,
+    mut actions: EventReader<AppAction>,
+    recipe_box_q: Query<(Entity, &RecipeBox)>,
+    boundary_box_q: Query<&BoundaryBox>,
+    link_graph_q: Query<(&BoxLink, Option<&LinkFlow>)>,
+    world: Res<FactoryWorld>,
+    plane_q: Query<(&ComputedNode, &GlobalTransform, &BackgroundColor), With<DragBoxPlane>>,
+    box_q: Query<(Entity, &Node), With<DragBox>>,
+    element_q: Query<(
+        &ComputedNode,
+        &GlobalTransform,
+        Option<&BackgroundColor>,
+        Option<&Children>,
+    )>,
+    mut text_params: ParamSet<(
+        Query<(&Text, &TextFont, &TextColor, &TextLayout)>,
+        Query<&mut Text>,
+    )>,
+    link_q: Query<(&ComputedNode, &GlobalTransform, &BackgroundColor, &Node), With<BoxLink>>,
+    plane_scroll: Res<PlaneScroll>,
+    last_solution: Res<LastSolution>,
+    export_ui: Res<ExportUi>,
+) {
+    let pressed_formats = button_q
+        .iter()
+        .filter(|(interaction, _)| **interaction == Interaction::Pressed)
+        .map(|(_, button)| button.format);
+
+    let action_formats: Vec<ExportFormat> = actions
+        .read()
+        .filter_map(|action| match action {
+            AppAction::Export(format) => Some(*format),
+            _ => None,
+        })
+        .collect();
+
+    for format in pressed_formats.chain(action_formats) {
+        let plan = match format {
+            ExportFormat::SolutionCsv | ExportFormat::SolutionMarkdown => {
+                let Some(solution) = &last_solution.0 else {
+                    text_params
+                        .p1()
+                        .get_mut(export_ui.status_text_entity)
+                        .expect("Should be a text")
+                        .0 = "Solve something first".into();
+                    continue;
+                };
+
+                Plan::from_solution(solution, world.as_ref())
+            }
+            _ => Plan::from_recipe_boxes(
+                recipe_box_q.iter().map(|(_, recipe_box)| recipe_box),
+                world.as_ref(),
+            )
+            .with_boundaries(boundary_box_q.iter(), world.as_ref()),
+        };
+
+        let contents = match format {
+            ExportFormat::Csv | ExportFormat::SolutionCsv => plan.to_csv(world.as_ref()),
+            ExportFormat::Markdown | ExportFormat::SolutionMarkdown => {
+                plan.to_markdown(world.as_ref())
+            }
+            ExportFormat::Svg => canvas_to_svg(
+                &plane_q,
+                &box_q,
+                &element_q,
+                &text_params.p0(),
+                &link_q,
+                plane_scroll.as_ref(),
+            ),
+            ExportFormat::RecipeGraph => world.recipe_graph_dot(),
+            ExportFormat::LinkGraph => link_graph_dot(&recipe_box_q, &link_graph_q, world.as_ref()),
+        };
+
+        commands.spawn(ExportTask {
+            format,
+            task: IoTaskPool::get().spawn(export_async(format, contents)),
+        });
+    }
+}
+
+/// Returns `false` if no file was picked.
+async fn export_async(format: ExportFormat, contents: String) -> std::io::Result<bool> {
+    let Some(file_handle) = rfd::AsyncFileDialog::new()
+        .add_filter(format.name(), &[format.extension()])
+        .set_file_name(format.file_name())
+        .save_file()
+        .await
+    else {
+        return Ok(false);
+    };
+
+    file_handle.write(contents.as_bytes()).await?;
+
+    Ok(true)
+}
+
+fn finish_export_dialogue(
+    mut commands: Commands,
+    mut task_q: Query<(Entity, &mut ExportTask)>,
+    export_ui: Res<ExportUi>,
+    mut text_q: Query<&mut Text>,
+) {
+    for (task_entity, mut task) in task_q.iter_mut() {
+        if task.task.is_finished() {
+            let Some(task_result) = block_on(poll_once(&mut task.task)) else {
+                error!("Expected task to be finished");
+                continue;
+            };
+
+            commands.entity(task_entity).despawn_recursive();
+
+            let mut status_text = text_q
+                .get_mut(export_ui.status_text_entity)
+                .expect("Should be a text");
+
+            match task_result {
+                Ok(true) => status_text.0 = format!("Exported {}", task.format.name()),
+                Ok(false) => {}
+                Err(err) => {
+                    error!("Couldn't export {}: {}", task.format.name(), err);
+                    status_text.0 = format!("Couldn't export {}", task.format.name());
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        boundary_boxes::BoundaryKind,
+        recipe_json::{LoadOptions, load_recipes},
+        solver::{
+            ByproductPolicies, ObjectiveMetric, ObjectiveWeights, ProductionProblem,
+            solve_production,
+        },
+    };
+
+    #[test]
+    fn csv_keeps_catalysts_on_both_sides() {
+        let mut world = FactoryWorld::default();
+        let water = world.get_resource_id("Water");
+        let catalyst = world.get_resource_id("Catalyst");
+        let fuel = world.get_resource_id("Fuel");
+
+        let plan = Plan {
+            boxes: vec![PlanBox {
+                recipe_name: "Catalytic Fuel".into(),
+                building: None,
+                multiplier: Rational::new(3, 2),
+                inputs: vec![
+                    (water, Rational::from_integer(30)),
+                    (catalyst, Rational::from_integer(10)),
+                ],
+                outputs: vec![
+                    (fuel, Rational::from_integer(15)),
+                    (catalyst, Rational::from_integer(10)),
+                ],
+            }],
+            sources: vec![(water, Rational::from_integer(30))],
+            sinks: vec![(fuel, Rational::from_integer(15))],
+            surplus_sinks: Vec::new(),
+        };
+
+        assert_eq!(
+            plan.to_csv(&world),
+            "Recipe,Building,Count,Clock %,Catalyst in / min,Catalyst out / min,\
+             Fuel in / min,Fuel out / min,Water in / min,Water out / min\n\
+             Catalytic Fuel,,2,75,10,10,,15,30,\n\
+             Source,,,,,,,,,30\n\
+             Sink,,,,,,15,,,\n"
+        );
+    }
+
+    #[test]
+    fn surplus_sinks_are_leftovers_not_outputs() {
+        let mut world = FactoryWorld::default();
+        let oil = world.get_resource_id("Oil");
+        let fuel = world.get_resource_id("Fuel");
+        let residue = world.get_resource_id("Residue");
+        let int = Rational::from_integer;
+
+        let plan = Plan {
+            boxes: vec![PlanBox {
+                recipe_name: "Refine".into(),
+                building: None,
+                multiplier: int(1),
+                inputs: vec![(oil, int(30))],
+                outputs: vec![(fuel, int(20)), (residue, int(10))],
+            }],
+            sources: Vec::new(),
+            sinks: Vec::new(),
+            surplus_sinks: Vec::new(),
+        }
+        .with_boundaries(
+            &[
+                BoundaryBox {
+                    kind: BoundaryKind::Sink(SinkKind::Output),
+                    resource_id: fuel,
+                    rate: int(20),
+                    uses_purity: false,
+                },
+                BoundaryBox {
+                    kind: BoundaryKind::Sink(SinkKind::AwesomeSink),
+                    resource_id: residue,
+                    rate: int(60),
+                    uses_purity: false,
+                },
+            ],
+            &world,
+        );
+
+        assert_eq!(plan.sinks, vec![(fuel, int(20))]);
+        assert_eq!(plan.surplus_sinks, vec![(residue, SinkKind::AwesomeSink)]);
+
+        let markdown = plan.to_markdown(&world);
+        let outputs =
+            &markdown[markdown.find("## Outputs").unwrap()..markdown.find("## Leftovers").unwrap()];
+        let leftovers =
+            &markdown[markdown.find("## Leftovers").unwrap()..markdown.find("## Boxes").unwrap()];
+
+        assert!(outputs.contains("| Fuel | 20 |"));
+        assert!(!outputs.contains("Residue"));
+        assert!(leftovers.contains("| Residue (into AWESOME Sink) | 10 |"));
+    }
+
+    #[test]
+    fn solutions_export_without_being_applied() {
+        let mut world = FactoryWorld::default();
+        load_recipes(
+            &mut world,
+            r#"{"recipes": [{"name": "Refine", "rates": [["Oil", -30], ["Fuel", 20], ["Residue", 10]]}]}"#,
+            LoadOptions::default(),
+        )
+        .unwrap();
+
+        let oil = world.get_resource_id("Oil");
+        let fuel = world.get_resource_id("Fuel");
+        let residue = world.get_resource_id("Residue");
+        let int = Rational::from_integer;
+
+        let solution = solve_production(
+            &ProductionProblem {
+                targets: std::collections::HashMap::from([(fuel, int(20))]),
+                raw_limits: std::collections::HashMap::new(),
+            },
+            &ByproductPolicies::default(),
+            &ObjectiveWeights::only(ObjectiveMetric::Buildings),
+            &world,
+        )
+        .unwrap();
+
+        let plan = Plan::from_solution(&solution, &world);
+
+        assert_eq!(plan.boxes.len(), 1);
+        assert_eq!(plan.sources, vec![(oil, int(30))]);
+        assert_eq!(plan.sinks, vec![(fuel, int(20))]);
+        assert_eq!(plan.surplus_sinks, vec![(residue, SinkKind::Storage)]);
+        assert!(
+            plan.to_markdown(&world)
+                .contains("| Residue (into Storage) | 10 |")
+        );
+    }
+}
@@ -0,0 +1,602 @@
+use std::{
+    cmp::Ordering,
+    fmt,
+    iter::Sum,
+    ops::{Add, AddAssign, Div, Mul, MulAssign, Neg, Sub, SubAssign},
+    str::FromStr,
+};
+
+use serde::{Deserialize, Serialize, de};
+
+/// An exact fraction, always stored in lowest terms with a positive denominator.
+///
+/// Rates in Satisfactory are things like 37.5/min or 2/3 of a machine,
+/// which floats can't add up exactly.
+///
+/// The operators panic if the result doesn't fit, in release builds too. Code that can build up
+/// large denominators, like the simplex, uses the `checked_` methods and reports an error instead.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Rational {
+    numerator: i128,
+    denominator: i128,
+}
+
+fn gcd(mut a: i128, mut b: i128) -> i128 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+
+    a.abs()
+}
+
+impl Rational {
+    pub const ZERO: Rational = Rational {
+        numerator: 0,
+        denominator: 1,
+    };
+
+    pub const ONE: Rational = Rational {
+        numerator: 1,
+        denominator: 1,
+    };
+
+    /// panics if the denominator is zero
+    pub fn new(numerator: i128, denominator: i128) -> Self {
+        assert!(denominator != 0, "Rational with a zero denominator");
+
+        let divisor = gcd(numerator, denominator) * denominator.signum();
+
+        Rational {
+            numerator: numerator / divisor,
+            denominator: denominator / divisor,
+        }
+    }
+
+    pub const fn from_integer(integer: i128) -> Self {
+        Rational {
+            numerator: integer,
+            denominator: 1,
+        }
+    }
+
+    /// Finds the simplest fraction within a tiny distance of a float,
+    /// so that values like `37.5` or `0.1` come out exact.
+    pub fn from_f64(value: f64) -> Option<Self> {
+        const MAX_DENOMINATOR: i128 = 1_000_000;
+
+        if !value.is_finite() || value.abs() > 1e15 {
+            return None;
+        }
+
+        // continued fraction expansion, stopping once close enough
+        let (mut previous_numerator, mut numerator) = (0i128, 1i128);
+        let (mut previous_denominator, mut denominator) = (1i128, 0i128);
+        let mut remainder = value;
+
+        loop {
+            let whole = remainder.floor();
+            let next_numerator = whole as i128 * numerator + previous_numerator;
+            let next_denominator = whole as i128 * denominator + previous_denominator;
+
+            if next_denominator > MAX_DENOMINATOR {
+                break;
+            }
+
+            (previous_numerator, numerator) = (numerator, next_numerator);
+            (previous_denominator, denominator) = (denominator, next_denominator);
+
+            let fraction = remainder - whole;
+
+            if (numerator as f64 / denominator as f64 - value).abs() <= 1e-9 * value.abs().max(1.)
+                || fraction < 1e-12
+            {
+                break;
+            }
+
+            remainder = 1. / fraction;
+        }
+
+        Some(Rational::new(numerator, denominator))
+    }
+
+    pub fn numerator(self) -> i128 {
+        self.numerator
+    }
+
+    pub fn denominator(self) -> i128 {
+        self.denominator
+    }
+
+    pub fn is_zero(self) -> bool {
+        self.numerator == 0
+    }
+
+    pub fn is_negative(self) -> bool {
+        self.numerator < 0
+    }
+
+    pub fn is_positive(self) -> bool {
+        self.numerator > 0
+    }
+
+    pub fn is_integer(self) -> bool {
+        self.denominator == 1
+    }
+
+    pub fn abs(self) -> Self {
+        Rational {
+            numerator: self.numerator.abs(),
+            denominator: self.denominator,
+        }
+    }
+
+    /// The smallest integer not less than this.
+    pub fn ceil(self) -> i128 {
+        -((-self.numerator).div_euclid(self.denominator))
+    }
+
+    pub fn recip(self) -> Self {
+        Rational::new(self.denominator, self.numerator)
+    }
+
+    /// `None` if the result doesn't fit.
+    pub fn checked_add(self, rhs: Rational) -> Option<Rational> {
+        let divisor = gcd(self.denominator, rhs.denominator);
+
+        let numerator = self
+            .numerator
+            .checked_mul(rhs.denominator / divisor)?
+            .checked_add(rhs.numerator.checked_mul(self.denominator / divisor)?)?;
+        let denominator = (self.denominator / divisor).checked_mul(rhs.denominator)?;
+
+        Some(Rational::new(numerator, denominator))
+    }
+
+    /// `None` if the result doesn't fit.
+    pub fn checked_sub(self, rhs: Rational) -> Option<Rational> {
+        self.checked_add(rhs.checked_neg()?)
+    }
+
+    /// `None` if the result doesn't fit.
+    pub fn checked_mul(self, rhs: Rational) -> Option<Rational> {
+        // cross cancel first to keep the intermediate values small
+        let a = gcd(self.numerator, rhs.denominator).max(1);
+        let b = gcd(rhs.numerator, self.denominator).max(1);
+
+        Some(Rational::new(
+            (self.numerator / a).checked_mul(rhs.numerator / b)?,
+            (self.denominator / b).checked_mul(rhs.denominator / a)?,
+        ))
+    }
+
+    /// `None` if `rhs` is zero or the result doesn't fit.
+    pub fn checked_div(self, rhs: Rational) -> Option<Rational> {
+        if rhs.is_zero() {
+            return None;
+        }
+
+        self.checked_mul(Rational::new(rhs.denominator, rhs.numerator))
+    }
+
+    /// `None` if the result doesn't fit.
+    pub fn checked_neg(self) -> Option<Rational> {
+        Some(Rational {
+            numerator: self.numerator.checked_neg()?,
+            denominator: self.denominator,
+        })
+    }
+
+    pub fn to_f32(self) -> f32 {
+        self.to_f64() as f32
+    }
+
+    pub fn to_f64(self) -> f64 {
+        self.numerator as f64 / self.denominator as f64
+    }
+}
+
+impl Default for Rational {
+    fn default() -> Self {
+        Rational::ZERO
+    }
+}
+
+impl From<i128> for Rational {
+    fn from(integer: i128) -> Self {
+        Rational::from_integer(integer)
+    }
+}
+
+impl Add for Rational {
+    type Output = Rational;
+
+    fn add(self, rhs: Rational) -> Rational {
+        self.checked_add(rhs).expect("Rational overflow")
+    }
+}
+
+impl Sub for Rational {
+    type Output = Rational;
+
+    fn sub(self, rhs: Rational) -> Rational {
+        self + -rhs
+    }
+}
+
+impl Mul for Rational {
+    type Output = Rational;
+
+    fn mul(self, rhs: Rational) -> Rational {
+        self.checked_mul(rhs).expect("Rational overflow")
+    }
+}
+
+impl Div for Rational {
+    type Output = Rational;
+
+    /// panics when dividing by zero
+    fn div(self, rhs: Rational) -> Rational {
+        self.mul(rhs.recip())
+    }
+}
+
+impl Neg for Rational {
+    type Output = Rational;
+
+    fn neg(self) -> Rational {
+        self.checked_neg().expect("Rational overflow")
+    }
+}
+
+impl AddAssign for Rational {
+    fn add_assign(&mut self, rhs: Rational) {
+        *self = *self + rhs;
+    }
+}
+
+impl SubAssign for Rational {
+    fn sub_assign(&mut self, rhs: Rational) {
+        *self = *self - rhs;
+    }
+}
+
+impl MulAssign for Rational {
+    fn mul_assign(&mut self, rhs: Rational) {
+        *self = *self * rhs;
+    }
+}
+
+impl Sum for Rational {
+    fn sum<I: Iterator<Item = Rational>>(iter: I) -> Rational {
+        iter.fold(Rational::ZERO, Add::add)
+    }
+}
+
+/// Compares `a/b` with `c/d` for positive `b` and `d` without overflowing, by comparing the
+/// whole parts and then the reciprocals of what's left, like a continued fraction.
+fn compare_fractions(a: i128, b: i128, c: i128, d: i128) -> Ordering {
+    let (a_whole, a_rest) = (a.div_euclid(b), a.rem_euclid(b));
+    let (c_whole, c_rest) = (c.div_euclid(d), c.rem_euclid(d));
+
+    match a_whole.cmp(&c_whole) {
+        Ordering::Equal => (),
+        ordering => return ordering,
+    }
+
+    match (a_rest == 0, c_rest == 0) {
+        (true, true) => Ordering::Equal,
+        (true, false) => Ordering::Less,
+        (false, true) => Ordering::Greater,
+        // the bigger remainder has the smaller reciprocal
+        (false, false) => compare_fractions(d, c_rest, b, a_rest),
+    }
+}
+
+impl Ord for Rational {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (
+            self.numerator.checked_mul(other.denominator),
+            other.numerator.checked_mul(self.denominator),
+        ) {
+            (Some(a), Some(b)) => a.cmp(&b),
+            _ => compare_fractions(
+                self.numerator,
+                self.denominator,
+                other.numerator,
+                other.denominator,
+            ),
+        }
+    }
+}
+
+impl PartialOrd for Rational {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Shows whole numbers and short decimals as decimals like `22.5`,
+/// and everything else as a mixed fraction like `2 1/3`.
+impl fmt::Display for Rational {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_integer() {
+            return write!(f, "{}", self.numerator);
+        }
+
+        // a denominator made of only 2s and 5s ends in a short decimal
+        let mut remaining = self.denominator;
+        let mut places = 0;
+
+        while remaining % 10 == 0 || remaining % 2 == 0 || remaining % 5 == 0 {
+            if remaining % 10 == 0 {
+                remaining /= 10;
+            } else if remaining % 2 == 0 {
+                remaining /= 2;
+            } else {
+                remaining /= 5;
+            }
+
+            places += 1;
+        }
+
+        let scale = 10i128.pow(places.min(4));
+
+        let scaled = if remaining == 1 && places <= 4 {
+            self.numerator.checked_mul(scale / self.denominator)
+        } else {
+            None
+        };
+
+        if let Some(scaled) = scaled {
+            let sign = if scaled < 0 { "-" } else { "" };
+            let decimals = format!("{:0width$}", scaled.abs() % scale, width = places as usize);
+
+            return write!(
+                f,
+                "{}{}.{}",
+                sign,
+                scaled.abs() / scale,
+                decimals.trim_end_matches('0')
+            );
+        }
+
+        let sign = if self.is_negative() { "-" } else { "" };
+        let whole = self.numerator.abs() / self.denominator;
+        let fraction = self.numerator.abs() % self.denominator;
+
+        if whole == 0 {
+            write!(f, "{}{}/{}", sign, fraction, self.denominator)
+        } else {
+            write!(f, "{}{} {}/{}", sign, whole, fraction, self.denominator)
+        }
+    }
+}
+
+impl fmt::Debug for Rational {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.numerator, self.denominator)
+    }
+}
+
+#[derive(Debug)]
+pub struct ParseRationalError;
+
+impl fmt::Display for ParseRationalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Invalid number, expected something like \"3\", \"22.5\", \"2/3\" or \"2 1/3\"")
+    }
+}
+
+/// Parses integers, decimals, fractions and mixed fractions.
+impl FromStr for Rational {
+    type Err = ParseRationalError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+
+        let (negative, s) = match s.strip_prefix('-') {
+            Some(rest) => (true, rest.trim_start()),
+            None => (false, s),
+        };
+
+        let parse_integer = |s: &str| s.trim().parse::<i128>().map_err(|_| ParseRationalError);
+
+        let value = if let Some((whole_and_numerator, denominator)) = s.split_once('/') {
+            let denominator = parse_integer(denominator)?;
+
+            if denominator == 0 {
+                return Err(ParseRationalError);
+            }
+
+            match whole_and_numerator.trim().split_once(' ') {
+                Some((whole, numerator)) => {
+                    Rational::from_integer(parse_integer(whole)?)
+                        + Rational::new(parse_integer(numerator)?, denominator)
+                }
+                None => Rational::new(parse_integer(whole_and_numerator)?, denominator),
+            }
+        } else if let Some((whole, decimals)) = s.split_once('.') {
+            let whole = if whole.is_empty() {
+                0
+            } else {
+                parse_integer(whole)?
+            };
+
+            if decimals.is_empty() || !decimals.chars().all(|c| c.is_ascii_digit()) {
+                return Err(ParseRationalError);
+            }
+
+            let places = u32::try_from(decimals.len()).map_err(|_| ParseRationalError)?;
+            let scale = 10i128.checked_pow(places).ok_or(ParseRationalError)?;
+
+            Rational::from_integer(whole) + Rational::new(parse_integer(decimals)?, scale)
+        } else {
+            Rational::from_integer(parse_integer(s)?)
+        };
+
+        Ok(if negative { -value } else { value })
+    }
+}
+
+/// Integers are written as numbers, anything else as a fraction string like `"2/3"`.
+impl Serialize for Rational {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match i64::try_from(self.numerator) {
+            Ok(integer) if self.is_integer() => serializer.serialize_i64(integer),
+            _ => serializer.collect_str(&format_args!("{}/{}", self.numerator, self.denominator)),
+        }
+    }
+}
+
+/// Accepts JSON numbers as well as any string [`FromStr`] understands.
+impl<'de> Deserialize<'de> for Rational {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct RationalVisitor;
+
+        impl de::Visitor<'_> for RationalVisitor {
+            type Value = Rational;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a number or a fraction string")
+            }
+
+            fn visit_i64<E: de::Error>(self, v: i64) -> Result<Rational, E> {
+                Ok(Rational::from_integer(v.into()))
+            }
+
+            fn visit_u64<E: de::Error>(self, v: u64) -> Result<Rational, E> {
+                Ok(Rational::from_integer(v.into()))
+            }
+
+            fn visit_f64<E: de::Error>(self, v: f64) -> Result<Rational, E> {
+                Rational::from_f64(v).ok_or_else(|| E::custom("number out of range"))
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<Rational, E> {
+                v.parse().map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_any(RationalVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> Rational {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn parses_integers_decimals_and_fractions() {
+        assert_eq!(parse("3"), Rational::from_integer(3));
+        assert_eq!(parse(" 22.5 "), Rational::new(45, 2));
+        assert_eq!(parse(".25"), Rational::new(1, 4));
+        assert_eq!(parse("2/3"), Rational::new(2, 3));
+        assert_eq!(parse("2 1/3"), Rational::new(7, 3));
+        assert_eq!(parse("-2 1/3"), Rational::new(-7, 3));
+        assert_eq!(parse("4/6"), Rational::new(2, 3));
+
+        for invalid in ["", "abc", "1/0", "1.", "1.2.3", "1.-5", "1 2"] {
+            assert!(invalid.parse::<Rational>().is_err(), "{:?}", invalid);
+        }
+    }
+
+    #[test]
+    fn displays_decimals_and_mixed_fractions() {
+        assert_eq!(Rational::from_integer(-4).to_string(), "-4");
+        assert_eq!(Rational::new(45, 2).to_string(), "22.5");
+        assert_eq!(Rational::new(-1, 8).to_string(), "-0.125");
+        assert_eq!(Rational::new(1, 3).to_string(), "1/3");
+        assert_eq!(Rational::new(-7, 3).to_string(), "-2 1/3");
+        assert_eq!(Rational::new(30, 11).to_string(), "2 8/11");
+        // too many decimal places to show as a decimal
+        assert_eq!(Rational::new(1, 64).to_string(), "1/64");
+    }
+
+    #[test]
+    fn display_round_trips_through_parsing() {
+        for value in [
+            Rational::new(45, 2),
+            Rational::new(-7, 3),
+            Rational::new(1, 64),
+            Rational::new(-1, 8),
+        ] {
+            assert_eq!(parse(&value.to_string()), value);
+        }
+    }
+
+    #[test]
+    fn from_f64_finds_simple_fractions() {
+        assert_eq!(Rational::from_f64(37.5), Some(Rational::new(75, 2)));
+        assert_eq!(Rational::from_f64(0.1), Some(Rational::new(1, 10)));
+        assert_eq!(Rational::from_f64(-2.0 / 3.0), Some(Rational::new(-2, 3)));
+        assert_eq!(Rational::from_f64(0.0), Some(Rational::ZERO));
+        assert_eq!(Rational::from_f64(f64::NAN), None);
+        assert_eq!(Rational::from_f64(1e20), None);
+    }
+
+    #[test]
+    fn orders_by_value() {
+        let mut values = vec![
+            Rational::new(1, 2),
+            Rational::new(-1, 3),
+            Rational::from_integer(2),
+            Rational::new(2, 3),
+            Rational::ZERO,
+        ];
+        values.sort();
+
+        assert_eq!(
+            values,
+            vec![
+                Rational::new(-1, 3),
+                Rational::ZERO,
+                Rational::new(1, 2),
+                Rational::new(2, 3),
+                Rational::from_integer(2),
+            ]
+        );
+    }
+
+    #[test]
+    fn orders_without_overflowing() {
+        let big = i128::MAX / 3;
+        let a = Rational::new(big, big - 1);
+        let b = Rational::new(big - 1, big - 2);
+
+        // both are just over one, b by a little more
+        assert!(a < b);
+        assert!(a > Rational::ONE);
+        assert!(-a > -b);
+        assert_eq!(a.cmp(&a), Ordering::Equal);
+    }
+
+    #[test]
+    fn checked_arithmetic_reports_overflow() {
+        let big = Rational::from_integer(i128::MAX / 2);
+
+        assert_eq!(big.checked_mul(Rational::from_integer(4)), None);
+        assert_eq!(
+            big.checked_add(big).map(|sum| sum / big),
+            Some(Rational::from_integer(2))
+        );
+        assert_eq!(
+            Rational::new(1, i128::MAX).checked_add(Rational::new(1, i128::MAX - 1)),
+            None
+        );
+        assert_eq!(Rational::ONE.checked_div(Rational::ZERO), None);
+        assert_eq!(
+            Rational::new(2, 3).checked_sub(Rational::new(1, 6)),
+            Some(Rational::new(1, 2))
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Rational overflow")]
+    fn operators_panic_on_overflow() {
+        let _ = Rational::from_integer(i128::MAX) + Rational::ONE;
+    }
+}
@@ -1,6 +1,6 @@
 use serde::Deserialize;
 
-use crate::factory_world::*;
+use crate::{factory_world::*, rational::Rational};
 
 #[derive(Deserialize)]
 pub struct RecipeListJson<'a> {
@@ -8,37 +8,581 @@ pub struct RecipeListJson<'a> {
     pub recipes: Vec<RecipeJson<'a>>,
 }
 
+/// A recipe is given either as per minute `rates` where inputs are negative,
+/// or as `ingredients` and `products`. With a `duration_seconds` the ingredients and products
+/// are the quantities of one cycle, otherwise they are per minute.
+///
+/// Only the second form can have the same resource as both an ingredient and a product.
 #[derive(Deserialize)]
 pub struct RecipeJson<'a> {
     pub name: &'a str,
-    pub rates: Vec<(&'a str, f32)>,
+    #[serde(default)]
+    pub rates: Vec<(&'a str, Rational)>,
+    #[serde(default)]
+    pub duration_seconds: Option<Rational>,
+    #[serde(default)]
+    pub ingredients: Vec<(&'a str, Rational)>,
+    #[serde(default)]
+    pub products: Vec<(&'a str, Rational)>,
+    #[serde(default)]
+    pub building: Option<&'a str>,
+}
+
+impl RecipeJson<'_> {
+    fn has_sides(&self) -> bool {
+        !self.ingredients.is_empty() || !self.products.is_empty()
+    }
+
+    /// Checks the recipe without touching the world, so a recipe that won't be loaded
+    /// doesn't add its resources.
+    fn check(&self) -> Result<(), &'static str> {
+        if !self.rates.is_empty() && (self.has_sides() || self.duration_seconds.is_some()) {
+            return Err("has rates as well as ingredients, products or a duration");
+        }
+
+        if self
+            .ingredients
+            .iter()
+            .chain(self.products.iter())
+            .any(|(_, quantity)| !quantity.is_positive())
+        {
+            return Err("has an ingredient or product that isn't positive");
+        }
+
+        if self
+            .duration_seconds
+            .is_some_and(|duration| !duration.is_positive())
+        {
+            return Err("has a duration that isn't positive");
+        }
+
+        Ok(())
+    }
+
+    /// Resolves resource names and normalises per cycle quantities into rates.
+    fn into_recipe(self, world: &mut FactoryWorld) -> Result<Recipe, &'static str> {
+        self.check()?;
+
+        let has_sides = self.has_sides();
+        let RecipeJson {
+            name,
+            rates,
+            duration_seconds,
+            ingredients,
+            products,
+            building,
+        } = self;
+
+        let recipe = match duration_seconds {
+            None if has_sides => Recipe {
+                name: name.to_string(),
+                ingredients: resolve_rates(world, ingredients),
+                products: resolve_rates(world, products),
+                building: None,
+                cycle_seconds: None,
+            },
+            None => Recipe::from_rates(name.to_string(), resolve_rates(world, rates)),
+            Some(duration) => Recipe::from_cycle(
+                name.to_string(),
+                duration,
+                resolve_rates(world, ingredients),
+                resolve_rates(world, products),
+            ),
+        };
+
+        Ok(Recipe {
+            building: building.map(str::to_string),
+            ..recipe
+        })
+    }
+}
+
+/// What to do when a loaded recipe has the same name as one already in the world.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ConflictPolicy {
+    /// Keep the existing recipe and don't load the new one.
+    #[default]
+    Skip,
+    /// Replace the ingredients and products of the existing recipe, keeping its [`RecipeId`].
+    Overwrite,
+    /// Load the new recipe with a numbered suffix added to its name.
+    Rename,
+    /// Don't load anything from the file if any recipe conflicts.
+    Abort,
+}
+
+impl ConflictPolicy {
+    pub const ALL: [ConflictPolicy; 4] = [
+        ConflictPolicy::Skip,
+        ConflictPolicy::Overwrite,
+        ConflictPolicy::Rename,
+        ConflictPolicy::Abort,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ConflictPolicy::Skip => "Skip",
+            ConflictPolicy::Overwrite => "Overwrite",
+            ConflictPolicy::Rename => "Rename",
+            ConflictPolicy::Abort => "Abort",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LoadOptions {
+    pub on_conflict: ConflictPolicy,
+}
+
+/// What happened to a single recipe in a loaded file.
+#[derive(Debug)]
+pub enum LoadOutcome<'json> {
+    Inserted(RecipeId),
+    /// An existing recipe had its ingredients and products replaced.
+    Overwritten(RecipeId),
+    /// Inserted under a new name because the name in the file was taken.
+    Renamed(&'json str, RecipeId),
+    /// Not loaded because the name was taken.
+    Skipped(&'json str),
+    /// Not loaded because the recipe doesn't make sense, with the reason why.
+    Invalid(&'json str, &'static str),
+}
+
+impl LoadOutcome<'_> {
+    /// The id of the recipe now in the world, if it was loaded.
+    pub fn recipe_id(&self) -> Option<RecipeId> {
+        match *self {
+            LoadOutcome::Inserted(recipe_id)
+            | LoadOutcome::Overwritten(recipe_id)
+            | LoadOutcome::Renamed(_, recipe_id) => Some(recipe_id),
+            LoadOutcome::Skipped(_) | LoadOutcome::Invalid(..) => None,
+        }
+    }
+
+    /// The recipe the file now owns, if it added one.
+    ///
+    /// Overwritten recipes aren't owned, they stay when the file no longer has them.
+    pub fn owned_recipe(&self, world: &FactoryWorld) -> Option<OwnedRecipe> {
+        let (file_name, recipe_id) = match *self {
+            LoadOutcome::Inserted(recipe_id) => (None, recipe_id),
+            LoadOutcome::Renamed(file_name, recipe_id) => (Some(file_name), recipe_id),
+            _ => return None,
+        };
+
+        let world_name = world.get_recipe(recipe_id).ok()?.name.clone();
+
+        Some(OwnedRecipe {
+            file_name: file_name.map_or_else(|| world_name.clone(), str::to_string),
+            world_name,
+        })
+    }
+}
+
+/// A recipe a file added to the world, which reloading the file keeps up to date.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OwnedRecipe {
+    /// The name in the file.
+    pub file_name: String,
+    /// The name in the world, which differs if the recipe was renamed to avoid a conflict.
+    pub world_name: String,
+}
+
+#[derive(Debug)]
+pub enum LoadError<'json> {
+    Json(serde_json::Error),
+    /// Loading was aborted because of these conflicting names.
+    Conflicts(Vec<&'json str>),
+}
+
+impl std::fmt::Display for LoadError<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LoadError::Json(err) => write!(f, "Invalid JSON: {}", err),
+            LoadError::Conflicts(names) => {
+                write!(f, "Conflicting recipe names: {}", names.join(", "))
+            }
+        }
+    }
 }
 
 pub fn load_recipes<'json>(
     world: &mut FactoryWorld,
     json: &'json str,
-) -> Result<Vec<Result<RecipeId, &'json str>>, serde_json::Error> {
-    let parsed_json: RecipeListJson = serde_json::from_str(json)?;
+    options: LoadOptions,
+) -> Result<Vec<LoadOutcome<'json>>, LoadError<'json>> {
+    let parsed_json: RecipeListJson = serde_json::from_str(json).map_err(LoadError::Json)?;
+
+    if options.on_conflict == ConflictPolicy::Abort {
+        let mut conflicts = Vec::new();
+
+        for (index, recipe_json) in parsed_json.recipes.iter().enumerate() {
+            let duplicated_in_file = parsed_json.recipes[..index]
+                .iter()
+                .any(|earlier| earlier.name == recipe_json.name);
+
+            if (duplicated_in_file || world.get_recipe_id(recipe_json.name).is_some())
+                && !conflicts.contains(&recipe_json.name)
+            {
+                conflicts.push(recipe_json.name);
+            }
+        }
+
+        if !conflicts.is_empty() {
+            return Err(LoadError::Conflicts(conflicts));
+        }
+    }
 
     Ok(parsed_json
         .recipes
         .into_iter()
-        .map(|RecipeJson { name, rates }| {
-            let recipe = Recipe {
-                name: name.to_string(),
-                ratios: rates
-                    .into_iter()
-                    .map(|(resource_name, rate)| {
-                        let resource_id = world.get_resource_id(resource_name);
-                        (resource_id, rate)
-                    })
-                    .collect(),
+        .map(|recipe_json| {
+            let name = recipe_json.name;
+
+            let skipped = world.get_recipe_id(name).is_some()
+                && matches!(
+                    options.on_conflict,
+                    ConflictPolicy::Skip | ConflictPolicy::Abort
+                );
+
+            if skipped {
+                return match recipe_json.check() {
+                    Ok(()) => LoadOutcome::Skipped(name),
+                    Err(reason) => LoadOutcome::Invalid(name, reason),
+                };
+            }
+
+            let mut recipe = match recipe_json.into_recipe(world) {
+                Ok(recipe) => recipe,
+                Err(reason) => return LoadOutcome::Invalid(name, reason),
+            };
+
+            let Some(existing_id) = world.get_recipe_id(name) else {
+                let recipe_id = world
+                    .insert_recipe(recipe)
+                    .expect("Recipe name was just checked");
+
+                return LoadOutcome::Inserted(recipe_id);
             };
 
-            match world.insert_recipe(recipe) {
-                Some(id) => Ok(id),
-                None => Err(name),
+            match options.on_conflict {
+                ConflictPolicy::Skip | ConflictPolicy::Abort => {
+                    unreachable!("Skipped recipes were handled above")
+                }
+                ConflictPolicy::Overwrite => {
+                    world
+                        .update_recipe(existing_id, recipe)
+                        .expect("Recipe id was just looked up");
+
+                    LoadOutcome::Overwritten(existing_id)
+                }
+                ConflictPolicy::Rename => {
+                    recipe.name = (2..)
+                        .map(|suffix| format!("{} ({})", name, suffix))
+                        .find(|new_name| world.get_recipe_id(new_name).is_none())
+                        .expect("Should find a free name");
+
+                    let recipe_id = world
+                        .insert_recipe(recipe)
+                        .expect("Recipe name was just checked");
+
+                    LoadOutcome::Renamed(name, recipe_id)
+                }
             }
         })
         .collect())
 }
+
+fn resolve_rates(
+    world: &mut FactoryWorld,
+    rates: Vec<(&str, Rational)>,
+) -> Vec<(ResourceId, Rational)> {
+    rates
+        .into_iter()
+        .map(|(resource_name, rate)| {
+            let resource_id = world.get_resource_id(resource_name);
+            (resource_id, rate)
+        })
+        .collect()
+}
+
+/// What changed when a recipe file was reloaded.
+#[derive(Default)]
+pub struct ReloadSummary {
+    pub added: Vec<RecipeId>,
+    /// Recipes the file owns that changed, and recipes it overwrote again.
+    pub updated: Vec<RecipeId>,
+    pub removed: Vec<RecipeId>,
+    /// Recipes left as they were because the name is taken and the policy is to skip them.
+    pub skipped: Vec<String>,
+    /// Recipes left as they were because the new version is invalid, with the reason why.
+    pub invalid: Vec<(String, &'static str)>,
+    /// All the recipes the file owns now, to diff against on the next reload.
+    pub owned: Vec<OwnedRecipe>,
+}
+
+/// Brings the recipes loaded from a file in line with its new contents.
+///
+/// Recipes the file owns are updated in place so their [`RecipeId`] doesn't change, and the
+/// ones that are no longer in the file are removed. New recipes whose name is taken by a recipe
+/// the file doesn't own go through `on_conflict` the same as when the file was first loaded.
+pub fn reload_recipes<'json>(
+    world: &mut FactoryWorld,
+    json: &'json str,
+    on_conflict: ConflictPolicy,
+    previous: &[OwnedRecipe],
+) -> Result<ReloadSummary, LoadError<'json>> {
+    let parsed_json: RecipeListJson = serde_json::from_str(json).map_err(LoadError::Json)?;
+
+    if on_conflict == ConflictPolicy::Abort {
+        let mut conflicts = Vec::new();
+
+        for (index, recipe_json) in parsed_json.recipes.iter().enumerate() {
+            let duplicated_in_file = parsed_json.recipes[..index]
+                .iter()
+                .any(|earlier| earlier.name == recipe_json.name);
+
+            let owned = previous
+                .iter()
+                .any(|owned| owned.file_name == recipe_json.name);
+            let taken = !owned && world.get_recipe_id(recipe_json.name).is_some();
+
+            if (duplicated_in_file || taken) && !conflicts.contains(&recipe_json.name) {
+                conflicts.push(recipe_json.name);
+            }
+        }
+
+        if !conflicts.is_empty() {
+            return Err(LoadError::Conflicts(conflicts));
+        }
+    }
+
+    let previous_owned: Vec<OwnedRecipe> = previous.to_vec();
+    let mut summary = ReloadSummary::default();
+
+    for recipe_json in parsed_json.recipes {
+        let file_name = recipe_json.name;
+
+        // a name repeated in the file conflicts with its first use
+        let already_owned = summary
+            .owned
+            .iter()
+            .any(|owned| owned.file_name == file_name);
+
+        let owner = previous_owned
+            .iter()
+            .find(|owned| owned.file_name == file_name)
+            .filter(|owned| !already_owned && world.get_recipe_id(&owned.world_name).is_some())
+            .cloned();
+
+        let skipped = owner.is_none()
+            && world.get_recipe_id(file_name).is_some()
+            && matches!(on_conflict, ConflictPolicy::Skip | ConflictPolicy::Abort);
+
+        if skipped {
+            match recipe_json.check() {
+                Ok(()) => summary.skipped.push(file_name.to_string()),
+                Err(reason) => summary.invalid.push((file_name.to_string(), reason)),
+            }
+
+            continue;
+        }
+
+        let mut new_recipe = match recipe_json.into_recipe(world) {
+            Ok(recipe) => recipe,
+            Err(reason) => {
+                summary.invalid.push((file_name.to_string(), reason));
+                summary.owned.extend(owner);
+                continue;
+            }
+        };
+
+        if let Some(owned) = owner {
+            let recipe_id = world
+                .get_recipe_id(&owned.world_name)
+                .expect("Owned recipe was just looked up");
+
+            update_if_changed(world, recipe_id, new_recipe, &mut summary);
+            summary.owned.push(owned);
+            continue;
+        }
+
+        let Some(existing_id) = world.get_recipe_id(file_name) else {
+            let recipe_id = world
+                .insert_recipe(new_recipe)
+                .expect("Recipe name was just checked");
+
+            summary.added.push(recipe_id);
+            summary.owned.push(OwnedRecipe {
+                file_name: file_name.to_string(),
+                world_name: file_name.to_string(),
+            });
+            continue;
+        };
+
+        match on_conflict {
+            ConflictPolicy::Skip | ConflictPolicy::Abort => {
+                unreachable!("Skipped recipes were handled above")
+            }
+            ConflictPolicy::Overwrite => {
+                update_if_changed(world, existing_id, new_recipe, &mut summary);
+            }
+            ConflictPolicy::Rename => {
+                new_recipe.name = (2..)
+                    .map(|suffix| format!("{} ({})", file_name, suffix))
+                    .find(|new_name| world.get_recipe_id(new_name).is_none())
+                    .expect("Should find a free name");
+
+                summary.owned.push(OwnedRecipe {
+                    file_name: file_name.to_string(),
+                    world_name: new_recipe.name.clone(),
+                });
+
+                let recipe_id = world
+                    .insert_recipe(new_recipe)
+                    .expect("Recipe name was just checked");
+
+                summary.added.push(recipe_id);
+            }
+        }
+    }
+
+    for previous in previous_owned {
+        if summary.owned.contains(&previous) {
+            continue;
+        }
+
+        if let Some(recipe_id) = world.get_recipe_id(&previous.world_name) {
+            world.remove_recipe(recipe_id);
+            summary.removed.push(recipe_id);
+        }
+    }
+
+    Ok(summary)
+}
+
+fn update_if_changed(
+    world: &mut FactoryWorld,
+    recipe_id: RecipeId,
+    new_recipe: Recipe,
+    summary: &mut ReloadSummary,
+) {
+    let recipe = world
+        .get_recipe(recipe_id)
+        .expect("Recipe id was just looked up");
+
+    if !recipe.same_rates(&new_recipe) {
+        world
+            .update_recipe(recipe_id, new_recipe)
+            .expect("Recipe id was just looked up");
+        summary.updated.push(recipe_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASE: &str = r#"{"recipes": [{"name": "Iron Ingot", "rates": [["Iron Ore", -30], ["Iron Ingot", 30]]}]}"#;
+    const MOD: &str = r#"{"recipes": [{"name": "Iron Ingot", "rates": [["Iron Ore", -20], ["Iron Ingot", 30]]}]}"#;
+    const MOD_CHANGED: &str = r#"{"recipes": [{"name": "Iron Ingot", "rates": [["Iron Ore", -10], ["Iron Ingot", 30]]}]}"#;
+    const EMPTY: &str = r#"{"recipes": []}"#;
+
+    fn ore_rate(world: &mut FactoryWorld, recipe_name: &str) -> Option<Rational> {
+        let recipe_id = world.get_recipe_id(recipe_name)?;
+        let ore_id = world.find_resource_id("Iron Ore")?;
+        Some(world.get_recipe(recipe_id).ok()?.net_rate(ore_id))
+    }
+
+    /// Loads the base recipes, then the mod with `on_conflict`, returning what the mod owns.
+    fn load_mod(world: &mut FactoryWorld, on_conflict: ConflictPolicy) -> Vec<OwnedRecipe> {
+        load_recipes(world, BASE, LoadOptions::default()).unwrap();
+
+        load_recipes(world, MOD, LoadOptions { on_conflict })
+            .unwrap()
+            .iter()
+            .filter_map(|outcome| outcome.owned_recipe(world))
+            .collect()
+    }
+
+    #[test]
+    fn reload_keeps_renamed_recipes_apart() {
+        let mut world = FactoryWorld::default();
+        let owned = load_mod(&mut world, ConflictPolicy::Rename);
+
+        let summary =
+            reload_recipes(&mut world, MOD_CHANGED, ConflictPolicy::Rename, &owned).unwrap();
+
+        assert_eq!(summary.updated.len(), 1);
+        assert_eq!(
+            ore_rate(&mut world, "Iron Ingot"),
+            Some(Rational::from_integer(-30))
+        );
+        assert_eq!(
+            ore_rate(&mut world, "Iron Ingot (2)"),
+            Some(Rational::from_integer(-10))
+        );
+
+        reload_recipes(&mut world, EMPTY, ConflictPolicy::Rename, &summary.owned).unwrap();
+
+        assert!(world.get_recipe_id("Iron Ingot").is_some());
+        assert!(world.get_recipe_id("Iron Ingot (2)").is_none());
+    }
+
+    #[test]
+    fn reload_never_removes_overwritten_recipes() {
+        let mut world = FactoryWorld::default();
+        let owned = load_mod(&mut world, ConflictPolicy::Overwrite);
+
+        assert!(owned.is_empty());
+        assert_eq!(
+            ore_rate(&mut world, "Iron Ingot"),
+            Some(Rational::from_integer(-20))
+        );
+
+        let summary =
+            reload_recipes(&mut world, MOD_CHANGED, ConflictPolicy::Overwrite, &owned).unwrap();
+
+        assert_eq!(
+            ore_rate(&mut world, "Iron Ingot"),
+            Some(Rational::from_integer(-10))
+        );
+
+        reload_recipes(&mut world, EMPTY, ConflictPolicy::Overwrite, &summary.owned).unwrap();
+
+        assert!(world.get_recipe_id("Iron Ingot").is_some());
+    }
+
+    #[test]
+    fn reload_skips_and_aborts_on_taken_names() {
+        let mut world = FactoryWorld::default();
+        load_recipes(&mut world, BASE, LoadOptions::default()).unwrap();
+
+        let summary = reload_recipes(&mut world, MOD, ConflictPolicy::Skip, &[]).unwrap();
+        assert_eq!(summary.skipped, vec!["Iron Ingot".to_string()]);
+
+        assert!(matches!(
+            reload_recipes(&mut world, MOD, ConflictPolicy::Abort, &[]),
+            Err(LoadError::Conflicts(_))
+        ));
+        assert_eq!(
+            ore_rate(&mut world, "Iron Ingot"),
+            Some(Rational::from_integer(-30))
+        );
+    }
+
+    #[test]
+    fn skipped_recipes_add_no_resources() {
+        const SLAG: &str = r#"{"recipes": [{"name": "Iron Ingot", "rates": [["Slag", -30], ["Iron Ingot", 30]]}]}"#;
+
+        let mut world = FactoryWorld::default();
+        load_recipes(&mut world, BASE, LoadOptions::default()).unwrap();
+
+        let outcomes = load_recipes(&mut world, SLAG, LoadOptions::default()).unwrap();
+        assert!(matches!(outcomes[..], [LoadOutcome::Skipped("Iron Ingot")]));
+
+        let summary = reload_recipes(&mut world, SLAG, ConflictPolicy::Skip, &[]).unwrap();
+        assert_eq!(summary.skipped, vec!["Iron Ingot".to_string()]);
+
+        assert!(world.find_resource_id("Slag").is_none());
+    }
+}
@@ -1,3 +1,5 @@
+use std::path::PathBuf;
+
 use bevy::{
     color::palettes::css::*,
     prelude::*,
@@ -5,23 +7,42 @@ use bevy::{
 };
 
 use crate::{
-    ControlPanelUi, CreateBaseUi, MainMenuItem, MainMenuUI,
+    ControlPanelUi, CreateBaseUi, MainMenuItem, MainMenuUI, MenuName,
     drag_plane::{DragBox, DragBoxPlane, PlaneScroll},
     factory_world::*,
+    keymap::{AppAction, action_sent},
+    rational::Rational,
+    recipe_watch::RecipeSources,
+    text_input::{FocusedTextInput, TextInput, TextInputSubmitted},
 };
 
-use crate::recipe_json::load_recipes;
+use crate::recipe_json::{ConflictPolicy, LoadOptions, LoadOutcome, load_recipes};
 
 pub fn build(app: &mut App) {
+    app.insert_resource(RecipeLoadOptions::default());
+    app.insert_resource(RateDisplay::default());
+    app.insert_resource(BoxIconMode::default());
+
     app.add_systems(Startup, create_recipe_ui.after(CreateBaseUi));
     app.add_systems(
         Update,
         (
             start_load_recipes_dialogue,
             finish_load_recipes_dialogue,
+            cycle_conflict_policy,
+            toggle_rate_display,
+            toggle_icon_mode,
+            toggle_collapsed_boxes,
+            update_box_collapse,
+            update_recipe_list,
+            toggle_recipe_locks,
             remove_recipe_ui,
+            refresh_recipe_boxes,
             insert_recipe_ui,
             despawn_recipes,
+            submit_multipliers,
+            sync_multiplier_inputs.after(submit_multipliers),
+            update_rate_texts,
         ),
     );
 }
@@ -31,14 +52,56 @@ pub struct RecipeList {
     pub recipe_list_entity: Entity,
 }
 
+/// Options used when loading recipes from a file.
+#[derive(Resource, Default)]
+pub struct RecipeLoadOptions(pub LoadOptions);
+
 #[derive(Component)]
 #[require(Button)]
 struct LoadRecipesButton;
 
+#[derive(Component)]
+#[require(Button)]
+struct ConflictPolicyButton;
+
+/// Whether boxes show the quantities of one cycle instead of per minute rates,
+/// for recipes that have a cycle time.
+#[derive(Resource, Default)]
+pub struct RateDisplay {
+    pub per_cycle: bool,
+}
+
+#[derive(Component)]
+#[require(Button)]
+struct RateDisplayButton;
+
+/// Whether every box is shrunk down to just its title, to fit more of the plane on screen.
+///
+/// This is toggled by hand, since the plane only scrolls and has no zoom level to follow.
+#[derive(Resource, Default)]
+pub struct BoxIconMode(pub bool);
+
+#[derive(Component)]
+#[require(Button)]
+struct IconModeButton;
+
+/// A box that only shows its title and ports, without rates.
+#[derive(Component)]
+pub struct CollapsedBox;
+
+#[derive(Component)]
+#[require(Button)]
+struct CollapseBoxButton {
+    box_entity: Entity,
+}
+
+/// The node holding the input and output grids of a box.
+#[derive(Component)]
+struct BoxContent;
+
 #[derive(Component)]
 #[require(Button)]
 struct RemoveRecipeButton {
-    list_item_entity: Entity,
     recipe_id: RecipeId,
 }
 
@@ -50,7 +113,43 @@ struct InsertRecipeButton {
 
 #[derive(Component)]
 #[require(Button)]
-struct DespawnRecipeButton {
+struct LockRecipeButton {
+    recipe_id: RecipeId,
+}
+
+#[derive(Component)]
+#[require(Button)]
+pub struct DespawnRecipeButton {
+    pub box_entity: Entity,
+}
+
+/// A drag box showing a single recipe.
+#[derive(Component)]
+pub struct RecipeBox {
+    pub recipe_id: RecipeId,
+    /// How many machines are running the recipe, scales all the rates.
+    pub multiplier: Rational,
+}
+
+/// A resource in a box that can be linked to other boxes.
+#[derive(Component, Clone, Copy, PartialEq)]
+#[require(Button, BackgroundColor)]
+pub struct BoxPort {
+    pub box_entity: Entity,
+    pub resource_id: ResourceId,
+    pub output: bool,
+}
+
+/// The text showing the rate of a resource in a box.
+#[derive(Component)]
+struct RateText {
+    box_entity: Entity,
+    /// Per minute at a multiplier of one, always positive.
+    rate: Rational,
+}
+
+#[derive(Component)]
+struct MultiplierInput {
     box_entity: Entity,
 }
 
@@ -70,7 +169,10 @@ fn create_recipe_ui(
 
     commands
         .spawn((
-            MainMenuItem { sub_menu_entity },
+            MainMenuItem {
+                sub_menu_entity,
+                menu: MenuName::Recipes,
+            },
             Text::new("Recipes"),
             Node {
                 width: Val::Percent(100.0),
@@ -83,6 +185,21 @@ fn create_recipe_ui(
         .spawn((LoadRecipesButton, Text::new("Load Recipes")))
         .set_parent(sub_menu_entity);
 
+    commands
+        .spawn((
+            ConflictPolicyButton,
+            Text::new(conflict_policy_text(ConflictPolicy::default())),
+        ))
+        .set_parent(sub_menu_entity);
+
+    commands
+        .spawn((RateDisplayButton, Text::new(rate_display_text(false))))
+        .set_parent(sub_menu_entity);
+
+    commands
+        .spawn((IconModeButton, Text::new(icon_mode_text(false))))
+        .set_parent(sub_menu_entity);
+
     let recipe_list_entity = commands
         .spawn((Node {
             flex_direction: FlexDirection::Column,
@@ -94,18 +211,158 @@ fn create_recipe_ui(
     commands.insert_resource(RecipeList { recipe_list_entity });
 }
 
+fn conflict_policy_text(policy: ConflictPolicy) -> String {
+    format!("On Duplicate: {}", policy.name())
+}
+
+/// Switches to the next policy for recipes with names that are already taken.
+#[allow(clippy::type_complexity)]
+fn cycle_conflict_policy(
+    mut button_q: Query<
+        (&Interaction, &mut Text),
+        (Changed<Interaction>, With<ConflictPolicyButton>),
+    >,
+    mut load_options: ResMut<RecipeLoadOptions>,
+) {
+    let Ok((Interaction::Pressed, mut text)) = button_q.get_single_mut() else {
+        return;
+    };
+
+    let policy = &mut load_options.0.on_conflict;
+    let index = ConflictPolicy::ALL
+        .iter()
+        .position(|other| other == policy)
+        .expect("All policies should be listed");
+    *policy = ConflictPolicy::ALL[(index + 1) % ConflictPolicy::ALL.len()];
+
+    text.0 = conflict_policy_text(*policy);
+}
+
+fn rate_display_text(per_cycle: bool) -> &'static str {
+    if per_cycle {
+        "Showing: Per Cycle"
+    } else {
+        "Showing: Per Minute"
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn toggle_rate_display(
+    mut button_q: Query<(&Interaction, &mut Text), (Changed<Interaction>, With<RateDisplayButton>)>,
+    mut rate_display: ResMut<RateDisplay>,
+) {
+    let Ok((Interaction::Pressed, mut text)) = button_q.get_single_mut() else {
+        return;
+    };
+
+    rate_display.per_cycle = !rate_display.per_cycle;
+
+    text.0 = rate_display_text(rate_display.per_cycle).into();
+}
+
+fn icon_mode_text(icons: bool) -> &'static str {
+    if icons { "Boxes: Icons" } else { "Boxes: Full" }
+}
+
+fn toggle_icon_mode(
+    button_q: Query<&Interaction, (Changed<Interaction>, With<IconModeButton>)>,
+    mut text_q: Query<&mut Text, With<IconModeButton>>,
+    mut actions: EventReader<AppAction>,
+    mut icon_mode: ResMut<BoxIconMode>,
+) {
+    let button_pressed = matches!(button_q.get_single(), Ok(Interaction::Pressed));
+    if !action_sent(&mut actions, AppAction::ToggleIconMode) && !button_pressed {
+        return;
+    }
+
+    icon_mode.0 = !icon_mode.0;
+
+    if let Ok(mut text) = text_q.get_single_mut() {
+        text.0 = icon_mode_text(icon_mode.0).into();
+    }
+}
+
+fn toggle_collapsed_boxes(
+    mut commands: Commands,
+    button_q: Query<(&Interaction, &CollapseBoxButton), Changed<Interaction>>,
+    collapsed_q: Query<Has<CollapsedBox>>,
+) {
+    for (interaction, button) in button_q.iter() {
+        let Interaction::Pressed = interaction else {
+            continue;
+        };
+
+        let Ok(collapsed) = collapsed_q.get(button.box_entity) else {
+            continue;
+        };
+
+        if collapsed {
+            commands.entity(button.box_entity).remove::<CollapsedBox>();
+        } else {
+            commands.entity(button.box_entity).insert(CollapsedBox);
+        }
+    }
+}
+
+/// Hides the rates of collapsed boxes, and everything but the titles in icon mode.
+fn update_box_collapse(
+    mut rate_text_q: Query<(&RateText, &mut Node), Without<BoxContent>>,
+    mut content_q: Query<&mut Node, With<BoxContent>>,
+    mut button_q: Query<(&CollapseBoxButton, &mut Text)>,
+    collapsed_q: Query<(), With<CollapsedBox>>,
+    icon_mode: Res<BoxIconMode>,
+) {
+    for (button, mut text) in button_q.iter_mut() {
+        let symbol = if collapsed_q.contains(button.box_entity) {
+            ">"
+        } else {
+            "v"
+        };
+
+        if text.0 != symbol {
+            text.0 = symbol.into();
+        }
+    }
+
+    let content_display = if icon_mode.0 {
+        Display::None
+    } else {
+        Display::Flex
+    };
+
+    for mut node in content_q.iter_mut() {
+        if node.display != content_display {
+            node.display = content_display;
+        }
+    }
+
+    for (rate_text, mut node) in rate_text_q.iter_mut() {
+        let display = if collapsed_q.contains(rate_text.box_entity) {
+            Display::None
+        } else {
+            Display::Flex
+        };
+
+        if node.display != display {
+            node.display = display;
+        }
+    }
+}
+
 #[derive(Component)]
 struct LoadRecipesTask {
-    task: Task<Option<String>>,
+    task: Task<Option<(PathBuf, String)>>,
 }
 
 fn start_load_recipes_dialogue(
     mut commands: Commands,
     button_q: Query<&Interaction, (Changed<Interaction>, With<LoadRecipesButton>)>,
+    mut actions: EventReader<AppAction>,
 ) {
-    let Ok(Interaction::Pressed) = button_q.get_single() else {
+    let button_pressed = matches!(button_q.get_single(), Ok(Interaction::Pressed));
+    if !action_sent(&mut actions, AppAction::LoadRecipes) && !button_pressed {
         return;
-    };
+    }
 
     info!("Loading recipes");
 
@@ -114,13 +371,16 @@ fn start_load_recipes_dialogue(
     });
 }
 
-async fn load_recipes_async() -> Option<String> {
+async fn load_recipes_async() -> Option<(PathBuf, String)> {
     let file_handle = rfd::AsyncFileDialog::new().pick_file().await;
 
     if let Some(file_handle) = file_handle {
         let bytes = file_handle.read().await;
 
-        String::from_utf8(bytes).ok()
+        Some((
+            file_handle.path().to_path_buf(),
+            String::from_utf8(bytes).ok()?,
+        ))
     } else {
         None
     }
@@ -130,7 +390,9 @@ fn finish_load_recipes_dialogue(
     mut commands: Commands,
     mut task_q: Query<(Entity, &mut LoadRecipesTask)>,
     mut world: ResMut<FactoryWorld>,
-    recipe_list: Res<RecipeList>,
+    mut recipe_sources: ResMut<RecipeSources>,
+    load_options: Res<RecipeLoadOptions>,
+    mut recipe_events: EventWriter<RecipeEvent>,
 ) {
     for (task_entity, mut task) in task_q.iter_mut() {
         if task.task.is_finished() {
@@ -141,61 +403,143 @@ fn finish_load_recipes_dialogue(
 
             commands.entity(task_entity).despawn_recursive();
 
-            let Some(json) = task_result else {
+            let Some((path, json)) = task_result else {
                 continue;
             };
 
-            let Ok(load_results) = load_recipes(world.as_mut(), &json) else {
-                error!("Invalid JSON");
-                continue;
+            let load_outcomes = match load_recipes(world.as_mut(), &json, load_options.0) {
+                Ok(load_outcomes) => load_outcomes,
+                Err(err) => {
+                    error!("Failed to load recipes: {}", err);
+                    continue;
+                }
             };
 
-            for load_result in load_results {
-                let recipe_id = match load_result {
-                    Ok(recipe_id) => recipe_id,
-                    Err(recipe_name) => {
-                        error!("Failed to load recipe \"{}\"", recipe_name);
-                        continue;
-                    }
-                };
+            let mut owned = Vec::new();
 
-                let recipe = world.get_recipe(recipe_id).expect("Recipe was just added");
+            for load_outcome in load_outcomes {
+                if let LoadOutcome::Skipped(recipe_name) = load_outcome {
+                    warn!("Skipped duplicate recipe \"{}\"", recipe_name);
+                }
 
-                commands
-                    .spawn(Node {
-                        flex_direction: FlexDirection::Row,
-                        ..default()
-                    })
-                    .with_children(|builder| {
-                        builder.spawn((
-                            RemoveRecipeButton {
-                                list_item_entity: builder.parent_entity(),
-                                recipe_id,
-                            },
-                            Text::new("X"),
-                        ));
+                if let LoadOutcome::Invalid(recipe_name, reason) = load_outcome {
+                    error!("Invalid recipe \"{}\", it {}", recipe_name, reason);
+                }
 
-                        builder.spawn((InsertRecipeButton { recipe_id }, Text::new(&recipe.name)));
-                    })
-                    .set_parent(recipe_list.recipe_list_entity);
+                if let LoadOutcome::Overwritten(recipe_id) = load_outcome {
+                    recipe_events.send(RecipeEvent::Updated(recipe_id));
+                }
+
+                owned.extend(load_outcome.owned_recipe(world.as_ref()));
             }
+
+            recipe_sources.watch(path, load_options.0.on_conflict, owned);
         }
     }
 }
 
-fn remove_recipe_ui(
+/// Rebuilds the list of recipes whenever the world changes.
+fn update_recipe_list(
     mut commands: Commands,
+    world: Res<FactoryWorld>,
+    recipe_list: Res<RecipeList>,
+) {
+    if !world.is_changed() {
+        return;
+    }
+
+    commands
+        .entity(recipe_list.recipe_list_entity)
+        .despawn_descendants();
+
+    let mut recipes: Vec<_> = world.iter_recipes().collect();
+    recipes.sort_by(|(_, a), (_, b)| a.name.cmp(&b.name));
+
+    for (recipe_id, recipe) in recipes {
+        commands
+            .spawn(Node {
+                flex_direction: FlexDirection::Row,
+                ..default()
+            })
+            .with_children(|builder| {
+                builder.spawn((RemoveRecipeButton { recipe_id }, Text::new("X")));
+
+                builder.spawn((InsertRecipeButton { recipe_id }, Text::new(&recipe.name)));
+
+                let lock_text = if world.is_recipe_locked(recipe_id) {
+                    " (locked)"
+                } else {
+                    " (unlocked)"
+                };
+
+                builder.spawn((LockRecipeButton { recipe_id }, Text::new(lock_text)));
+            })
+            .set_parent(recipe_list.recipe_list_entity);
+    }
+}
+
+/// Locking a recipe changes the world, which rebuilds the list with the new state.
+fn toggle_recipe_locks(
+    button_q: Query<(&Interaction, &LockRecipeButton), Changed<Interaction>>,
+    mut world: ResMut<FactoryWorld>,
+) {
+    for (interaction, button) in button_q.iter() {
+        let Interaction::Pressed = interaction else {
+            continue;
+        };
+
+        let locked = world.is_recipe_locked(button.recipe_id);
+        world.set_recipe_locked(button.recipe_id, !locked);
+    }
+}
+
+fn remove_recipe_ui(
     button_q: Query<(&Interaction, &RemoveRecipeButton), Changed<Interaction>>,
     mut world: ResMut<FactoryWorld>,
+    mut recipe_events: EventWriter<RecipeEvent>,
 ) {
     for (interaction, button) in button_q.iter() {
         let Interaction::Pressed = interaction else {
             continue;
         };
 
-        world.remove_recipe(button.recipe_id);
+        if world.remove_recipe(button.recipe_id).is_some() {
+            recipe_events.send(RecipeEvent::Removed(button.recipe_id));
+        }
+    }
+}
 
-        commands.entity(button.list_item_entity).despawn_recursive();
+/// Rebuilds boxes whose recipe changed, and despawns boxes whose recipe was removed.
+fn refresh_recipe_boxes(
+    mut commands: Commands,
+    mut recipe_events: EventReader<RecipeEvent>,
+    recipe_box_q: Query<(Entity, &RecipeBox)>,
+    world: Res<FactoryWorld>,
+) {
+    for &event in recipe_events.read() {
+        for (box_entity, recipe_box) in recipe_box_q.iter() {
+            match event {
+                RecipeEvent::Updated(recipe_id) if recipe_box.recipe_id == recipe_id => {
+                    let recipe = world
+                        .get_recipe(recipe_id)
+                        .expect("Updated recipe should be in world");
+
+                    commands.entity(box_entity).despawn_descendants();
+
+                    spawn_recipe_box_contents(
+                        &mut commands,
+                        box_entity,
+                        recipe,
+                        recipe_box.multiplier,
+                        world.as_ref(),
+                    );
+                }
+                RecipeEvent::Removed(recipe_id) if recipe_box.recipe_id == recipe_id => {
+                    commands.entity(box_entity).despawn_recursive();
+                }
+                _ => (),
+            }
+        }
     }
 }
 
@@ -213,92 +557,257 @@ fn insert_recipe_ui(
 
         info!("inserting recipe {:?}", button.recipe_id);
 
-        let recipe = world
-            .get_recipe(button.recipe_id)
-            .expect("Recipe should be in world");
-
         let root_plane_entity = plane_q.get_single().expect("Should be one root plane");
 
-        commands
-            .spawn((
-                DragBox {
-                    position: -plane_scroll.scroll_position,
-                },
-                Node {
-                    position_type: PositionType::Absolute,
-                    ..default()
+        spawn_recipe_box(
+            &mut commands,
+            root_plane_entity,
+            -plane_scroll.scroll_position,
+            button.recipe_id,
+            Rational::ONE,
+            world.as_ref(),
+        )
+        .expect("Recipe should be in world");
+    }
+}
+
+/// Spawns a box for a recipe on the plane, fails if the recipe doesn't exist.
+pub fn spawn_recipe_box(
+    commands: &mut Commands,
+    plane_entity: Entity,
+    position: Vec2,
+    recipe_id: RecipeId,
+    multiplier: Rational,
+    world: &FactoryWorld,
+) -> Result<Entity, InvalidRecipeError> {
+    let recipe = world.get_recipe(recipe_id)?;
+
+    let box_entity = commands
+        .spawn((
+            DragBox { position },
+            RecipeBox {
+                recipe_id,
+                multiplier,
+            },
+            Node {
+                position_type: PositionType::Absolute,
+                ..default()
+            },
+        ))
+        .set_parent(plane_entity)
+        .id();
+
+    spawn_recipe_box_contents(commands, box_entity, recipe, multiplier, world);
+
+    Ok(box_entity)
+}
+
+fn spawn_recipe_box_contents(
+    commands: &mut Commands,
+    box_entity: Entity,
+    recipe: &Recipe,
+    multiplier: Rational,
+    world: &FactoryWorld,
+) {
+    spawn_box_contents(
+        commands,
+        box_entity,
+        &recipe.name,
+        RED.into(),
+        &recipe.ingredients,
+        &recipe.products,
+        true,
+        world,
+        |builder| {
+            builder.spawn((
+                MultiplierInput { box_entity },
+                TextInput {
+                    value: multiplier.to_string(),
+                    placeholder: "x".into(),
                 },
-            ))
-            .set_parent(root_plane_entity)
-            .with_children(|builder| {
-                let box_entity = builder.parent_entity();
+                BackgroundColor(BLACK.into()),
+            ));
+        },
+    );
+}
 
+/// Spawns the header and the input and output grids of a box as children of `box_entity`.
+///
+/// `header_buttons` can add extra buttons to the header, before the collapse and despawn buttons.
+/// If `ports` is set, the resource names can be clicked to link boxes together.
+#[allow(clippy::too_many_arguments)]
+pub fn spawn_box_contents(
+    commands: &mut Commands,
+    box_entity: Entity,
+    title: &str,
+    title_color: Color,
+    inputs: &[(ResourceId, Rational)],
+    outputs: &[(ResourceId, Rational)],
+    ports: bool,
+    world: &FactoryWorld,
+    header_buttons: impl FnOnce(&mut ChildBuilder),
+) {
+    commands.entity(box_entity).with_children(|builder| {
+        builder
+            .spawn(Node {
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                ..default()
+            })
+            .with_children(|builder| {
+                // header
                 builder
                     .spawn(Node {
-                        flex_direction: FlexDirection::Column,
-                        align_items: AlignItems::Center,
+                        width: Val::Percent(100.),
+                        flex_direction: FlexDirection::Row,
                         ..default()
                     })
                     .with_children(|builder| {
-                        // header
-                        builder
-                            .spawn(Node {
-                                width: Val::Percent(100.),
-                                flex_direction: FlexDirection::Row,
+                        builder.spawn((
+                            Text::new(title),
+                            TextLayout {
+                                justify: JustifyText::Center,
+                                ..default()
+                            },
+                            Node {
+                                flex_grow: 1.,
                                 ..default()
-                            })
-                            .with_children(|builder| {
-                                builder.spawn((
-                                    Text::new(&recipe.name),
-                                    TextLayout {
-                                        justify: JustifyText::Center,
-                                        ..default()
-                                    },
-                                    Node {
-                                        flex_grow: 1.,
-                                        ..default()
-                                    },
-                                    BackgroundColor(RED.into()),
-                                ));
-
-                                builder.spawn((DespawnRecipeButton { box_entity }, Text::new("X")));
-                            });
-
-                        // content
-                        builder
-                            .spawn((
-                                Node {
-                                    flex_direction: FlexDirection::Row,
+                            },
+                            BackgroundColor(title_color),
+                        ));
+
+                        header_buttons(builder);
+
+                        builder.spawn((CollapseBoxButton { box_entity }, Text::new("v")));
+
+                        builder.spawn((DespawnRecipeButton { box_entity }, Text::new("X")));
+                    });
+
+                // content
+                builder
+                    .spawn((
+                        BoxContent,
+                        Node {
+                            flex_direction: FlexDirection::Row,
+                            ..default()
+                        },
+                        BackgroundColor(DARK_GREY.into()),
+                    ))
+                    .with_children(|builder| {
+                        for (side, rates) in [(false, inputs), (true, outputs)] {
+                            builder
+                                .spawn(Node {
+                                    display: Display::Grid,
+                                    grid_template_columns: RepeatedGridTrack::auto(2),
                                     ..default()
-                                },
-                                BackgroundColor(DARK_GREY.into()),
-                            ))
-                            .with_children(|builder| {
-                                for side in [false, true] {
-                                    builder
-                                        .spawn(Node {
-                                            display: Display::Grid,
-                                            grid_template_columns: RepeatedGridTrack::auto(2),
-                                            ..default()
-                                        })
-                                        .with_children(|builder| {
-                                            for (resource_id, ratio) in recipe.iter_ratios() {
-                                                let resource_name = world
-                                                    .get_resource_name(resource_id)
-                                                    .expect("Resource name should exist");
-
-                                                if (ratio < 0.) ^ side {
-                                                    builder.spawn(Text::new(resource_name));
-
-                                                    builder
-                                                        .spawn(Text::new(format!("{:.2}", ratio)));
-                                                }
-                                            }
-                                        });
-                                }
-                            });
+                                })
+                                .with_children(|builder| {
+                                    for &(resource_id, rate) in rates {
+                                        let resource_name = world
+                                            .get_resource_name(resource_id)
+                                            .expect("Resource name should exist");
+
+                                        let mut name_commands =
+                                            builder.spawn(Text::new(resource_name));
+
+                                        if ports {
+                                            name_commands.insert(BoxPort {
+                                                box_entity,
+                                                resource_id,
+                                                output: side,
+                                            });
+                                        }
+
+                                        builder.spawn((
+                                            RateText { box_entity, rate },
+                                            Text::new(rate.to_string()),
+                                        ));
+                                    }
+                                });
+                        }
                     });
             });
+    });
+}
+
+/// Sets the multiplier of a recipe box when its input is submitted.
+fn submit_multipliers(
+    mut submitted_events: EventReader<TextInputSubmitted>,
+    mut input_q: Query<(&MultiplierInput, &mut TextInput)>,
+    mut recipe_box_q: Query<&mut RecipeBox>,
+    mut focused: ResMut<FocusedTextInput>,
+) {
+    for event in submitted_events.read() {
+        let Ok((multiplier_input, mut text_input)) = input_q.get_mut(event.entity) else {
+            continue;
+        };
+
+        let Ok(mut recipe_box) = recipe_box_q.get_mut(multiplier_input.box_entity) else {
+            error!("Couldn't query recipe box {}", multiplier_input.box_entity);
+            continue;
+        };
+
+        match event.value.parse::<Rational>() {
+            Ok(multiplier) if !multiplier.is_negative() => recipe_box.multiplier = multiplier,
+            _ => info!("Invalid multiplier \"{}\"", event.value),
+        }
+
+        text_input.value = recipe_box.multiplier.to_string();
+        focused.0 = None;
+    }
+}
+
+/// Shows multipliers that were changed by something other than their input.
+fn sync_multiplier_inputs(
+    mut input_q: Query<(Entity, &MultiplierInput, &mut TextInput)>,
+    recipe_box_q: Query<Ref<RecipeBox>>,
+    focused: Res<FocusedTextInput>,
+) {
+    for (input_entity, multiplier_input, mut text_input) in input_q.iter_mut() {
+        if focused.0 == Some(input_entity) {
+            continue;
+        }
+
+        let Ok(recipe_box) = recipe_box_q.get(multiplier_input.box_entity) else {
+            continue;
+        };
+
+        let value = recipe_box.multiplier.to_string();
+
+        if recipe_box.is_changed() && text_input.value != value {
+            text_input.value = value;
+        }
+    }
+}
+
+/// Keeps the rates shown in a recipe box in line with its multiplier and the rate display.
+fn update_rate_texts(
+    recipe_box_q: Query<Ref<RecipeBox>>,
+    mut rate_text_q: Query<(Ref<RateText>, &mut Text)>,
+    rate_display: Res<RateDisplay>,
+    world: Res<FactoryWorld>,
+) {
+    for (rate_text, mut text) in rate_text_q.iter_mut() {
+        let Ok(recipe_box) = recipe_box_q.get(rate_text.box_entity) else {
+            continue;
+        };
+
+        if !recipe_box.is_changed() && !rate_text.is_added() && !rate_display.is_changed() {
+            continue;
+        }
+
+        let rate = rate_text.rate * recipe_box.multiplier;
+
+        let per_cycle = world
+            .get_recipe(recipe_box.recipe_id)
+            .ok()
+            .filter(|_| rate_display.per_cycle)
+            .and_then(|recipe| Some((recipe.per_cycle(rate)?, recipe.cycle_seconds?)));
+
+        text.0 = match per_cycle {
+            Some((quantity, cycle_seconds)) => format!("{} / {}s", quantity, cycle_seconds),
+            None => rate.to_string(),
+        };
     }
 }
 
@@ -0,0 +1,155 @@
+use std::{
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use bevy::prelude::*;
+
+use crate::{
+    factory_world::*,
+    recipe_json::{ConflictPolicy, OwnedRecipe, reload_recipes},
+};
+
+const POLL_INTERVAL_SECONDS: f32 = 1.;
+
+pub fn build(app: &mut App) {
+    app.insert_resource(RecipeSources {
+        sources: Vec::new(),
+        poll_timer: Timer::from_seconds(POLL_INTERVAL_SECONDS, TimerMode::Repeating),
+    });
+
+    app.add_systems(Update, reload_changed_recipe_sources);
+}
+
+/// The recipe files that have been loaded, which are watched for changes.
+#[derive(Resource)]
+pub struct RecipeSources {
+    sources: Vec<RecipeSource>,
+    poll_timer: Timer,
+}
+
+struct RecipeSource {
+    path: PathBuf,
+    /// When the file was last loaded successfully.
+    modified: Option<SystemTime>,
+    /// What to do with recipes whose name is taken, kept from when the file was loaded.
+    on_conflict: ConflictPolicy,
+    /// The recipes that came from this file last time it was loaded.
+    owned: Vec<OwnedRecipe>,
+    /// Why the last reload failed, so the same error isn't logged on every poll.
+    last_error: Option<String>,
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+impl RecipeSources {
+    /// Starts watching a file that recipes were loaded from, with the conflict policy
+    /// they were loaded with.
+    pub fn watch(&mut self, path: PathBuf, on_conflict: ConflictPolicy, owned: Vec<OwnedRecipe>) {
+        let modified = modified_time(&path);
+
+        if let Some(source) = self.sources.iter_mut().find(|source| source.path == path) {
+            source.modified = modified;
+            source.on_conflict = on_conflict;
+            source.owned.extend(owned);
+            return;
+        }
+
+        info!("Watching {:?} for recipe changes", path);
+
+        self.sources.push(RecipeSource {
+            path,
+            modified,
+            on_conflict,
+            owned,
+            last_error: None,
+        });
+    }
+}
+
+impl RecipeSource {
+    fn report_error(&mut self, message: String) {
+        if self.last_error.as_ref() != Some(&message) {
+            error!("{}", message);
+            self.last_error = Some(message);
+        }
+    }
+}
+
+/// Polls the modified time of each recipe file and diffs it into the world when it changes.
+fn reload_changed_recipe_sources(
+    time: Res<Time>,
+    mut recipe_sources: ResMut<RecipeSources>,
+    mut world: ResMut<FactoryWorld>,
+    mut recipe_events: EventWriter<RecipeEvent>,
+) {
+    if !recipe_sources.poll_timer.tick(time.delta()).just_finished() {
+        return;
+    }
+
+    for source in recipe_sources.sources.iter_mut() {
+        let modified = modified_time(&source.path);
+
+        if modified == source.modified {
+            continue;
+        }
+
+        // the modified time is only kept once the file loads, so a half written file is
+        // tried again on the next poll even if finishing it doesn't change the time
+        let json = match std::fs::read_to_string(&source.path) {
+            Ok(json) => json,
+            Err(err) => {
+                source.report_error(format!(
+                    "Couldn't read recipe file {:?}: {}",
+                    source.path, err
+                ));
+                continue;
+            }
+        };
+
+        let summary = match reload_recipes(world.as_mut(), &json, source.on_conflict, &source.owned)
+        {
+            Ok(summary) => summary,
+            Err(err) => {
+                source.report_error(format!("Couldn't reload {:?}: {}", source.path, err));
+                continue;
+            }
+        };
+
+        source.modified = modified;
+        source.last_error = None;
+
+        info!(
+            "Reloaded {:?}: {} added, {} updated, {} removed",
+            source.path,
+            summary.added.len(),
+            summary.updated.len(),
+            summary.removed.len()
+        );
+
+        for (recipe_name, reason) in summary.invalid.iter() {
+            warn!(
+                "Kept old version of recipe \"{}\", it {}",
+                recipe_name, reason
+            );
+        }
+
+        for recipe_name in summary.skipped.iter() {
+            warn!("Skipped duplicate recipe \"{}\"", recipe_name);
+        }
+
+        source.owned = summary.owned;
+
+        recipe_events.send_batch(
+            summary
+                .updated
+                .into_iter()
+                .map(RecipeEvent::Updated)
+                .chain(summary.removed.into_iter().map(RecipeEvent::Removed)),
+        );
+    }
+}
@@ -0,0 +1,170 @@
+use std::fmt::Write;
+
+use bevy::prelude::*;
+
+use crate::{
+    ControlPanelUi, CreateBaseUi, MainMenuItem, MainMenuUI, MenuName,
+    factory_world::*,
+    plan_export::decimal,
+    solver::{LastSolution, ProductionSolution},
+};
+
+pub fn build(app: &mut App) {
+    app.add_systems(Startup, create_sensitivity_ui.after(CreateBaseUi));
+    app.add_systems(Update, show_sensitivity);
+}
+
+#[derive(Resource)]
+struct SensitivityUi {
+    report_text_entity: Entity,
+}
+
+/// A report of the shadow prices, binding constraints and cost ranges of a solution.
+///
+/// Costs are in the units of the objective it was solved for, and rounded since the
+/// tie breaking weights make their fractions unreadable.
+pub fn sensitivity_report(solution: &ProductionSolution, world: &FactoryWorld) -> String {
+    let sensitivity = &solution.sensitivity;
+    let resource_name =
+        |resource_id: ResourceId| world.get_resource_name(resource_id).unwrap_or("?");
+
+    let mut report = format!("Objective: {}\n", decimal(solution.objective));
+
+    // leaving out costs that only come from breaking ties
+    let marginal_costs: Vec<(ResourceId, String)> = sensitivity
+        .marginal_costs
+        .iter()
+        .map(|&(resource_id, cost)| (resource_id, decimal(cost)))
+        .filter(|(_, cost)| cost != "0")
+        .collect();
+
+    if !marginal_costs.is_empty() {
+        report.push_str("Cost of one more per minute:\n");
+
+        for (resource_id, cost) in marginal_costs {
+            writeln!(report, "  {}: {}", resource_name(resource_id), cost).unwrap();
+        }
+    }
+
+    if !sensitivity.binding_limits.is_empty() {
+        report.push_str("Used up sources:\n");
+
+        for &(resource_id, saving) in sensitivity.binding_limits.iter() {
+            writeln!(
+                report,
+                "  {}: one more per minute saves {}",
+                resource_name(resource_id),
+                decimal(saving)
+            )
+            .unwrap();
+
+            for &(_, target_id, extra) in sensitivity
+                .extra_outputs
+                .iter()
+                .filter(|&&(limit_id, ..)| limit_id == resource_id)
+            {
+                writeln!(
+                    report,
+                    "    or makes up to {} more {} per minute",
+                    decimal(extra),
+                    resource_name(target_id)
+                )
+                .unwrap();
+            }
+        }
+    }
+
+    report.push_str("Change in cost per machine the plan stays the same for:\n");
+
+    for cost_range in sensitivity.cost_ranges.iter() {
+        let Ok(recipe) = world.get_recipe(cost_range.recipe_id) else {
+            continue;
+        };
+
+        let used = solution
+            .multipliers
+            .iter()
+            .any(|&(recipe_id, _)| recipe_id == cost_range.recipe_id);
+
+        let more = cost_range
+            .upper
+            .map_or("any".into(), |upper| decimal(upper - cost_range.cost));
+        let less = cost_range
+            .lower
+            .map_or("any".into(), |lower| decimal(cost_range.cost - lower));
+
+        if used {
+            writeln!(
+                report,
+                "  {}: up to {} more or {} less",
+                recipe.name, more, less
+            )
+            .unwrap();
+        } else {
+            writeln!(
+                report,
+                "  {} (unused): used once {} cheaper",
+                recipe.name, less
+            )
+            .unwrap();
+        }
+    }
+
+    report
+}
+
+fn create_sensitivity_ui(
+    mut commands: Commands,
+    main_menu: Res<MainMenuUI>,
+    control_panel: Res<ControlPanelUi>,
+) {
+    let sub_menu_entity = commands
+        .spawn((Node {
+            display: Display::None,
+            flex_direction: FlexDirection::Column,
+            ..default()
+        },))
+        .set_parent(control_panel.control_panel_entity)
+        .id();
+
+    commands
+        .spawn((
+            MainMenuItem {
+                sub_menu_entity,
+                menu: MenuName::Sensitivity,
+            },
+            Text::new("Sensitivity"),
+            Node {
+                width: Val::Percent(100.0),
+                ..default()
+            },
+        ))
+        .set_parent(main_menu.main_menu_entity);
+
+    let report_text_entity = commands
+        .spawn(Text::new("Solve something first"))
+        .set_parent(sub_menu_entity)
+        .id();
+
+    commands.insert_resource(SensitivityUi { report_text_entity });
+}
+
+fn show_sensitivity(
+    last_solution: Res<LastSolution>,
+    sensitivity_ui: Res<SensitivityUi>,
+    mut text_q: Query<&mut Text>,
+    world: Res<FactoryWorld>,
+) {
+    if !last_solution.is_changed() {
+        return;
+    }
+
+    let mut report_text = text_q
+        .get_mut(sensitivity_ui.report_text_entity)
+        .expect("Should be a text");
+
+    report_text.0 = match &last_solution.0 {
+        Some(solution) => sensitivity_report(solution, world.as_ref()),
+        None => "Solve something first".into(),
+    };
+}
@@ -0,0 +1,1778 @@
+use std::collections::{HashMap, HashSet};
+
+use bevy::prelude::*;
+
+use crate::{
+    ControlPanelUi, CreateBaseUi, MainMenuItem, MainMenuUI, MenuName,
+    boundary_boxes::BoundaryBox,
+    box_links::{BoxLink, spawn_link},
+    chain_solve::LinkShare,
+    drag_plane::{DragBoxPlane, PlaneScroll, SelectedBox},
+    factory_world::*,
+    keymap::{AppAction, action_sent},
+    linear_program::{LinearProgram, LpError, LpSolution, Relation},
+    rational::Rational,
+    recipe_ui::spawn_recipe_box,
+    text_input::{FocusedTextInput, TextInput, TextInputSubmitted},
+};
+
+/// Every variable is weighted at least one over this, so plans that score the same
+/// don't run recipes or bring in resources for nothing.
+const TIE_BREAK_DIVISOR: i128 = 1_000_000;
+
+/// How many times the solver re-solves to cut down the number of recipes used.
+const RECIPE_REWEIGHT_ROUNDS: usize = 4;
+
+/// MW used by an AWESOME sink.
+const SINK_POWER: i128 = 30;
+/// Per minute an AWESOME sink takes in, fed by a single Mk.5 belt.
+const SINK_INTAKE: i128 = 780;
+
+/// Buildings and power of a plan, counted the same way for the solver, the canvas and snapshots.
+pub struct PlanTotals {
+    /// AWESOME sinks, counting part filled ones as part of a sink.
+    pub sink_count: Rational,
+    /// Machines and sinks, counting underclocked ones as part of a machine.
+    pub building_count: Rational,
+    /// MW used by the machines and sinks.
+    pub power_used: Rational,
+}
+
+/// Totals up machines, each given as how many there are and the MW they use together,
+/// and the AWESOME sinks it takes to sink `sunk` per minute.
+pub fn plan_totals(
+    machines: impl IntoIterator<Item = (Rational, Rational)>,
+    sunk: Rational,
+) -> PlanTotals {
+    let sink_count = sunk / Rational::from_integer(SINK_INTAKE);
+
+    let (machine_count, machine_power) = machines.into_iter().fold(
+        (Rational::ZERO, Rational::ZERO),
+        |(count, power), (machine_count, machine_power)| {
+            (count + machine_count, power + machine_power)
+        },
+    );
+
+    PlanTotals {
+        sink_count,
+        building_count: machine_count + sink_count,
+        power_used: machine_power + sink_count * Rational::from_integer(SINK_POWER),
+    }
+}
+
+/// How far apart boxes are placed when a solution is applied to the canvas.
+const APPLIED_BOX_SPACING: Vec2 = Vec2::new(280., 180.);
+const APPLIED_BOX_COLUMNS: usize = 4;
+
+pub fn build(app: &mut App) {
+    app.insert_resource(ByproductPolicies::default());
+    app.insert_resource(LastSolution(None));
+    app.insert_resource(SolverObjective {
+        preset: ObjectivePreset::LeastRawResources,
+        weights: ObjectiveWeights::only(ObjectiveMetric::RawResources),
+    });
+
+    app.add_systems(Startup, create_solver_ui.after(CreateBaseUi));
+    app.add_systems(
+        Update,
+        (
+            list_byproducts,
+            list_scarcities,
+            cycle_byproduct_policies,
+            cycle_objective_presets,
+            submit_objective_weights,
+            run_solver,
+            apply_solution,
+        )
+            .chain(),
+    );
+}
+
+/// What the solver may do with a resource it makes more of than is wanted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ByproductPolicy {
+    /// Everything made has to be used up by other recipes or the targets.
+    Consume,
+    /// Anything left over is sent to AWESOME sinks, which count as buildings using power.
+    Sink,
+    /// Anything left over piles up in storage, for nothing.
+    Accumulate,
+}
+
+impl ByproductPolicy {
+    pub fn name(self) -> &'static str {
+        match self {
+            ByproductPolicy::Consume => "Consume",
+            ByproductPolicy::Sink => "Sink",
+            ByproductPolicy::Accumulate => "Accumulate",
+        }
+    }
+
+    fn next(self) -> ByproductPolicy {
+        match self {
+            ByproductPolicy::Consume => ByproductPolicy::Sink,
+            ByproductPolicy::Sink => ByproductPolicy::Accumulate,
+            ByproductPolicy::Accumulate => ByproductPolicy::Consume,
+        }
+    }
+}
+
+/// The policy for each resource, resources without one may accumulate.
+#[derive(Resource, Default, Clone)]
+pub struct ByproductPolicies(pub HashMap<ResourceId, ByproductPolicy>);
+
+impl ByproductPolicies {
+    pub fn get(&self, resource_id: ResourceId) -> ByproductPolicy {
+        self.0
+            .get(&resource_id)
+            .copied()
+            .unwrap_or(ByproductPolicy::Accumulate)
+    }
+}
+
+/// What a plan has to make, and what it has to make it from.
+pub struct ProductionProblem {
+    /// Per minute of each resource the plan has to make, on top of what it uses itself.
+    pub targets: HashMap<ResourceId, Rational>,
+    /// Per minute of a resource that can be brought in at most.
+    ///
+    /// Resources no recipe has as its main product can be brought in without limit,
+    /// unless they have one.
+    pub raw_limits: HashMap<ResourceId, Rational>,
+}
+
+impl ProductionProblem {
+    pub fn target(&self, resource_id: ResourceId) -> Rational {
+        self.targets
+            .get(&resource_id)
+            .copied()
+            .unwrap_or(Rational::ZERO)
+    }
+}
+
+#[derive(Clone)]
+pub struct ProductionSolution {
+    /// How many machines run each recipe the plan uses.
+    pub multipliers: Vec<(RecipeId, Rational)>,
+    /// Per minute of each raw resource brought in.
+    pub raw_inputs: Vec<(ResourceId, Rational)>,
+    /// Per minute made beyond the targets, and what happens to it.
+    pub surpluses: Vec<(ResourceId, Rational, ByproductPolicy)>,
+    /// AWESOME sinks taking the surpluses with the sink policy, counting part filled ones as part of a sink.
+    pub sink_count: Rational,
+    /// Machines and sinks, counting underclocked ones as part of a machine.
+    pub building_count: Rational,
+    /// MW used by the machines.
+    pub power_used: Rational,
+    /// The weighted sum of the metrics the plan was solved for.
+    pub objective: Rational,
+    pub sensitivity: Sensitivity,
+}
+
+/// How the objective of a solved plan responds to changes in the problem.
+///
+/// When recipes are weighted, this is for the recipes the plan uses, which are kept fixed,
+/// and the objective it describes leaves out the recipe count.
+#[derive(Clone)]
+pub struct Sensitivity {
+    /// The resources the plan was asked to make.
+    pub targets: Vec<ResourceId>,
+    /// What making one more per minute of a resource would add to the objective, for every
+    /// resource whose balance holds the plan back, sorted by name.
+    ///
+    /// For raw resources this is what one more per minute for free would save.
+    pub marginal_costs: Vec<(ResourceId, Rational)>,
+    /// Raw resources the plan uses all of, with what one more per minute of limit would save.
+    pub binding_limits: Vec<(ResourceId, Rational)>,
+    /// How much more per minute of a target could be made with one more per minute of a used up
+    /// raw resource, making no less of the other targets, as the raw resource, target and amount.
+    pub extra_outputs: Vec<(ResourceId, ResourceId, Rational)>,
+    /// How far each recipe's cost per machine can move before a different plan would be better.
+    pub cost_ranges: Vec<RecipeCostRange>,
+}
+
+#[derive(Clone)]
+pub struct RecipeCostRange {
+    pub recipe_id: RecipeId,
+    /// The cost per machine in the objective now.
+    pub cost: Rational,
+    /// `None` where there's no limit.
+    pub lower: Option<Rational>,
+    pub upper: Option<Rational>,
+}
+
+impl Sensitivity {
+    pub fn marginal_cost(&self, resource_id: ResourceId) -> Rational {
+        self.marginal_costs
+            .iter()
+            .find(|&&(id, _)| id == resource_id)
+            .map_or(Rational::ZERO, |&(_, cost)| cost)
+    }
+}
+
+/// The last plan the solver came up with.
+#[derive(Resource)]
+pub struct LastSolution(pub Option<ProductionSolution>);
+
+/// A measure of a plan the solver can keep down.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ObjectiveMetric {
+    /// Machines, counting underclocked ones as part of a machine.
+    Buildings,
+    /// MW used by the machines.
+    Power,
+    /// Raw resources brought in per minute, each scaled by its scarcity.
+    RawResources,
+    /// Distinct recipes used.
+    Recipes,
+}
+
+impl ObjectiveMetric {
+    pub const ALL: [ObjectiveMetric; 4] = [
+        ObjectiveMetric::Buildings,
+        ObjectiveMetric::Power,
+        ObjectiveMetric::RawResources,
+        ObjectiveMetric::Recipes,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ObjectiveMetric::Buildings => "Buildings",
+            ObjectiveMetric::Power => "Power",
+            ObjectiveMetric::RawResources => "Raw resources",
+            ObjectiveMetric::Recipes => "Recipes",
+        }
+    }
+}
+
+/// How much each metric counts against a plan, the solver keeps their weighted sum as low as it can.
+#[derive(Clone)]
+pub struct ObjectiveWeights {
+    pub buildings: Rational,
+    pub power: Rational,
+    pub raw_resources: Rational,
+    pub recipes: Rational,
+    /// How much a unit of each raw resource counts, resources without one count 1.
+    pub scarcity: HashMap<ResourceId, Rational>,
+}
+
+impl ObjectiveWeights {
+    /// Only counts the one metric.
+    pub fn only(metric: ObjectiveMetric) -> Self {
+        let mut weights = ObjectiveWeights {
+            buildings: Rational::ZERO,
+            power: Rational::ZERO,
+            raw_resources: Rational::ZERO,
+            recipes: Rational::ZERO,
+            scarcity: HashMap::new(),
+        };
+
+        weights.set(metric, Rational::ONE);
+        weights
+    }
+
+    pub fn get(&self, metric: ObjectiveMetric) -> Rational {
+        match metric {
+            ObjectiveMetric::Buildings => self.buildings,
+            ObjectiveMetric::Power => self.power,
+            ObjectiveMetric::RawResources => self.raw_resources,
+            ObjectiveMetric::Recipes => self.recipes,
+        }
+    }
+
+    pub fn set(&mut self, metric: ObjectiveMetric, weight: Rational) {
+        match metric {
+            ObjectiveMetric::Buildings => self.buildings = weight,
+            ObjectiveMetric::Power => self.power = weight,
+            ObjectiveMetric::RawResources => self.raw_resources = weight,
+            ObjectiveMetric::Recipes => self.recipes = weight,
+        }
+    }
+
+    pub fn scarcity(&self, resource_id: ResourceId) -> Rational {
+        self.scarcity
+            .get(&resource_id)
+            .copied()
+            .unwrap_or(Rational::ONE)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ObjectivePreset {
+    LeastRawResources,
+    FewestBuildings,
+    LeastPower,
+    FewestRecipes,
+    /// Whatever weights were typed in.
+    Custom,
+}
+
+impl ObjectivePreset {
+    pub fn name(self) -> &'static str {
+        match self {
+            ObjectivePreset::LeastRawResources => "Least raw resources",
+            ObjectivePreset::FewestBuildings => "Fewest buildings",
+            ObjectivePreset::LeastPower => "Least power",
+            ObjectivePreset::FewestRecipes => "Fewest recipes",
+            ObjectivePreset::Custom => "Custom",
+        }
+    }
+
+    /// The metric the preset counts on its own, nothing for custom weights.
+    pub fn metric(self) -> Option<ObjectiveMetric> {
+        match self {
+            ObjectivePreset::LeastRawResources => Some(ObjectiveMetric::RawResources),
+            ObjectivePreset::FewestBuildings => Some(ObjectiveMetric::Buildings),
+            ObjectivePreset::LeastPower => Some(ObjectiveMetric::Power),
+            ObjectivePreset::FewestRecipes => Some(ObjectiveMetric::Recipes),
+            ObjectivePreset::Custom => None,
+        }
+    }
+
+    fn next(self) -> ObjectivePreset {
+        match self {
+            ObjectivePreset::LeastRawResources => ObjectivePreset::FewestBuildings,
+            ObjectivePreset::FewestBuildings => ObjectivePreset::LeastPower,
+            ObjectivePreset::LeastPower => ObjectivePreset::FewestRecipes,
+            ObjectivePreset::FewestRecipes => ObjectivePreset::Custom,
+            ObjectivePreset::Custom => ObjectivePreset::LeastRawResources,
+        }
+    }
+}
+
+/// What the solver is asked to keep down.
+#[derive(Resource)]
+pub struct SolverObjective {
+    pub preset: ObjectivePreset,
+    pub weights: ObjectiveWeights,
+}
+
+/// Resources that some recipe has as its main product.
+///
+/// Resources only given back as byproducts, like the water from aluminum scrap,
+/// still have to be brought in from elsewhere when there isn't enough.
+fn main_products(world: &FactoryWorld) -> HashSet<ResourceId> {
+    world
+        .iter_recipes()
+        .filter_map(|(_, recipe)| recipe.products.first().map(|&(resource_id, _)| resource_id))
+        .collect()
+}
+
+/// The linear program for a production problem, with where each recipe and raw resource is in it.
+///
+/// There's a multiplier variable for each recipe, then one for how much of each raw resource is
+/// brought in, then one for how much of each resource with the sink policy is sunk.
+struct ProductionModel<'w> {
+    program: LinearProgram,
+    recipes: Vec<(RecipeId, &'w Recipe)>,
+    raw_resources: Vec<ResourceId>,
+    sunk_resources: Vec<ResourceId>,
+    /// Raw resources with a limit, in the order of their constraints, which come first.
+    limits: Vec<ResourceId>,
+    /// The coefficients of the balance of every resource, sorted by name.
+    balances: Vec<(ResourceId, Vec<(usize, Rational)>)>,
+    power_id: Option<ResourceId>,
+}
+
+impl<'w> ProductionModel<'w> {
+    fn new(
+        problem: &ProductionProblem,
+        policies: &ByproductPolicies,
+        world: &'w FactoryWorld,
+        usable: impl Fn(RecipeId) -> bool,
+    ) -> Self {
+        let mut recipes: Vec<(RecipeId, &Recipe)> = world
+            .iter_recipes()
+            .filter(|&(recipe_id, _)| usable(recipe_id))
+            .collect();
+        recipes.sort_by(|(_, a), (_, b)| a.name.cmp(&b.name));
+
+        // counting recipes that can't be used, so what they make can't be brought in instead
+        let made = main_products(world);
+
+        let mut resources: Vec<ResourceId> = Vec::new();
+
+        for resource_id in recipes
+            .iter()
+            .flat_map(|(_, recipe)| recipe.ingredients.iter().chain(recipe.products.iter()))
+            .map(|&(resource_id, _)| resource_id)
+            .chain(problem.targets.keys().copied())
+            .chain(problem.raw_limits.keys().copied())
+        {
+            if !resources.contains(&resource_id) {
+                resources.push(resource_id);
+            }
+        }
+
+        resources.sort_by_key(|&resource_id| world.get_resource_name(resource_id));
+
+        let raw_resources: Vec<ResourceId> = resources
+            .iter()
+            .copied()
+            .filter(|resource_id| {
+                !made.contains(resource_id) || problem.raw_limits.contains_key(resource_id)
+            })
+            .collect();
+
+        let sunk_resources: Vec<ResourceId> = resources
+            .iter()
+            .copied()
+            .filter(|&resource_id| policies.get(resource_id) == ByproductPolicy::Sink)
+            .collect();
+
+        let power_id = world.find_resource_id(POWER_RESOURCE);
+
+        let mut model = ProductionModel {
+            program: LinearProgram::new(recipes.len() + raw_resources.len() + sunk_resources.len()),
+            recipes,
+            raw_resources,
+            sunk_resources,
+            limits: Vec::new(),
+            balances: Vec::with_capacity(resources.len()),
+            power_id,
+        };
+
+        for (raw_index, resource_id) in model.raw_resources.iter().enumerate() {
+            if let Some(&limit) = problem.raw_limits.get(resource_id) {
+                model.program.add_constraint(
+                    vec![(model.import_variable(raw_index), Rational::ONE)],
+                    Relation::LessOrEqual,
+                    limit,
+                );
+                model.limits.push(*resource_id);
+            }
+        }
+
+        for resource_id in resources {
+            let mut coefficients: Vec<(usize, Rational)> = model
+                .recipes
+                .iter()
+                .enumerate()
+                .map(|(recipe_index, (_, recipe))| (recipe_index, recipe.net_rate(resource_id)))
+                .filter(|(_, rate)| !rate.is_zero())
+                .collect();
+
+            if let Some(raw_index) = model.raw_resources.iter().position(|&id| id == resource_id) {
+                coefficients.push((model.import_variable(raw_index), Rational::ONE));
+            }
+
+            if let Some(variable) = model.sink_variable(resource_id) {
+                coefficients.push((variable, -Rational::ONE));
+            }
+
+            // the sinks run on power like any other building
+            if Some(resource_id) == model.power_id {
+                let sink_power = -Rational::new(SINK_POWER * 60, SINK_INTAKE);
+
+                coefficients.extend(
+                    model
+                        .sunk_resources
+                        .iter()
+                        .filter_map(|&sunk_id| model.sink_variable(sunk_id))
+                        .map(|variable| (variable, sink_power)),
+                );
+            }
+
+            // what's sunk is accounted for by its variable, so the balance has to be exact
+            let relation = match policies.get(resource_id) {
+                ByproductPolicy::Consume | ByproductPolicy::Sink => Relation::Equal,
+                ByproductPolicy::Accumulate => Relation::GreaterOrEqual,
+            };
+
+            model.program.add_constraint(
+                coefficients.clone(),
+                relation,
+                problem.target(resource_id),
+            );
+            model.balances.push((resource_id, coefficients));
+        }
+
+        model
+    }
+
+    fn import_variable(&self, raw_index: usize) -> usize {
+        self.recipes.len() + raw_index
+    }
+
+    fn sink_variable(&self, resource_id: ResourceId) -> Option<usize> {
+        let sink_index = self
+            .sunk_resources
+            .iter()
+            .position(|&id| id == resource_id)?;
+
+        Some(self.recipes.len() + self.raw_resources.len() + sink_index)
+    }
+
+    /// Per minute the solution sends to AWESOME sinks.
+    fn sunk(&self, values: &[Rational]) -> Rational {
+        self.sunk_resources
+            .iter()
+            .filter_map(|&resource_id| self.sink_variable(resource_id))
+            .map(|variable| values[variable])
+            .sum()
+    }
+
+    /// MW used by one machine running the recipe.
+    fn power_used(&self, recipe: &Recipe) -> Rational {
+        recipe
+            .ingredients
+            .iter()
+            .filter(|&&(resource_id, _)| Some(resource_id) == self.power_id)
+            .map(|&(_, rate)| rate / Rational::from_integer(60))
+            .sum()
+    }
+
+    /// Weights the variables, with `recipe_costs` charged on top for each machine of each recipe.
+    fn set_objective(&mut self, weights: &ObjectiveWeights, recipe_costs: &[Rational]) {
+        let tie_break_weight = Rational::new(1, TIE_BREAK_DIVISOR);
+
+        for (recipe_index, &(_, recipe)) in self.recipes.iter().enumerate() {
+            self.program.objective[recipe_index] = tie_break_weight
+                + weights.buildings
+                + weights.power * self.power_used(recipe)
+                + recipe_costs[recipe_index];
+        }
+
+        for raw_index in 0..self.raw_resources.len() {
+            let resource_id = self.raw_resources[raw_index];
+
+            let weight = if Some(resource_id) == self.power_id {
+                Rational::ZERO
+            } else {
+                weights.raw_resources * weights.scarcity(resource_id)
+            };
+
+            let variable = self.import_variable(raw_index);
+            self.program.objective[variable] = tie_break_weight + weight;
+        }
+
+        let sink_weight = (weights.buildings + weights.power * Rational::from_integer(SINK_POWER))
+            / Rational::from_integer(SINK_INTAKE);
+
+        for resource_id in self.sunk_resources.clone() {
+            let variable = self
+                .sink_variable(resource_id)
+                .expect("Sunk resources have a variable");
+            self.program.objective[variable] = tie_break_weight + sink_weight;
+        }
+    }
+
+    fn solution(
+        &self,
+        lp_solution: &LpSolution,
+        problem: &ProductionProblem,
+        policies: &ByproductPolicies,
+        weights: &ObjectiveWeights,
+    ) -> ProductionSolution {
+        let values = &lp_solution.values;
+
+        let multipliers: Vec<(RecipeId, Rational)> = self
+            .recipes
+            .iter()
+            .zip(values.iter())
+            .filter(|(_, value)| value.is_positive())
+            .map(|(&(recipe_id, _), &value)| (recipe_id, value))
+            .collect();
+
+        let raw_inputs: Vec<(ResourceId, Rational)> = self
+            .raw_resources
+            .iter()
+            .enumerate()
+            .map(|(raw_index, &resource_id)| (resource_id, values[self.import_variable(raw_index)]))
+            .filter(|(_, rate)| rate.is_positive())
+            .collect();
+
+        let surpluses = self
+            .balances
+            .iter()
+            .filter_map(|(resource_id, coefficients)| {
+                let sink_variable = self.sink_variable(*resource_id);
+
+                // what's sunk is still a surplus
+                let net: Rational = coefficients
+                    .iter()
+                    .filter(|&&(variable, _)| Some(variable) != sink_variable)
+                    .map(|&(variable, coefficient)| coefficient * values[variable])
+                    .sum();
+
+                let surplus = net - problem.target(*resource_id);
+
+                surplus
+                    .is_positive()
+                    .then(|| (*resource_id, surplus, policies.get(*resource_id)))
+            })
+            .collect();
+
+        let PlanTotals {
+            sink_count,
+            building_count,
+            power_used,
+        } = plan_totals(
+            self.recipes
+                .iter()
+                .zip(values.iter())
+                .map(|(&(_, recipe), &value)| (value, self.power_used(recipe) * value)),
+            self.sunk(values),
+        );
+
+        let weighted_raw_inputs: Rational = raw_inputs
+            .iter()
+            .filter(|&&(resource_id, _)| Some(resource_id) != self.power_id)
+            .map(|&(resource_id, rate)| weights.scarcity(resource_id) * rate)
+            .sum();
+
+        let objective = weights.buildings * building_count
+            + weights.power * power_used
+            + weights.raw_resources * weighted_raw_inputs
+            + weights.recipes * Rational::from_integer(multipliers.len() as i128);
+
+        ProductionSolution {
+            multipliers,
+            raw_inputs,
+            surpluses,
+            sink_count,
+            building_count,
+            power_used,
+            objective,
+            sensitivity: self.sensitivity(lp_solution, problem),
+        }
+    }
+
+    fn sensitivity(&self, lp_solution: &LpSolution, problem: &ProductionProblem) -> Sensitivity {
+        let (limit_duals, balance_duals) = lp_solution.duals.split_at(self.limits.len());
+
+        let mut targets: Vec<ResourceId> = problem.targets.keys().copied().collect();
+        targets.sort_by_key(|&resource_id| {
+            self.balances
+                .iter()
+                .position(|&(balance_id, _)| balance_id == resource_id)
+        });
+
+        let marginal_costs = self
+            .balances
+            .iter()
+            .zip(balance_duals.iter())
+            .filter(|(_, dual)| !dual.is_zero())
+            .map(|(&(resource_id, _), &dual)| (resource_id, dual))
+            .collect();
+
+        let binding_limits = self
+            .limits
+            .iter()
+            .zip(limit_duals.iter())
+            .filter_map(|(&resource_id, &dual)| {
+                let raw_index = self
+                    .raw_resources
+                    .iter()
+                    .position(|&id| id == resource_id)?;
+                let imported = lp_solution.values[self.import_variable(raw_index)];
+
+                (Some(&imported) == problem.raw_limits.get(&resource_id))
+                    .then_some((resource_id, -dual))
+            })
+            .collect();
+
+        let cost_ranges = self
+            .recipes
+            .iter()
+            .enumerate()
+            .map(|(recipe_index, &(recipe_id, _))| {
+                let (lower, upper) = lp_solution.cost_ranges[recipe_index];
+
+                RecipeCostRange {
+                    recipe_id,
+                    cost: self.program.objective[recipe_index],
+                    lower,
+                    upper,
+                }
+            })
+            .collect();
+
+        Sensitivity {
+            targets,
+            marginal_costs,
+            binding_limits,
+            extra_outputs: Vec::new(),
+            cost_ranges,
+        }
+    }
+}
+
+/// Finds the recipes to run to meet the targets, keeping the weighted objective as low as it can.
+///
+/// Recipes can feed each other in cycles, every resource only has to balance overall.
+/// The number of recipes isn't linear, so it's kept down by solving a few times. The first solve
+/// charges the recipe weight per machine, and each later one charges it per machine over one more
+/// than the machines the recipe last ran, rounded up, so recipes that barely ran get expensive.
+/// The best plan found is solved once more with just its recipes, without those charges.
+///
+/// Locked recipes aren't used.
+pub fn solve_production(
+    problem: &ProductionProblem,
+    policies: &ByproductPolicies,
+    weights: &ObjectiveWeights,
+    world: &FactoryWorld,
+) -> Result<ProductionSolution, LpError> {
+    let usable = |recipe_id| !world.is_recipe_locked(recipe_id);
+
+    let mut solution = solve_with_recipes(problem, policies, weights, world, usable)?;
+    solution.sensitivity.extra_outputs = extra_outputs(
+        problem,
+        policies,
+        world,
+        usable,
+        &solution.sensitivity.binding_limits,
+    );
+
+    Ok(solution)
+}
+
+/// Works out [`Sensitivity::extra_outputs`] by making as much of each target as the limits allow,
+/// then again with each used up limit raised by one.
+///
+/// Targets that could be made without limit are left out.
+fn extra_outputs(
+    problem: &ProductionProblem,
+    policies: &ByproductPolicies,
+    world: &FactoryWorld,
+    usable: impl Fn(RecipeId) -> bool,
+    binding_limits: &[(ResourceId, Rational)],
+) -> Vec<(ResourceId, ResourceId, Rational)> {
+    let mut extra_outputs = Vec::new();
+
+    if binding_limits.is_empty() {
+        return extra_outputs;
+    }
+
+    let mut targets: Vec<ResourceId> = problem.targets.keys().copied().collect();
+    targets.sort_by_key(|&resource_id| world.get_resource_name(resource_id));
+
+    for target_id in targets {
+        let mut model = ProductionModel::new(problem, policies, world, &usable);
+
+        let Some(balance_index) = model
+            .balances
+            .iter()
+            .position(|&(resource_id, _)| resource_id == target_id)
+        else {
+            continue;
+        };
+
+        // a variable for everything made beyond the target, which is all that counts
+        let extra_variable = model.program.variable_count();
+        model.program.objective = vec![Rational::ZERO; extra_variable];
+        model.program.objective.push(-Rational::ONE);
+        model.program.constraints[model.limits.len() + balance_index]
+            .coefficients
+            .push((extra_variable, -Rational::ONE));
+
+        let Ok(most) = model.program.solve() else {
+            continue;
+        };
+
+        for &(limit_id, _) in binding_limits {
+            let Some(limit_index) = model.limits.iter().position(|&id| id == limit_id) else {
+                continue;
+            };
+
+            let limit = model.program.constraints[limit_index].rhs;
+            model.program.constraints[limit_index].rhs = limit + Rational::ONE;
+            let raised = model.program.solve();
+            model.program.constraints[limit_index].rhs = limit;
+
+            if let Ok(raised) = raised {
+                let extra = most.objective - raised.objective;
+
+                if extra.is_positive() {
+                    extra_outputs.push((limit_id, target_id, extra));
+                }
+            }
+        }
+    }
+
+    extra_outputs
+}
+
+/// Like [`solve_production`], but only using the recipes that are `usable`, locked or not.
+pub fn solve_with_recipes(
+    problem: &ProductionProblem,
+    policies: &ByproductPolicies,
+    weights: &ObjectiveWeights,
+    world: &FactoryWorld,
+    usable: impl Fn(RecipeId) -> bool,
+) -> Result<ProductionSolution, LpError> {
+    let mut model = ProductionModel::new(problem, policies, world, usable);
+
+    let rounds = if weights.recipes.is_positive() {
+        RECIPE_REWEIGHT_ROUNDS
+    } else {
+        1
+    };
+
+    let mut recipe_costs = vec![weights.recipes; model.recipes.len()];
+    let mut best: Option<ProductionSolution> = None;
+
+    for _ in 0..rounds {
+        model.set_objective(weights, &recipe_costs);
+
+        let lp_solution = model.program.solve()?;
+        let solution = model.solution(&lp_solution, problem, policies, weights);
+        let values = lp_solution.values;
+
+        // rounded up to whole machines, so the costs don't get unwieldy denominators
+        for (cost, &value) in recipe_costs.iter_mut().zip(values.iter()) {
+            *cost = weights.recipes / Rational::from_integer((value + Rational::ONE).ceil());
+        }
+
+        if best
+            .as_ref()
+            .is_none_or(|best| solution.objective < best.objective)
+        {
+            best = Some(solution);
+        }
+    }
+
+    let best = best.expect("Should have solved at least once");
+
+    if rounds == 1 {
+        return Ok(best);
+    }
+
+    // the heuristic recipe costs would skew the sensitivity, so the plan is solved once more
+    // with the recipes it uses and only the linear metrics, which can only make it better
+    let used: HashSet<RecipeId> = best
+        .multipliers
+        .iter()
+        .map(|&(recipe_id, _)| recipe_id)
+        .collect();
+
+    let mut model = ProductionModel::new(problem, policies, world, |recipe_id| {
+        used.contains(&recipe_id)
+    });
+    model.set_objective(weights, &vec![Rational::ZERO; model.recipes.len()]);
+
+    let lp_solution = model.program.solve()?;
+
+    Ok(model.solution(&lp_solution, problem, policies, weights))
+}
+
+/// Output boxes on the canvas are the targets, and source boxes limit the raw resources.
+///
+/// AWESOME sink and storage boxes take whatever is left over, so they don't ask for anything.
+pub fn canvas_problem<'a>(
+    boundary_boxes: impl Iterator<Item = &'a BoundaryBox>,
+) -> ProductionProblem {
+    let mut problem = ProductionProblem {
+        targets: HashMap::new(),
+        raw_limits: HashMap::new(),
+    };
+
+    for boundary_box in boundary_boxes {
+        let totals = if boundary_box.is_source() {
+            &mut problem.raw_limits
+        } else if boundary_box.is_output() {
+            &mut problem.targets
+        } else {
+            continue;
+        };
+
+        *totals
+            .entry(boundary_box.resource_id)
+            .or_insert(Rational::ZERO) += boundary_box.actual_rate();
+    }
+
+    problem
+}
+
+/// A plain text report of a solution, a line per recipe, raw input and surplus.
+pub fn solution_report(solution: &ProductionSolution, world: &FactoryWorld) -> String {
+    let resource_name =
+        |resource_id: ResourceId| world.get_resource_name(resource_id).unwrap_or("?");
+
+    let mut report = format!(
+        "{} buildings, {} MW, {} recipes\nRecipes:\n",
+        solution.building_count,
+        solution.power_used,
+        solution.multipliers.len()
+    );
+
+    for &(recipe_id, multiplier) in solution.multipliers.iter() {
+        let recipe_name = world
+            .get_recipe(recipe_id)
+            .map_or("?", |recipe| recipe.name.as_str());
+
+        report.push_str(&format!("  {} x{}\n", recipe_name, multiplier));
+    }
+
+    report.push_str("Raw inputs per minute:\n");
+
+    for &(resource_id, rate) in solution.raw_inputs.iter() {
+        report.push_str(&format!("  {} {}\n", resource_name(resource_id), rate));
+    }
+
+    if !solution.surpluses.is_empty() {
+        report.push_str("Surplus per minute:\n");
+
+        if solution.sink_count.is_positive() {
+            report.push_str(&format!("  into {} AWESOME sinks\n", solution.sink_count));
+        }
+
+        for &(resource_id, rate, policy) in solution.surpluses.iter() {
+            let fate = match policy {
+                ByproductPolicy::Sink => " (sunk)",
+                _ => "",
+            };
+
+            report.push_str(&format!(
+                "  {} {}{}\n",
+                resource_name(resource_id),
+                rate,
+                fate
+            ));
+        }
+    }
+
+    report
+}
+
+/// Lines for the recipes a solution added and dropped compared to the one before it.
+pub fn recipe_changes(
+    previous: &ProductionSolution,
+    solution: &ProductionSolution,
+    world: &FactoryWorld,
+) -> String {
+    let recipe_ids = |solution: &ProductionSolution| -> HashSet<RecipeId> {
+        solution
+            .multipliers
+            .iter()
+            .map(|&(recipe_id, _)| recipe_id)
+            .collect()
+    };
+
+    let (previous_ids, solution_ids) = (recipe_ids(previous), recipe_ids(solution));
+
+    let mut changes: Vec<(&str, char)> = solution_ids
+        .difference(&previous_ids)
+        .map(|&recipe_id| (recipe_id, '+'))
+        .chain(
+            previous_ids
+                .difference(&solution_ids)
+                .map(|&recipe_id| (recipe_id, '-')),
+        )
+        .filter_map(|(recipe_id, sign)| {
+            Some((world.get_recipe(recipe_id).ok()?.name.as_str(), sign))
+        })
+        .collect();
+
+    changes.sort();
+
+    changes
+        .into_iter()
+        .map(|(recipe_name, sign)| format!("  {} {}\n", sign, recipe_name))
+        .collect()
+}
+
+#[derive(Resource)]
+struct SolverUi {
+    preset_button_entity: Entity,
+    byproduct_list_entity: Entity,
+    scarcity_list_entity: Entity,
+    report_text_entity: Entity,
+}
+
+#[derive(Component)]
+#[require(Button)]
+struct ObjectivePresetButton;
+
+#[derive(Component)]
+struct ObjectiveWeightInput {
+    metric: ObjectiveMetric,
+}
+
+#[derive(Component)]
+struct ScarcityInput {
+    resource_id: ResourceId,
+}
+
+#[derive(Component)]
+#[require(Button)]
+struct SolveButton;
+
+#[derive(Component)]
+#[require(Button)]
+struct ApplySolutionButton;
+
+#[derive(Component)]
+#[require(Button)]
+struct ByproductPolicyButton {
+    resource_id: ResourceId,
+}
+
+fn create_solver_ui(
+    mut commands: Commands,
+    main_menu: Res<MainMenuUI>,
+    control_panel: Res<ControlPanelUi>,
+) {
+    let sub_menu_entity = commands
+        .spawn((Node {
+            display: Display::None,
+            flex_direction: FlexDirection::Column,
+            ..default()
+        },))
+        .set_parent(control_panel.control_panel_entity)
+        .id();
+
+    commands
+        .spawn((
+            MainMenuItem {
+                sub_menu_entity,
+                menu: MenuName::Solver,
+            },
+            Text::new("Solver"),
+            Node {
+                width: Val::Percent(100.0),
+                ..default()
+            },
+        ))
+        .set_parent(main_menu.main_menu_entity);
+
+    commands
+        .spawn(Text::new("Solves for the sinks, within the sources"))
+        .set_parent(sub_menu_entity);
+
+    let preset_button_entity = commands
+        .spawn((
+            ObjectivePresetButton,
+            Text::new(preset_button_text(ObjectivePreset::LeastRawResources)),
+        ))
+        .set_parent(sub_menu_entity)
+        .id();
+
+    let default_weights = ObjectiveWeights::only(ObjectiveMetric::RawResources);
+
+    for metric in ObjectiveMetric::ALL {
+        commands
+            .spawn(Node {
+                flex_direction: FlexDirection::Row,
+                column_gap: Val::Px(8.),
+                ..default()
+            })
+            .with_children(|builder| {
+                builder.spawn(Text::new(metric.name()));
+
+                builder.spawn((
+                    ObjectiveWeightInput { metric },
+                    TextInput {
+                        value: default_weights.get(metric).to_string(),
+                        placeholder: "weight".into(),
+                    },
+                    BackgroundColor(Color::BLACK),
+                ));
+            })
+            .set_parent(sub_menu_entity);
+    }
+
+    commands
+        .spawn((SolveButton, Text::new("Solve")))
+        .set_parent(sub_menu_entity);
+
+    commands
+        .spawn((ApplySolutionButton, Text::new("Apply to Canvas")))
+        .set_parent(sub_menu_entity);
+
+    let report_text_entity = commands
+        .spawn(Text::default())
+        .set_parent(sub_menu_entity)
+        .id();
+
+    commands
+        .spawn(Text::new("Byproducts:"))
+        .set_parent(sub_menu_entity);
+
+    let byproduct_list_entity = commands
+        .spawn(Node {
+            flex_direction: FlexDirection::Column,
+            ..default()
+        })
+        .set_parent(sub_menu_entity)
+        .id();
+
+    commands
+        .spawn(Text::new("Scarcity of raw resources:"))
+        .set_parent(sub_menu_entity);
+
+    let scarcity_list_entity = commands
+        .spawn(Node {
+            flex_direction: FlexDirection::Column,
+            ..default()
+        })
+        .set_parent(sub_menu_entity)
+        .id();
+
+    commands.insert_resource(SolverUi {
+        preset_button_entity,
+        byproduct_list_entity,
+        scarcity_list_entity,
+        report_text_entity,
+    });
+}
+
+fn preset_button_text(preset: ObjectivePreset) -> String {
+    format!("Objective: {}", preset.name())
+}
+
+fn byproduct_button_text(resource_name: &str, policy: ByproductPolicy) -> String {
+    format!("{}: {}", resource_name, policy.name())
+}
+
+/// Lists a policy button for every resource made alongside something else.
+fn list_byproducts(
+    mut commands: Commands,
+    solver_ui: Res<SolverUi>,
+    policies: Res<ByproductPolicies>,
+    world: Res<FactoryWorld>,
+) {
+    if !world.is_changed() {
+        return;
+    }
+
+    commands
+        .entity(solver_ui.byproduct_list_entity)
+        .despawn_descendants();
+
+    let mut byproducts: Vec<(&str, ResourceId)> = Vec::new();
+
+    for (_, recipe) in world.iter_recipes() {
+        if recipe.products.len() < 2 {
+            continue;
+        }
+
+        for &(resource_id, _) in recipe.products.iter() {
+            let Some(resource_name) = world.get_resource_name(resource_id) else {
+                continue;
+            };
+
+            if !byproducts.contains(&(resource_name, resource_id)) {
+                byproducts.push((resource_name, resource_id));
+            }
+        }
+    }
+
+    byproducts.sort_by_key(|&(resource_name, _)| resource_name);
+
+    for (resource_name, resource_id) in byproducts {
+        commands
+            .spawn((
+                ByproductPolicyButton { resource_id },
+                Text::new(byproduct_button_text(
+                    resource_name,
+                    policies.get(resource_id),
+                )),
+            ))
+            .set_parent(solver_ui.byproduct_list_entity);
+    }
+}
+
+fn cycle_byproduct_policies(
+    mut button_q: Query<(&Interaction, &ByproductPolicyButton, &mut Text), Changed<Interaction>>,
+    mut policies: ResMut<ByproductPolicies>,
+    world: Res<FactoryWorld>,
+) {
+    for (interaction, button, mut text) in button_q.iter_mut() {
+        let Interaction::Pressed = interaction else {
+            continue;
+        };
+
+        let policy = policies.get(button.resource_id).next();
+        policies.0.insert(button.resource_id, policy);
+
+        let resource_name = world.get_resource_name(button.resource_id).unwrap_or("?");
+        text.0 = byproduct_button_text(resource_name, policy);
+    }
+}
+
+/// Lists a scarcity input for every raw resource, apart from power.
+fn list_scarcities(
+    mut commands: Commands,
+    solver_ui: Res<SolverUi>,
+    objective: Res<SolverObjective>,
+    world: Res<FactoryWorld>,
+) {
+    if !world.is_changed() {
+        return;
+    }
+
+    commands
+        .entity(solver_ui.scarcity_list_entity)
+        .despawn_descendants();
+
+    let made = main_products(world.as_ref());
+
+    let mut raw_resources: Vec<(&str, ResourceId)> = Vec::new();
+
+    for (_, recipe) in world.iter_recipes() {
+        for &(resource_id, _) in recipe.ingredients.iter() {
+            let Some(resource_name) = world.get_resource_name(resource_id) else {
+                continue;
+            };
+
+            if !made.contains(&resource_id)
+                && resource_name != POWER_RESOURCE
+                && !raw_resources.contains(&(resource_name, resource_id))
+            {
+                raw_resources.push((resource_name, resource_id));
+            }
+        }
+    }
+
+    raw_resources.sort_by_key(|&(resource_name, _)| resource_name);
+
+    for (resource_name, resource_id) in raw_resources {
+        commands
+            .spawn(Node {
+                flex_direction: FlexDirection::Row,
+                column_gap: Val::Px(8.),
+                ..default()
+            })
+            .with_children(|builder| {
+                builder.spawn(Text::new(resource_name));
+
+                builder.spawn((
+                    ScarcityInput { resource_id },
+                    TextInput {
+                        value: objective.weights.scarcity(resource_id).to_string(),
+                        placeholder: "scarcity".into(),
+                    },
+                    BackgroundColor(Color::BLACK),
+                ));
+            })
+            .set_parent(solver_ui.scarcity_list_entity);
+    }
+}
+
+/// Switches to the next preset, filling in its weights.
+fn cycle_objective_presets(
+    button_q: Query<&Interaction, (Changed<Interaction>, With<ObjectivePresetButton>)>,
+    solver_ui: Res<SolverUi>,
+    mut text_q: Query<&mut Text>,
+    mut weight_input_q: Query<(&ObjectiveWeightInput, &mut TextInput)>,
+    mut objective: ResMut<SolverObjective>,
+) {
+    for interaction in button_q.iter() {
+        let Interaction::Pressed = interaction else {
+            continue;
+        };
+
+        objective.preset = objective.preset.next();
+
+        if let Some(metric) = objective.preset.metric() {
+            let scarcity = std::mem::take(&mut objective.weights.scarcity);
+
+            objective.weights = ObjectiveWeights {
+                scarcity,
+                ..ObjectiveWeights::only(metric)
+            };
+        }
+
+        for (weight_input, mut text_input) in weight_input_q.iter_mut() {
+            text_input.value = objective.weights.get(weight_input.metric).to_string();
+        }
+
+        let mut preset_text = text_q
+            .get_mut(solver_ui.preset_button_entity)
+            .expect("Should be a text");
+
+        preset_text.0 = preset_button_text(objective.preset);
+    }
+}
+
+/// Typing in a weight makes the objective custom.
+fn submit_objective_weights(
+    mut submitted_events: EventReader<TextInputSubmitted>,
+    mut input_q: Query<(
+        Option<&ObjectiveWeightInput>,
+        Option<&ScarcityInput>,
+        &mut TextInput,
+    )>,
+    solver_ui: Res<SolverUi>,
+    mut text_q: Query<&mut Text>,
+    mut objective: ResMut<SolverObjective>,
+    mut focused: ResMut<FocusedTextInput>,
+) {
+    for event in submitted_events.read() {
+        let Ok((weight_input, scarcity_input, mut text_input)) = input_q.get_mut(event.entity)
+        else {
+            continue;
+        };
+
+        let weight = event
+            .value
+            .trim()
+            .parse::<Rational>()
+            .ok()
+            .filter(|weight| !weight.is_negative());
+
+        if let Some(weight_input) = weight_input {
+            if let Some(weight) = weight {
+                objective.weights.set(weight_input.metric, weight);
+                objective.preset = ObjectivePreset::Custom;
+            } else {
+                info!("Invalid weight \"{}\"", event.value);
+            }
+
+            text_input.value = objective.weights.get(weight_input.metric).to_string();
+        } else if let Some(scarcity_input) = scarcity_input {
+            if let Some(weight) = weight {
+                objective
+                    .weights
+                    .scarcity
+                    .insert(scarcity_input.resource_id, weight);
+            } else {
+                info!("Invalid scarcity \"{}\"", event.value);
+            }
+
+            text_input.value = objective
+                .weights
+                .scarcity(scarcity_input.resource_id)
+                .to_string();
+        } else {
+            continue;
+        }
+
+        let mut preset_text = text_q
+            .get_mut(solver_ui.preset_button_entity)
+            .expect("Should be a text");
+
+        preset_text.0 = preset_button_text(objective.preset);
+
+        focused.0 = None;
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn run_solver(
+    button_q: Query<&Interaction, (Changed<Interaction>, With<SolveButton>)>,
+    mut actions: EventReader<AppAction>,
+    boundary_box_q: Query<&BoundaryBox>,
+    solver_ui: Res<SolverUi>,
+    mut text_q: Query<&mut Text>,
+    policies: Res<ByproductPolicies>,
+    objective: Res<SolverObjective>,
+    mut last_solution: ResMut<LastSolution>,
+    world: Res<FactoryWorld>,
+) {
+    let pressed = button_q
+        .iter()
+        .any(|interaction| *interaction == Interaction::Pressed);
+
+    if !action_sent(&mut actions, AppAction::Solve) && !pressed {
+        return;
+    }
+
+    let mut report_text = text_q
+        .get_mut(solver_ui.report_text_entity)
+        .expect("Should be a text");
+
+    let problem = canvas_problem(boundary_box_q.iter());
+
+    if problem.targets.is_empty() {
+        report_text.0 = "Add sink boxes for what the plan should make".into();
+        last_solution.0 = None;
+        return;
+    }
+
+    match solve_production(&problem, &policies, &objective.weights, world.as_ref()) {
+        Ok(solution) => {
+            report_text.0 = solution_report(&solution, world.as_ref());
+
+            if let Some(previous) = &last_solution.0 {
+                let changes = recipe_changes(previous, &solution, world.as_ref());
+
+                if !changes.is_empty() {
+                    report_text.0.push_str("Changes from the last plan:\n");
+                    report_text.0.push_str(&changes);
+                }
+            }
+
+            last_solution.0 = Some(solution);
+        }
+        Err(err) => {
+            report_text.0 = err.to_string();
+            last_solution.0 = None;
+        }
+    }
+}
+
+/// Spawns a box for each recipe in the last solution, selected and linked together
+/// and to the sources and sinks by the flows of the solution.
+#[allow(clippy::too_many_arguments)]
+fn apply_solution(
+    mut commands: Commands,
+    button_q: Query<&Interaction, (Changed<Interaction>, With<ApplySolutionButton>)>,
+    last_solution: Res<LastSolution>,
+    boundary_box_q: Query<(Entity, &BoundaryBox)>,
+    selected_q: Query<Entity, With<SelectedBox>>,
+    plane_q: Query<Entity, With<DragBoxPlane>>,
+    plane_scroll: Res<PlaneScroll>,
+    world: Res<FactoryWorld>,
+) {
+    if !button_q
+        .iter()
+        .any(|interaction| *interaction == Interaction::Pressed)
+    {
+        return;
+    }
+
+    let Some(solution) = &last_solution.0 else {
+        return;
+    };
+
+    let root_plane_entity = plane_q.get_single().expect("Should be one root plane");
+
+    for selected_entity in selected_q.iter() {
+        commands.entity(selected_entity).remove::<SelectedBox>();
+    }
+
+    // what each box makes and takes of each resource in the plan, per minute
+    let mut producers: HashMap<ResourceId, Vec<(Entity, Rational)>> = HashMap::new();
+    let mut consumers: HashMap<ResourceId, Vec<(Entity, Rational)>> = HashMap::new();
+
+    for (index, &(recipe_id, multiplier)) in solution.multipliers.iter().enumerate() {
+        let Ok(recipe) = world.get_recipe(recipe_id) else {
+            continue;
+        };
+
+        let grid_position = Vec2::new(
+            (index % APPLIED_BOX_COLUMNS) as f32,
+            (index / APPLIED_BOX_COLUMNS) as f32,
+        );
+
+        let Ok(box_entity) = spawn_recipe_box(
+            &mut commands,
+            root_plane_entity,
+            -plane_scroll.scroll_position + grid_position * APPLIED_BOX_SPACING,
+            recipe_id,
+            multiplier,
+            world.as_ref(),
+        ) else {
+            continue;
+        };
+
+        commands.entity(box_entity).insert(SelectedBox);
+
+        for (resource_id, rate) in recipe.iter_net_rates() {
+            if rate.is_positive() {
+                producers
+                    .entry(resource_id)
+                    .or_default()
+                    .push((box_entity, rate * multiplier));
+            } else if rate.is_negative() {
+                consumers
+                    .entry(resource_id)
+                    .or_default()
+                    .push((box_entity, -rate * multiplier));
+            }
+        }
+    }
+
+    // sources bring in what the plan imports, as far as they go, outputs take their rate
+    // and the other sinks take what's left over
+    let mut imports: HashMap<ResourceId, Rational> = solution.raw_inputs.iter().copied().collect();
+    let mut surpluses: HashMap<ResourceId, Rational> = solution
+        .surpluses
+        .iter()
+        .map(|&(resource_id, rate, _)| (resource_id, rate))
+        .collect();
+
+    for (box_entity, boundary_box) in boundary_box_q.iter() {
+        let resource_id = boundary_box.resource_id;
+
+        if boundary_box.is_source() {
+            let Some(import) = imports.get_mut(&resource_id) else {
+                continue;
+            };
+
+            let rate = boundary_box.actual_rate().min(*import);
+            *import -= rate;
+
+            if rate.is_positive() {
+                producers
+                    .entry(resource_id)
+                    .or_default()
+                    .push((box_entity, rate));
+            }
+        } else if boundary_box.is_output() {
+            consumers
+                .entry(resource_id)
+                .or_default()
+                .push((box_entity, boundary_box.actual_rate()));
+        } else if let Some(surplus) = surpluses.remove(&resource_id) {
+            consumers
+                .entry(resource_id)
+                .or_default()
+                .push((box_entity, surplus));
+        }
+    }
+
+    // each link carries part of the plan's flow, so the links add up to what each box makes and takes
+    for (resource_id, from_boxes) in producers.iter_mut() {
+        let Some(to_boxes) = consumers.get_mut(resource_id) else {
+            continue;
+        };
+
+        for (from_box, to_box, flow) in pair_flows(from_boxes, to_boxes) {
+            let link_entity = spawn_link(
+                &mut commands,
+                root_plane_entity,
+                BoxLink {
+                    from_box,
+                    to_box,
+                    resource_id: *resource_id,
+                },
+            );
+
+            commands.entity(link_entity).insert(LinkShare(flow));
+        }
+    }
+}
+
+/// Pairs off what the `from_boxes` make with what the `to_boxes` take, in order,
+/// with how much goes from one to the other.
+fn pair_flows(
+    from_boxes: &mut [(Entity, Rational)],
+    to_boxes: &mut [(Entity, Rational)],
+) -> Vec<(Entity, Entity, Rational)> {
+    let mut pairs = Vec::new();
+    let (mut from_index, mut to_index) = (0, 0);
+
+    while let (Some(from), Some(to)) = (from_boxes.get(from_index), to_boxes.get(to_index)) {
+        let flow = from.1.min(to.1);
+
+        if flow.is_positive() {
+            pairs.push((from.0, to.0, flow));
+        }
+
+        from_boxes[from_index].1 -= flow;
+        to_boxes[to_index].1 -= flow;
+
+        if !from_boxes[from_index].1.is_positive() {
+            from_index += 1;
+        }
+
+        if !to_boxes[to_index].1.is_positive() {
+            to_index += 1;
+        }
+    }
+
+    pairs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        boundary_boxes::{BoundaryKind, SinkKind},
+        recipe_json::{LoadOptions, load_recipes},
+    };
+
+    const REFINERY: &str = r#"{"recipes": [
+        {"name": "Refine", "rates": [["Oil", -30], ["Fuel", 20], ["Residue", 10]]},
+        {"name": "Recycle", "rates": [["Residue", -1000], ["Fuel", 1]]}
+    ]}"#;
+
+    fn refinery_world() -> FactoryWorld {
+        let mut world = FactoryWorld::default();
+        load_recipes(&mut world, REFINERY, LoadOptions::default()).unwrap();
+        world
+    }
+
+    fn solve_fuel(world: &mut FactoryWorld, residue_policy: ByproductPolicy) -> ProductionSolution {
+        let fuel = world.get_resource_id("Fuel");
+        let residue = world.get_resource_id("Residue");
+
+        let problem = ProductionProblem {
+            targets: HashMap::from([(fuel, Rational::from_integer(20))]),
+            raw_limits: HashMap::new(),
+        };
+        let policies = ByproductPolicies(HashMap::from([(residue, residue_policy)]));
+
+        solve_production(
+            &problem,
+            &policies,
+            &ObjectiveWeights::only(ObjectiveMetric::Buildings),
+            world,
+        )
+        .unwrap()
+    }
+
+    fn uses_recipe(world: &mut FactoryWorld, solution: &ProductionSolution, name: &str) -> bool {
+        let recipe_id = world.get_recipe_id(name).unwrap();
+        solution.multipliers.iter().any(|&(id, _)| id == recipe_id)
+    }
+
+    #[test]
+    fn accumulated_byproducts_are_free() {
+        let mut world = refinery_world();
+        let solution = solve_fuel(&mut world, ByproductPolicy::Accumulate);
+
+        assert!(!uses_recipe(&mut world, &solution, "Recycle"));
+        assert_eq!(solution.building_count, Rational::ONE);
+        assert_eq!(solution.sink_count, Rational::ZERO);
+        assert_eq!(solution.surpluses.len(), 1);
+    }
+
+    #[test]
+    fn consumed_byproducts_are_used_up() {
+        let mut world = refinery_world();
+        let solution = solve_fuel(&mut world, ByproductPolicy::Consume);
+
+        assert!(uses_recipe(&mut world, &solution, "Recycle"));
+        assert!(solution.surpluses.is_empty());
+    }
+
+    #[test]
+    fn only_output_boxes_are_targets() {
+        let mut world = FactoryWorld::default();
+        let fuel = world.get_resource_id("Fuel");
+        let residue = world.get_resource_id("Residue");
+
+        let sink = |sink_kind, resource_id| BoundaryBox {
+            kind: BoundaryKind::Sink(sink_kind),
+            resource_id,
+            rate: Rational::from_integer(20),
+            uses_purity: false,
+        };
+
+        let problem = canvas_problem(
+            [
+                sink(SinkKind::Output, fuel),
+                sink(SinkKind::AwesomeSink, residue),
+                sink(SinkKind::Storage, fuel),
+            ]
+            .iter(),
+        );
+
+        assert_eq!(
+            problem.targets,
+            HashMap::from([(fuel, Rational::from_integer(20))])
+        );
+        assert!(problem.raw_limits.is_empty());
+    }
+
+    #[test]
+    fn sinking_costs_buildings_and_power() {
+        let mut world = refinery_world();
+        let residue = world.get_resource_id("Residue");
+        let mut policies = ByproductPolicies::default();
+        policies.0.insert(residue, ByproductPolicy::Sink);
+
+        // with nothing else to do with the residue, it all goes into sinks
+        let problem = ProductionProblem {
+            targets: HashMap::from([(world.get_resource_id("Fuel"), Rational::from_integer(20))]),
+            raw_limits: HashMap::new(),
+        };
+        let refine_only = world.get_recipe_id("Refine").unwrap();
+        let solution = solve_with_recipes(
+            &problem,
+            &policies,
+            &ObjectiveWeights::only(ObjectiveMetric::Buildings),
+            &world,
+            |recipe_id| recipe_id == refine_only,
+        )
+        .unwrap();
+
+        let sink_count = Rational::new(10, SINK_INTAKE);
+        assert_eq!(solution.sink_count, sink_count);
+        assert_eq!(solution.building_count, Rational::ONE + sink_count);
+        assert_eq!(
+            solution.power_used,
+            sink_count * Rational::from_integer(SINK_POWER)
+        );
+        assert_eq!(
+            solution.surpluses,
+            vec![(residue, Rational::from_integer(10), ByproductPolicy::Sink)]
+        );
+
+        // recycling takes fewer buildings than sinking, so it's used when it can be
+        let solution = solve_fuel(&mut world, ByproductPolicy::Sink);
+        assert!(uses_recipe(&mut world, &solution, "Recycle"));
+        assert_eq!(solution.sink_count, Rational::ZERO);
+    }
+
+    #[test]
+    fn recipe_weights_leave_the_sensitivity_alone() {
+        let mut world = refinery_world();
+        let fuel = world.get_resource_id("Fuel");
+        let problem = ProductionProblem {
+            targets: HashMap::from([(fuel, Rational::from_integer(40))]),
+            raw_limits: HashMap::new(),
+        };
+        let policies = ByproductPolicies::default();
+
+        let mut weights = ObjectiveWeights::only(ObjectiveMetric::Buildings);
+        let buildings_only = solve_production(&problem, &policies, &weights, &world).unwrap();
+
+        weights.recipes = Rational::from_integer(5);
+        let with_recipes = solve_production(&problem, &policies, &weights, &world).unwrap();
+
+        assert_eq!(with_recipes.multipliers, buildings_only.multipliers);
+        assert_eq!(
+            with_recipes.objective,
+            buildings_only.objective + Rational::from_integer(5)
+        );
+        assert_eq!(
+            with_recipes.sensitivity.marginal_cost(fuel),
+            buildings_only.sensitivity.marginal_cost(fuel)
+        );
+    }
+
+    #[test]
+    fn extra_outputs_come_from_raising_used_up_limits() {
+        let mut world = refinery_world();
+        let oil = world.get_resource_id("Oil");
+        let fuel = world.get_resource_id("Fuel");
+        let residue = world.get_resource_id("Residue");
+
+        // residue can only come from refining, or there'd be no end to the fuel
+        let problem = ProductionProblem {
+            targets: HashMap::from([(fuel, Rational::from_integer(20))]),
+            raw_limits: HashMap::from([
+                (oil, Rational::from_integer(30)),
+                (residue, Rational::ZERO),
+            ]),
+        };
+
+        let solution = solve_production(
+            &problem,
+            &ByproductPolicies::default(),
+            &ObjectiveWeights::only(ObjectiveMetric::Buildings),
+            &world,
+        )
+        .unwrap();
+
+        // two thirds of a fuel from refining each oil, and a little more from recycling the residue
+        assert_eq!(
+            solution.sensitivity.extra_outputs,
+            vec![
+                (oil, fuel, Rational::new(2, 3) + Rational::new(1, 3000)),
+                (residue, fuel, Rational::new(1, 1000)),
+            ]
+        );
+    }
+
+    #[test]
+    fn flows_are_paired_off_in_order() {
+        let [a, b, c, d] = [0, 1, 2, 3].map(Entity::from_raw);
+        let int = Rational::from_integer;
+
+        let pairs = pair_flows(
+            &mut [(a, int(30)), (b, int(30))],
+            &mut [(c, int(40)), (d, int(20))],
+        );
+
+        assert_eq!(
+            pairs,
+            vec![(a, c, int(30)), (b, c, int(10)), (b, d, int(20))]
+        );
+    }
+}
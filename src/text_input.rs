@@ -0,0 +1,162 @@
+use bevy::{
+    input::{
+        ButtonState,
+        keyboard::{Key, KeyboardInput},
+    },
+    prelude::*,
+};
+
+pub fn build(app: &mut App) {
+    app.insert_resource(FocusedTextInput(None));
+    app.add_event::<TextInputSubmitted>();
+
+    app.add_systems(
+        Update,
+        (focus_text_inputs, type_text_inputs, update_text_input_text)
+            .chain()
+            .in_set(TextInputSystems),
+    );
+}
+
+/// Systems that focus text inputs and type into them.
+///
+/// Systems that focus an input from a key press should run after these,
+/// so the key that focused it isn't typed into it.
+#[derive(SystemSet, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TextInputSystems;
+
+/// A single line text field. Click it to focus, press enter to submit.
+#[derive(Component, Default)]
+#[require(Button, Text)]
+pub struct TextInput {
+    pub value: String,
+    /// Shown while the field is empty and not focused.
+    pub placeholder: String,
+}
+
+impl TextInput {
+    pub fn new(placeholder: impl Into<String>) -> Self {
+        TextInput {
+            value: String::new(),
+            placeholder: placeholder.into(),
+        }
+    }
+}
+
+/// The text input currently receiving keyboard input, if any.
+///
+/// Other systems reading the keyboard should ignore it while this is `Some`.
+#[derive(Resource)]
+pub struct FocusedTextInput(pub Option<Entity>);
+
+/// Sent when enter is pressed in a focused text input.
+#[derive(Event)]
+pub struct TextInputSubmitted {
+    pub entity: Entity,
+    pub value: String,
+}
+
+fn focus_text_inputs(
+    mouse_input: Res<ButtonInput<MouseButton>>,
+    input_q: Query<(Entity, &Interaction), With<TextInput>>,
+    mut focused: ResMut<FocusedTextInput>,
+) {
+    if !mouse_input.just_pressed(MouseButton::Left) {
+        return;
+    }
+
+    let pressed_input = input_q
+        .iter()
+        .find(|(_, interaction)| **interaction == Interaction::Pressed)
+        .map(|(entity, _)| entity);
+
+    if focused.0 != pressed_input {
+        focused.0 = pressed_input;
+    }
+}
+
+fn type_text_inputs(
+    mut keyboard_events: EventReader<KeyboardInput>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut input_q: Query<&mut TextInput>,
+    mut focused: ResMut<FocusedTextInput>,
+    mut submitted_events: EventWriter<TextInputSubmitted>,
+) {
+    let Some(focused_entity) = focused.0 else {
+        keyboard_events.clear();
+        return;
+    };
+
+    let Ok(mut input) = input_q.get_mut(focused_entity) else {
+        focused.0 = None;
+        return;
+    };
+
+    // keys pressed with these are shortcuts, not text
+    let shortcut_modifier = keyboard_input.any_pressed([
+        KeyCode::ControlLeft,
+        KeyCode::ControlRight,
+        KeyCode::AltLeft,
+        KeyCode::AltRight,
+        KeyCode::SuperLeft,
+        KeyCode::SuperRight,
+    ]);
+
+    for event in keyboard_events.read() {
+        if event.state != ButtonState::Pressed {
+            continue;
+        }
+
+        if shortcut_modifier && matches!(event.logical_key, Key::Character(_) | Key::Space) {
+            continue;
+        }
+
+        match &event.logical_key {
+            Key::Character(characters) => {
+                input
+                    .value
+                    .extend(characters.chars().filter(|c| !c.is_control()));
+            }
+            Key::Space => input.value.push(' '),
+            Key::Backspace => {
+                input.value.pop();
+            }
+            Key::Enter => {
+                submitted_events.send(TextInputSubmitted {
+                    entity: focused_entity,
+                    value: input.value.clone(),
+                });
+            }
+            Key::Escape => {
+                focused.0 = None;
+                break;
+            }
+            _ => (),
+        }
+    }
+}
+
+fn update_text_input_text(
+    mut input_q: Query<(Entity, &TextInput, &mut Text, &mut TextColor)>,
+    focused: Res<FocusedTextInput>,
+) {
+    for (input_entity, input, mut text, mut text_color) in input_q.iter_mut() {
+        let is_focused = focused.0 == Some(input_entity);
+
+        let (new_text, new_color) = if is_focused {
+            (format!("{}|", input.value), Color::WHITE)
+        } else if input.value.is_empty() {
+            (input.placeholder.clone(), Color::srgb(0.5, 0.5, 0.5))
+        } else {
+            (input.value.clone(), Color::WHITE)
+        };
+
+        if text.0 != new_text {
+            text.0 = new_text;
+        }
+
+        if text_color.0 != new_color {
+            text_color.0 = new_color;
+        }
+    }
+}